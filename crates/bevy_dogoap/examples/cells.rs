@@ -61,19 +61,19 @@ fn spawn_cell(commands: &mut Commands, position: Vec3, speed: f32) {
         .add_precondition(AtFood::is(true))
         .add_mutator(Hunger::decrease(10.0))
         .add_mutator(AtFood::set(true))
-        .set_cost(1);
+        .set_cost(1.0);
 
     let replicate_action = ReplicateAction::new()
         .add_precondition(Hunger::is_less(10.0))
         .add_mutator(IsReplicating::set(true))
         .add_mutator(Hunger::increase(25.0))
-        .set_cost(10);
+        .set_cost(10.0);
 
     let go_to_food_action = GoToFoodAction::new()
         .add_precondition(AtFood::is(false))
         .add_mutator(AtFood::set(true))
         .add_mutator(Hunger::increase(1.0))
-        .set_cost(2);
+        .set_cost(2.0);
 
     let mut rng = rand::thread_rng();
     let starting_hunger = rng.gen_range(20.0..45.0);
@@ -1,5 +1,7 @@
 use bevy::{color::palettes::css::*, prelude::*, time::common_conditions::on_timer};
 use bevy_dogoap::prelude::*;
+// Not in the prelude as it would clash with the Condition trait of Bevy
+use dogoap::prelude::Condition;
 use rand::Rng;
 use std::{collections::HashMap, time::Duration};
 
@@ -29,6 +31,22 @@ enum Location {
     Merchant,
 }
 
+impl Location {
+    /// The other way around from `EnumDatum::datum`, for reading what the planner picked
+    fn from_datum(datum: Datum) -> Option<Self> {
+        [
+            Location::House,
+            Location::Outside,
+            Location::Mushroom,
+            Location::Ore,
+            Location::Smelter,
+            Location::Merchant,
+        ]
+        .into_iter()
+        .find(|location| location.datum() == datum)
+    }
+}
+
 /// This is our marker components, so we can keep track of the various in-game entities
 #[derive(Component)]
 struct Miner;
@@ -68,20 +86,9 @@ struct SellMetalAction;
 #[derive(Component, Clone, Reflect, Default, ActionComponent)]
 struct GoToOutsideAction;
 
+/// Goes to whatever `location` the planner picked, see `handle_go_to_action`
 #[derive(Component, Clone, Reflect, Default, ActionComponent)]
-struct GoToHouseAction;
-
-#[derive(Component, Clone, Reflect, Default, ActionComponent)]
-struct GoToMushroomAction;
-
-#[derive(Component, Clone, Reflect, Default, ActionComponent)]
-struct GoToOreAction;
-
-#[derive(Component, Clone, Reflect, Default, ActionComponent)]
-struct GoToSmelterAction;
-
-#[derive(Component, Clone, Reflect, Default, ActionComponent)]
-struct GoToMerchantAction;
+struct GoToAction;
 
 // All of our State fields
 
@@ -115,21 +122,21 @@ fn startup(mut commands: Commands, windows: Query<&Window>) {
             .add_precondition(Energy::is_less(50.0))
             .add_precondition(AtLocation::is(Location::House))
             .add_mutator(Energy::increase(100.0))
-            .set_cost(1);
+            .set_cost(1.0);
 
         let eat_action = EatAction::new()
             .add_precondition(Hunger::is_more(50.0))
             .add_precondition(AtLocation::is(Location::Mushroom))
             .add_mutator(Hunger::decrease(25.0))
             .add_mutator(AtLocation::set(Location::Outside))
-            .set_cost(2);
+            .set_cost(2.0);
 
         let mine_ore_action = MineOreAction::new()
             .add_precondition(Energy::is_more(10.0))
             .add_precondition(Hunger::is_less(75.0))
             .add_precondition(AtLocation::is(Location::Ore))
             .add_mutator(HasOre::set(true))
-            .set_cost(3);
+            .set_cost(3.0);
 
         let smelt_ore_action = SmeltOreAction::new()
             .add_precondition(Energy::is_more(10.0))
@@ -138,7 +145,7 @@ fn startup(mut commands: Commands, windows: Query<&Window>) {
             .add_precondition(HasOre::is(true))
             .add_mutator(HasOre::set(false))
             .add_mutator(HasMetal::set(true))
-            .set_cost(4);
+            .set_cost(4.0);
 
         let sell_metal_action = SellMetalAction::new()
             .add_precondition(Energy::is_more(10.0))
@@ -147,36 +154,38 @@ fn startup(mut commands: Commands, windows: Query<&Window>) {
             .add_precondition(HasMetal::is(true))
             .add_mutator(GoldAmount::increase(1))
             .add_mutator(HasMetal::set(false))
-            .set_cost(5);
+            .set_cost(5.0);
 
         let go_to_outside_action = GoToOutsideAction::new()
             .add_mutator(AtLocation::set(Location::Outside))
-            .set_cost(1);
-
-        let go_to_house_action = GoToHouseAction::new()
+            .set_cost(1.0);
+
+        // One action for all the places we can go to from outside, the planner picks where
+        let mut go_to_action = GoToAction::new()
+            .with_parameter(
+                "location",
+                [
+                    Location::House,
+                    Location::Mushroom,
+                    Location::Ore,
+                    Location::Smelter,
+                    Location::Merchant,
+                ]
+                .map(Location::datum),
+            )
             .add_precondition(AtLocation::is(Location::Outside))
-            .add_mutator(AtLocation::set(Location::House))
-            .set_cost(1);
-
-        let go_to_mushroom_action = GoToMushroomAction::new()
-            .add_precondition(AtLocation::is(Location::Outside))
-            .add_mutator(AtLocation::set(Location::Mushroom))
-            .set_cost(2);
-
-        let go_to_ore_action = GoToOreAction::new()
-            .add_precondition(AtLocation::is(Location::Outside))
-            .add_mutator(AtLocation::set(Location::Ore))
-            .set_cost(3);
-
-        let go_to_smelter_action = GoToSmelterAction::new()
-            .add_precondition(AtLocation::is(Location::Outside))
-            .add_mutator(AtLocation::set(Location::Smelter))
-            .set_cost(4);
-
-        let go_to_merchant_action = GoToMerchantAction::new()
-            .add_precondition(AtLocation::is(Location::Outside))
-            .add_mutator(AtLocation::set(Location::Merchant))
-            .set_cost(5);
+            .add_mutator(AtLocation::copy_from("location"))
+            .set_cost(1.0);
+        // The further away, the more it costs to get there
+        for (location, cost) in [
+            (Location::Mushroom, 2.0),
+            (Location::Ore, 3.0),
+            (Location::Smelter, 4.0),
+            (Location::Merchant, 5.0),
+        ] {
+            let going_there = Condition::compare("location", Compare::Equals(location.datum()));
+            go_to_action = go_to_action.with_cost_when(going_there, cost);
+        }
 
         let (mut planner, components) = create_planner!({
             actions: [
@@ -187,11 +196,7 @@ fn startup(mut commands: Commands, windows: Query<&Window>) {
                 (SellMetalAction, sell_metal_action),
                 //
                 (GoToOutsideAction, go_to_outside_action),
-                (GoToHouseAction, go_to_house_action),
-                (GoToMushroomAction, go_to_mushroom_action),
-                (GoToOreAction, go_to_ore_action),
-                (GoToSmelterAction, go_to_smelter_action),
-                (GoToMerchantAction, go_to_merchant_action),
+                (GoToAction, go_to_action),
             ],
             state: [GoldAmount(0), Hunger(25.0), Energy(75.0), AtLocation(Location::Outside), HasOre(false), HasMetal(false)],
             goals: [gold_goal],
@@ -351,49 +356,79 @@ fn go_to_location<T>(
     }
 }
 
-fn handle_go_to_house_action(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &GoToHouseAction, &mut Transform, &mut AtLocation), Without<House>>,
-    q_house: Query<&Transform, With<House>>,
-) {
-    for (entity, _action, mut t_entity, mut at_location) in query.iter_mut() {
-        let t_house = q_house
-            .get_single()
-            .expect("There should only be one house!");
-
-        go_to_location::<GoToHouseAction>(
-            &mut at_location,
-            time.delta_seconds(),
-            &mut t_entity,
-            t_house.translation,
-            Location::House,
-            entity,
-            &mut commands,
-        )
-    }
-}
-
-fn handle_go_to_smelter_action(
+fn handle_go_to_action(
     mut commands: Commands,
     time: Res<Time>,
     mut query: Query<
-        (Entity, &GoToSmelterAction, &mut Transform, &mut AtLocation),
-        Without<Smelter>,
+        (
+            Entity,
+            &GoToAction,
+            &Planner,
+            &mut Transform,
+            &mut AtLocation,
+        ),
+        (
+            Without<House>,
+            Without<Smelter>,
+            Without<Merchant>,
+            Without<Mushroom>,
+            Without<Ore>,
+        ),
     >,
+    q_house: Query<&Transform, With<House>>,
     q_smelter: Query<&Transform, With<Smelter>>,
+    q_merchant: Query<&Transform, With<Merchant>>,
+    q_mushrooms: Query<(Entity, &Transform), With<Mushroom>>,
+    q_ore: Query<(Entity, &Transform), With<Ore>>,
 ) {
-    for (entity, _action, mut t_entity, mut at_location) in query.iter_mut() {
-        let t_smelter = q_smelter
-            .get_single()
-            .expect("There should only be one smelter!");
+    for (entity, _action, planner, mut t_entity, mut at_location) in query.iter_mut() {
+        // The planner tells us where it wanted us to go
+        let location = planner
+            .argument("location")
+            .and_then(Location::from_datum)
+            .expect("GoToAction should always be planned with a location");
 
-        go_to_location::<GoToSmelterAction>(
+        let origin = t_entity.translation;
+        let destination = match location {
+            Location::House => {
+                q_house
+                    .get_single()
+                    .expect("There should only be one house!")
+                    .translation
+            }
+            Location::Smelter => {
+                q_smelter
+                    .get_single()
+                    .expect("There should only be one smelter!")
+                    .translation
+            }
+            Location::Merchant => {
+                q_merchant
+                    .get_single()
+                    .expect("There should only be one merchant!")
+                    .translation
+            }
+            Location::Mushroom => {
+                let items = q_mushrooms.iter().map(|(e, t)| (e, *t)).collect();
+                find_closest(origin, items)
+                    .expect("No mushroom could be found, HOW?!")
+                    .1
+            }
+            Location::Ore => {
+                let items = q_ore.iter().map(|(e, t)| (e, *t)).collect();
+                find_closest(origin, items)
+                    .expect("No closest could be found, HOW?!")
+                    .1
+            }
+            Location::Outside => unreachable!("Going outside is done by GoToOutsideAction"),
+        };
+
+        go_to_location::<GoToAction>(
             &mut at_location,
             time.delta_seconds(),
             &mut t_entity,
-            t_smelter.translation,
-            Location::Smelter,
+            destination,
+            location,
             entity,
             &mut commands,
         )
@@ -427,92 +462,6 @@ fn handle_go_to_outside_action(
     }
 }
 
-fn handle_go_to_merchant_action(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<
-        (Entity, &GoToMerchantAction, &mut Transform, &mut AtLocation),
-        Without<Merchant>,
-    >,
-    q_destination: Query<&Transform, With<Merchant>>,
-) {
-    for (entity, _action, mut t_entity, mut at_location) in query.iter_mut() {
-        let t_destination = q_destination
-            .get_single()
-            .expect("There should only be one merchant!");
-
-        go_to_location::<GoToMerchantAction>(
-            &mut at_location,
-            time.delta_seconds(),
-            &mut t_entity,
-            t_destination.translation,
-            Location::Merchant,
-            entity,
-            &mut commands,
-        )
-    }
-}
-
-fn handle_go_to_mushroom_action(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<
-        (Entity, &GoToMushroomAction, &mut Transform, &mut AtLocation),
-        Without<Mushroom>,
-    >,
-    q_mushrooms: Query<(Entity, &Transform), With<Mushroom>>,
-) {
-    for (entity, _action, mut t_entity, mut at_location) in query.iter_mut() {
-        let origin = t_entity.translation;
-        let items: Vec<(Entity, Transform)> = q_mushrooms.iter().map(|(e, t)| (e, *t)).collect();
-        let mushroom = find_closest(origin, items);
-
-        let mushroom = match mushroom {
-            Some(v) => v,
-            None => panic!("No mushroom could be found, HOW?!"),
-        };
-
-        go_to_location::<GoToMushroomAction>(
-            &mut at_location,
-            time.delta_seconds(),
-            &mut t_entity,
-            mushroom.1,
-            Location::Mushroom,
-            entity,
-            &mut commands,
-        )
-    }
-}
-
-fn handle_go_to_ore_action(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &GoToOreAction, &mut Transform, &mut AtLocation), Without<Ore>>,
-    q_world_resource: Query<(Entity, &Transform), With<Ore>>,
-) {
-    for (entity, _action, mut t_entity, mut at_location) in query.iter_mut() {
-        let origin = t_entity.translation;
-        let items: Vec<(Entity, Transform)> =
-            q_world_resource.iter().map(|(e, t)| (e, *t)).collect();
-        let closest = find_closest(origin, items);
-
-        let closest = match closest {
-            Some(v) => v,
-            None => panic!("No closest could be found, HOW?!"),
-        };
-
-        go_to_location::<GoToOreAction>(
-            &mut at_location,
-            time.delta_seconds(),
-            &mut t_entity,
-            closest.1,
-            Location::Ore,
-            entity,
-            &mut commands,
-        )
-    }
-}
-
 // Helper function that figures out what (Entity, Transform) tuple is the closest to our origin
 fn find_closest(origin: Vec3, items: Vec<(Entity, Transform)>) -> Option<(Entity, Vec3)> {
     let mut closest: Option<(Entity, Transform, f32)> = None;
@@ -810,12 +759,9 @@ fn print_current_local_state(
         Option<&MineOreAction>,
         Option<&SmeltOreAction>,
         Option<&SellMetalAction>,
-        Option<&GoToHouseAction>,
         Option<&GoToOutsideAction>,
-        Option<&GoToMushroomAction>,
-        Option<&GoToOreAction>,
-        Option<&GoToSmelterAction>,
-        Option<&GoToMerchantAction>,
+        Option<&GoToAction>,
+        &Planner,
     )>,
     // action_query: Query<&dyn ActionComponent>,
     mut q_child: Query<&mut Text, With<NeedsText>>,
@@ -829,19 +775,8 @@ fn print_current_local_state(
 
         let mut current_action = "Idle";
 
-        let (
-            sleep,
-            eat,
-            mine,
-            smelting,
-            selling_metal,
-            go_to_house,
-            go_to_outside,
-            go_to_mushroom,
-            go_to_ore,
-            go_to_smelter,
-            go_to_merchant,
-        ) = q_actions.get(entity).unwrap();
+        let (sleep, eat, mine, smelting, selling_metal, go_to_outside, go_to, planner) =
+            q_actions.get(entity).unwrap();
 
         if sleep.is_some() {
             current_action = "Sleeping";
@@ -863,28 +798,19 @@ fn print_current_local_state(
             current_action = "Selling metal";
         }
 
-        if go_to_house.is_some() {
-            current_action = "Going to house";
-        }
-
         if go_to_outside.is_some() {
             current_action = "Going to outside";
         }
 
-        if go_to_mushroom.is_some() {
-            current_action = "Going to mushroom";
-        }
-
-        if go_to_ore.is_some() {
-            current_action = "Going to ore";
-        }
-
-        if go_to_smelter.is_some() {
-            current_action = "Going to smelter";
-        }
-
-        if go_to_merchant.is_some() {
-            current_action = "Going to merchant";
+        if go_to.is_some() {
+            current_action = match planner.argument("location").and_then(Location::from_datum) {
+                Some(Location::House) => "Going to house",
+                Some(Location::Mushroom) => "Going to mushroom",
+                Some(Location::Ore) => "Going to ore",
+                Some(Location::Smelter) => "Going to smelter",
+                Some(Location::Merchant) => "Going to merchant",
+                Some(Location::Outside) | None => "Going somewhere",
+            };
         }
 
         for &child in children.iter() {
@@ -967,11 +893,7 @@ fn main() {
         FixedUpdate,
         (
             handle_go_to_outside_action,
-            handle_go_to_house_action,
-            handle_go_to_mushroom_action,
-            handle_go_to_ore_action,
-            handle_go_to_smelter_action,
-            handle_go_to_merchant_action,
+            handle_go_to_action,
             handle_sleep_action,
             handle_eat_action,
             handle_mine_ore_action,
@@ -173,7 +173,7 @@ struct Customer {
 #[derive(Component)]
 struct Worker;
 
-#[derive(Clone, Default, Copy, Reflect)]
+#[derive(Clone, Default, Copy, Reflect, EnumDatum)]
 enum Item {
     #[default]
     Nothing,
@@ -703,6 +703,7 @@ fn draw_state_debug(
                         Datum::F64(v) => format!("{:.2}", v).to_string(),
                         Datum::I64(v) => format!("{}", v).to_string(),
                         Datum::Enum(v) => format!("{}", v).to_string(),
+                        Datum::Symbol(v) => v.to_string(),
                     }
                 );
             }
@@ -1,13 +1,12 @@
 #![cfg_attr(rustfmt, rustfmt_skip)]
 use std::collections::VecDeque;
 
-use std::{collections::HashMap, fmt};
+use std::{collections::HashMap, fmt, sync::{Arc, Mutex, MutexGuard}, time::Duration};
 
 use bevy::prelude::*;
 
 #[cfg(feature = "compute-pool")]
 use {
-    std::time::Instant,
     bevy::tasks::futures_lite::future,
     bevy::tasks::{AsyncComputeTaskPool, Task}
 };
@@ -34,9 +33,19 @@ pub struct Planner {
     pub current_goal: Option<Goal>,
     /// What [`Action`] we're currrently trying to execute
     pub current_action: Option<Action>,
+    /// The values the planner chose for the parameters of `current_action`, see
+    /// [`Planner::argument`]
+    pub current_arguments: Vec<(String, Datum)>,
 
     // queue of action keys, first is current
     pub current_plan: VecDeque<String>,
+    /// The [`Plan`] the actions in `current_plan` come from, to check with [`Plan::validate`]
+    /// if it can still be followed
+    pub plan: Option<Plan>,
+    /// The goal `plan` was made for, so it isn't repaired after `current_goal` changed
+    plan_goal: Option<Goal>,
+    /// The goal of the plan that is being computed right now
+    planning_goal: Option<Goal>,
 
     // TODO figure out how to get reflect to work, if possible
     #[reflect(ignore)]
@@ -53,6 +62,31 @@ pub struct Planner {
     /// then turn it to false. Combine with always_plan set to false and you can manually decide when
     /// new plans should be made.
     pub plan_next_tick: bool,
+    /// Stop planning after expanding this many nodes, to keep the worst-case planning time bounded
+    pub max_expanded_nodes: Option<usize>,
+    /// Stop planning once it has been running for this long
+    pub planning_timeout: Option<Duration>,
+    /// If the Planner should follow the plan that got closest to the goal, when it
+    /// couldn't find one that reaches it
+    pub accept_partial_plans: bool,
+    /// What mutators like `Increment`, preconditions and goals do with keys that aren't in the
+    /// state while planning
+    pub missing_keys: MissingKeyBehavior,
+    /// Decides what each [`Action`] costs in the current state, for costs that come from the
+    /// world rather than the [`LocalState`], like the real distance to walk somewhere. Planning
+    /// happens away from the [`World`], so keep whatever it needs inside of it, updated from
+    /// your own systems
+    #[reflect(ignore)]
+    pub cost_model: Option<Arc<dyn CostModel>>,
+    /// How [`Action`]s with more than one possible outcome are planned with
+    pub outcomes: OutcomeMode,
+    /// If the Planner should repair the previous `plan` with [`repair_plan`] instead of
+    /// planning from scratch every time, which is a lot cheaper as long as the plan can
+    /// mostly still be followed, but won't always find the cheapest plan. Off by default
+    pub repair_plans: bool,
+    /// How finely the floats of each key are told apart while planning, filled in from the
+    /// `#[datum(step = ...)]` of the [`DatumComponent`]s
+    pub quantization: Quantization,
 
     /// Internal prepared vector of just [`Action`]
     actions_for_dogoap: Vec<Action>,
@@ -76,7 +110,26 @@ struct Task<T>(T);
 /// We do it in a asyncronous manner as make_plan blocks and if it takes 100ms, we'll delay frames
 /// by 100ms...
 #[derive(Component)]
-pub struct ComputePlan(Task<Option<(Vec<dogoap::prelude::Node>, usize)>>);
+pub struct ComputePlan(Task<Result<Plan, PlanError>>);
+
+/// A [`PlanCache`] shared by every [`Planner`], so Entities with the same actions that end
+/// up in the same state reuse the plans made for each other. Planners only use it if it has
+/// been added with `app.insert_resource(SharedPlanCache::new(capacity))`
+#[derive(Resource, Clone)]
+pub struct SharedPlanCache(pub Arc<Mutex<PlanCache>>);
+
+impl SharedPlanCache {
+    pub fn new(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(PlanCache::new(capacity))))
+    }
+
+    /// The cache itself, like for checking how many plans were reused with
+    /// [`PlanCache::hits`]. Planners wait for it while it's locked, so keep it short
+    pub fn lock(&self) -> MutexGuard<'_, PlanCache> {
+        // The cache is still fine to use after a planner panicked while holding the lock
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
 
 /// This Component gets added when the planner for an Entity is currently planning,
 /// and removed once a plan has been created. Normally this will take under 1ms,
@@ -94,11 +147,15 @@ impl Planner {
         }
 
         let mut state = LocalState::new();
+        let mut quantization = Quantization::new();
 
         for component in components.iter() {
             state
                 .data
                 .insert(component.field_key(), component.field_value());
+            if let Some(step) = component.quantization_step() {
+                quantization = quantization.with_step(&component.field_key(), step);
+            }
         }
 
         Self {
@@ -108,13 +165,50 @@ impl Planner {
             goals,
             actions_map,
             current_action: None,
+            current_arguments: vec![],
             current_plan: VecDeque::new(),
+            plan: None,
+            plan_goal: None,
+            planning_goal: None,
             always_plan: true,
             remove_goal_on_no_plan_found: true,
             plan_next_tick: false,
+            max_expanded_nodes: None,
+            planning_timeout: None,
+            accept_partial_plans: false,
+            missing_keys: MissingKeyBehavior::default(),
+            cost_model: None,
+            outcomes: OutcomeMode::default(),
+            repair_plans: false,
+            quantization,
             actions_for_dogoap,
         }
     }
+
+    /// The value chosen for the parameter called `name` of the current action, like where
+    /// to go for an action with a `location` parameter
+    pub fn argument(&self, name: &str) -> Option<Datum> {
+        self.current_arguments
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// The [`PlanOptions`] to plan with, based on `max_expanded_nodes`, `planning_timeout`,
+    /// `accept_partial_plans`, `missing_keys`, `cost_model`, `outcomes` and `quantization`
+    pub fn plan_options(&self) -> PlanOptions {
+        let mut options = PlanOptions::new()
+            .with_partial_plans(self.accept_partial_plans)
+            .with_missing_keys(self.missing_keys)
+            .with_outcomes(self.outcomes);
+        options.max_expanded_nodes = self.max_expanded_nodes;
+        options.cost_model = self.cost_model.clone();
+        options.quantization = self.quantization.clone();
+        if let Some(timeout) = self.planning_timeout {
+            options = options.with_timeout(timeout);
+        }
+        options
+    }
 }
 
 /// This system "syncs" our [`DatumComponent`]s with the LocalState in the [`Planner`]
@@ -137,35 +231,35 @@ pub fn update_planner_local_state(
 /// and creates a new task for generating a new plan
 pub fn create_planner_tasks(
     mut commands: Commands,
-    query: Query<(Entity, &Planner), Without<ComputePlan>>,
+    mut query: Query<(Entity, &mut Planner), Without<ComputePlan>>,
+    cache: Option<Res<SharedPlanCache>>,
 ) {
     #[cfg(feature = "compute-pool")]
     let thread_pool = AsyncComputeTaskPool::get();
 
-    for (entity, planner) in query.iter() {
+    for (entity, mut planner) in query.iter_mut() {
+        // A plan for another goal is no use for repairing, so forget it once the goal changed
+        if planner.plan.is_some() && planner.plan_goal != planner.current_goal {
+            planner.plan = None;
+            planner.plan_goal = None;
+        }
         if planner.always_plan {
             if let Some(goal) = planner.current_goal.clone() {
                 let state = planner.state.clone();
                 let actions = planner.actions_for_dogoap.clone();
+                let options = planner.plan_options();
+                let previous = planner.plan.clone().filter(|_| planner.repair_plans);
+                let cache = cache.as_deref().cloned();
+                planner.planning_goal = Some(goal.clone());
 
                 #[cfg(feature = "compute-pool")]
                 let task = thread_pool.spawn(async move {
-                    let start = Instant::now();
-
                     // WARN this is the part that can be slow for large search spaces and why we use AsyncComputePool
-                    let plan = make_plan(&state, &actions[..], &goal);
-                    let duration_ms = start.elapsed().as_millis();
-
-                    if duration_ms > 10 {
-                        let steps = plan.clone().expect("plan was empty?!").0.len(); // Not very clever to clone if things are slow?
-                        warn!("Planning duration for Entity {entity} was {duration_ms}ms for {steps} steps");
-                    }
-
-                    plan
+                    compute_plan(entity, &options, previous.as_ref(), cache, &state, &actions, &goal)
                 });
 
                 #[cfg(not(feature = "compute-pool"))]
-                let task = Task(make_plan(&state, &actions[..], &goal));
+                let task = Task(compute_plan(entity, &options, previous.as_ref(), cache, &state, &actions, &goal));
 
                 commands
                     .entity(entity)
@@ -175,10 +269,58 @@ pub fn create_planner_tasks(
     }
 }
 
+/// Takes the plan from the `cache` if it has one. Otherwise repairs the `previous` plan if
+/// there is one, or plans from scratch, and caches the result
+fn compute_plan(
+    entity: Entity,
+    options: &PlanOptions,
+    previous: Option<&Plan>,
+    cache: Option<SharedPlanCache>,
+    state: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+) -> Result<Plan, PlanError> {
+    // Only locked while looking up and inserting, so other planners don't wait for planning
+    if let Some(plan) = cache.as_ref().and_then(|cache| cache.lock().get(options, state, actions, goal)) {
+        debug!("Reusing cached plan for Entity {entity} with {} steps", plan.steps.len());
+        return Ok(plan);
+    }
+    let (plan, stats) = match previous {
+        Some(previous) => repair_plan_with_stats(options, previous, state, actions, goal),
+        None => make_plan_with_stats(options, state, actions, goal),
+    };
+    report_plan_stats(entity, &plan, &stats);
+    if let (Some(cache), Ok(plan)) = (&cache, &plan) {
+        cache.lock().insert(options, state, actions, goal, plan.clone());
+    }
+    plan
+}
+
+/// Logs how much work went into planning, as a warning when it took longer than 10ms
+fn report_plan_stats(entity: Entity, plan: &Result<Plan, PlanError>, stats: &PlanStats) {
+    let outcome = match plan {
+        Ok(plan) => format!("for {} steps costing {}", plan.steps.len(), plan.cost),
+        Err(_) => "without finding a plan".to_string(),
+    };
+    let message = format!(
+        "Planning for Entity {entity} took {:?} {outcome}: expanded {} nodes, generated {}, pruned {} duplicates, peak open set {}",
+        stats.elapsed,
+        stats.expanded_nodes,
+        stats.generated_nodes,
+        stats.duplicates_pruned,
+        stats.peak_open_set,
+    );
+    if stats.elapsed > Duration::from_millis(10) {
+        warn!("{message}");
+    } else {
+        debug!("{message}");
+    }
+}
+
 #[cfg(not(feature = "compute-pool"))]
 fn grab_plan_from_task(
-    task: &mut Task<Option<(Vec<dogoap::prelude::Node>, usize)>>,
-) -> Option<(Vec<dogoap::prelude::Node>, usize)> {
+    task: &mut Task<Result<Plan, PlanError>>,
+) -> Result<Plan, PlanError> {
     task.0.clone()
 }
 
@@ -199,9 +341,21 @@ pub fn handle_planner_tasks(
         };
 
         commands.entity(entity).remove::<ComputePlan>();
+
+        // If we couldn't reach the goal, start moving towards it with the partial plan, if any
+        let p = match p {
+            Err(err) if err.partial_plan().is_some() => {
+                debug!("Following partial plan for our goal in Entity {}: {}", entity, err);
+                Ok(err.into_partial_plan().unwrap())
+            }
+            p => p,
+        };
+
         match p {
-            Some((plan, _cost)) => {
-                let effects = get_effects_from_plan(plan);
+            Ok(plan) => {
+                let effects = plan.steps.clone();
+                planner.plan = Some(plan);
+                planner.plan_goal = planner.planning_goal.take();
 
                 let effect_names: VecDeque<String> =
                     effects.iter().map(|i| i.action.to_string()).collect();
@@ -234,17 +388,20 @@ pub fn handle_planner_tasks(
 
                         action_component.insert(&mut commands, entity);
                         planner.current_action = Some(found_action.clone());
+                        planner.current_arguments = first_effect.arguments.clone();
                     }
                     None => {
                         if planner.remove_goal_on_no_plan_found {
                             debug!("Seems there is nothing to be done, removing current goal");
                             planner.current_goal = None;
+                            planner.plan = None;
+                            planner.plan_goal = None;
                         }
                     }
                 }
             }
-            None => {
-                warn!("Didn't find any plan for our goal in Entity {}!", entity);
+            Err(err) => {
+                warn!("Didn't find any plan for our goal in Entity {}: {}", entity, err);
                 // warn!("No plan found");
             }
         }
@@ -1,11 +1,15 @@
 // TODO change to upstream once available
 pub use bevy_trait_query_0_14_0::RegisterExt;
 
-pub use dogoap::prelude::{Action, Compare, Datum, Goal, LocalState, Mutator};
+pub use dogoap::prelude::{
+    Action, Compare, Comparison, CostModel, Datum, EnumValue, Goal, KeyOperand, LocalState,
+    MissingKeyBehavior, Mutator, OutcomeMode, Parameter, Plan, PlanCache, PlanError, PlanStats,
+    Quantization, SearchState, StepFailure, Symbol,
+};
 
 pub use crate::{
     create_action_map, create_goal, create_planner, create_state, planner::IsPlanning,
-    planner::Planner, register_actions, register_components,
+    planner::Planner, planner::SharedPlanCache, register_actions, register_components,
 };
 
 pub use crate::plugin::DogoapPlugin;
@@ -50,11 +50,50 @@ impl fmt::Debug for dyn InserterComponent {
 ///     ("hunger".to_string(), Compare::LessThanEquals(Datum::F64(10.0)))
 /// )
 /// ```
+///
+/// Floats can be rounded to a step while planning, so values that only differ by a tiny
+/// bit don't count as different states:
+///
+/// ```rust
+/// # use bevy_dogoap::prelude::*;
+/// #[derive(DatumComponent)]
+/// #[datum(step = 0.5)]
+/// struct Energy(f64);
+///
+/// assert_eq!(Some(0.5), Energy(49.9999).quantization_step());
+/// ```
+///
+/// [`Symbol`](dogoap::prelude::Symbol)s are only equal or not, so they can be set and
+/// compared for equality, but not increased or compared with "greater" or "less":
+///
+/// ```rust
+/// # use bevy_dogoap::prelude::*;
+/// #[derive(DatumComponent)]
+/// struct Holding(Symbol);
+///
+/// assert_eq!(
+///     Holding::is(Symbol::new("iron_ore")),
+///     ("holding".to_string(), Compare::Equals(Datum::symbol("iron_ore")))
+/// );
+/// ```
+///
+/// ```compile_fail
+/// # use bevy_dogoap::prelude::*;
+/// #[derive(DatumComponent)]
+/// struct Holding(Symbol);
+///
+/// Holding::is_more(Symbol::new("iron_ore"));
+/// ```
 #[bevy_trait_query_0_14_0::queryable]
 #[reflect_trait]
 pub trait DatumComponent: Send + Sync {
     fn field_key(&self) -> String;
     fn field_value(&self) -> Datum;
+    /// The step to round the value to while planning, set with `#[datum(step = 0.01)]`,
+    /// see [`Quantization`](dogoap::prelude::Quantization)
+    fn quantization_step(&self) -> Option<f64> {
+        None
+    }
 }
 
 /// ActionComponent allows you to create Actions directly from your action struct
@@ -100,6 +139,25 @@ pub trait ActionComponent: Send + Sync {
     fn action_type_name(&self) -> &'static str;
 }
 
+/// An enum that can be used as a [`Datum`], derived with `#[derive(EnumDatum)]`
+///
+/// ```rust
+/// # use bevy_dogoap::prelude::*;
+/// #[derive(Clone, Copy, EnumDatum)]
+/// enum Location {
+///     Home = 10,
+///     Outside = 20,
+/// }
+///
+/// #[derive(Clone, EnumComponent)]
+/// struct AtLocation(Location);
+///
+/// assert_eq!("Datum:Enum(Location::Outside)", Location::Outside.datum().to_string());
+/// assert_eq!(
+///     AtLocation::is(Location::Home),
+///     ("at_location".to_string(), Compare::Equals(Location::Home.datum()))
+/// );
+/// ```
 pub trait EnumDatum: Send + Sync {
     fn datum(self) -> Datum;
 }
@@ -108,8 +166,15 @@ pub trait EnumDatum: Send + Sync {
 pub trait Precondition<T> {
     fn is(val: T) -> (String, Compare);
     fn is_not(val: T) -> (String, Compare);
+    /// Greater than or equal to `val`
     fn is_more(val: T) -> (String, Compare);
+    /// Less than or equal to `val`
     fn is_less(val: T) -> (String, Compare);
+    fn is_greater_than(val: T) -> (String, Compare);
+    fn is_less_than(val: T) -> (String, Compare);
+    /// Between `min` and `max`, including both
+    fn is_in_range(min: T, max: T) -> (String, Compare);
+    fn is_one_of(vals: Vec<T>) -> (String, Compare);
 }
 
 // Implemented by derive DatumComponent in order to mutate
@@ -117,4 +182,13 @@ pub trait MutatorTrait<T> {
     fn set(val: T) -> Mutator;
     fn increase(val: T) -> Mutator;
     fn decrease(val: T) -> Mutator;
+    fn multiply(val: T) -> Mutator;
+    fn divide(val: T) -> Mutator;
+    /// Keeps the value from going below `val`
+    fn clamp_min(val: T) -> Mutator;
+    /// Keeps the value from going above `val`
+    fn clamp_max(val: T) -> Mutator;
+    fn toggle() -> Mutator;
+    /// Sets the value to whatever another key has, like `Gold::copy_from(&Savings::key())`
+    fn copy_from(key: &str) -> Mutator;
 }
@@ -4,7 +4,7 @@
 mod tests {
     use dogoap::{
         prelude::*,
-        simple::{simple_decrement_action, simple_increment_action},
+        simple::{simple_action, simple_decrement_action, simple_increment_action},
     };
 
     extern crate test;
@@ -36,7 +36,10 @@ mod tests {
                     Mutator::Increment("hunger".to_string(), Datum::I64(5)),
                 ],
                 state: LocalState::default(),
-                cost: 1,
+                cost: 1.0,
+                conditional_costs: vec![],
+                probability: None,
+                arguments: vec![],
             })
             .with_precondition("hunger", Compare::LessThanEquals(Datum::I64(50)))
             .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(50)));
@@ -44,14 +47,70 @@ mod tests {
         let actions: Vec<Action> = vec![sleep_action, eat_action, rob_people];
 
         let plan = make_plan_with_strategy(strategy, &start, &actions[..], &goal);
-        let effects = get_effects_from_plan(plan.clone().unwrap().0);
+        let effects = get_effects_from_plan(plan.clone().unwrap());
 
         assert_eq!(11, effects.len());
         assert_eq!(expected_state, effects.last().unwrap().state);
     }
 
+    // Lots of keys and actions that have nothing to do with the goal, which
+    // StartToGoal has to wade through but GoalToStart never looks at
+    fn wide_plan(strategy: PlanningStrategy) {
+        let mut start = LocalState::new()
+            .with_datum("has_sword", Datum::Bool(false))
+            .with_datum("has_ore", Datum::Bool(false))
+            .with_datum("gold", Datum::I64(0));
+
+        let mut actions = vec![];
+        for i in 0..12 {
+            let chore = format!("chore_{}", i);
+            start = start.with_datum(&chore, Datum::Bool(false));
+            actions.push(simple_action(
+                &format!("do_{}", chore),
+                &chore,
+                Datum::Bool(true),
+            ));
+            actions.push(simple_action(
+                &format!("undo_{}", chore),
+                &chore,
+                Datum::Bool(false),
+            ));
+        }
+
+        actions.push(simple_action("mine", "has_ore", Datum::Bool(true)));
+        actions.push(
+            simple_action("forge", "has_sword", Datum::Bool(true))
+                .with_precondition("has_ore", Compare::Equals(Datum::Bool(true)))
+                .with_precondition("gold", Compare::GreaterThanEquals(Datum::I64(3))),
+        );
+        actions.push(simple_increment_action("work", "gold", Datum::I64(1)));
+
+        let goal = Goal::new().with_req("has_sword", Compare::Equals(Datum::Bool(true)));
+
+        let plan = make_plan_with_strategy(strategy, &start, &actions[..], &goal);
+        let effects = get_effects_from_plan(plan.unwrap());
+
+        assert_eq!(5, effects.len());
+    }
+
+    // Planning on compact states makes `make_plan` about twice as fast here as planning on
+    // LocalStates with String keys did. About a third of what's left is turning the path
+    // back into Effects with String keys, which the search can't do without
     #[bench]
     fn bench_start_to_goal_strategy(b: &mut Bencher) {
         b.iter(|| long_plan(PlanningStrategy::StartToGoal));
     }
+
+    #[bench]
+    fn bench_goal_to_start_strategy(b: &mut Bencher) {
+        b.iter(|| long_plan(PlanningStrategy::GoalToStart));
+    }
+
+    // StartToGoal takes a few milliseconds on this one, over a hundred times as long as
+    // GoalToStart, so only bench GoalToStart. On `long_plan` it's the other way around,
+    // GoalToStart takes over ten times as long as StartToGoal
+    #[bench]
+    fn bench_goal_to_start_strategy_wide(b: &mut Bencher) {
+        b.iter(|| wide_plan(PlanningStrategy::GoalToStart));
+    }
 }
@@ -11,12 +11,17 @@ fn main() {
 
     let eat_action = Action {
         key: "eat".to_string(),
+        parameters: vec![],
         preconditions: vec![],
+        conditions: vec![],
         effects: vec![Effect {
             action: "eat".to_string(),
             mutators: vec![Mutator::Set("is_hungry".to_string(), Datum::Bool(false))],
             state: LocalState::new(),
-            cost: 1,
+            cost: 1.0,
+            conditional_costs: vec![],
+            probability: None,
+            arguments: vec![],
         }],
     };
 
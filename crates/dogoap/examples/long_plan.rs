@@ -30,7 +30,10 @@ fn main() {
                 Mutator::Increment("hunger".to_string(), Datum::I64(5)),
             ],
             state: LocalState::default(),
-            cost: 1,
+            cost: 1.0,
+            conditional_costs: vec![],
+            probability: None,
+            arguments: vec![],
         })
         .with_precondition("hunger", Compare::LessThanEquals(Datum::I64(50)))
         .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(50)));
@@ -38,7 +41,7 @@ fn main() {
     let actions: Vec<Action> = vec![sleep_action, eat_action, rob_people];
 
     let plan = make_plan(&start, &actions[..], &goal);
-    let effects = get_effects_from_plan(plan.clone().unwrap().0);
+    let effects = get_effects_from_plan(plan.clone().unwrap());
     assert_eq!(11, effects.len());
 
     print_plan(plan.clone().unwrap());
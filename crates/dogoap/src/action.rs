@@ -1,32 +1,57 @@
 // crate "dogoap" file action.rs
+use std::borrow::Cow;
 use std::hash::{Hash, Hasher};
 
 use bevy_reflect::Reflect;
 
-use crate::compare::Compare;
+use crate::compare::{compare_values, Compare};
+use crate::condition::Condition;
+use crate::datum::Datum;
 use crate::effect::Effect;
-use crate::mutator::Mutator;
+use crate::error::PlanError;
+use crate::mutator::{MissingKeyBehavior, Mutator};
+
+/// A choice the planner gets to make when using an [`Action`], like where to go for a
+/// `go_to` action. Preconditions, conditions, conditional costs and mutators of the action
+/// refer to the chosen value by the parameter's `name`, wherever they could refer to another
+/// key: `Compare::ToKey` compares against the value and `Mutator::CopyFrom` sets it.
+/// Comparing the parameter itself allows for leaving out some of the values, or making them
+/// cost differently with [`Effect::with_cost_when`].
+///
+/// The planner tries every one of the `values`, see [`Action::ground`]. The chosen one ends
+/// up in the `arguments` of the [`Effect`]
+#[derive(Reflect, Clone, Debug, PartialEq, Hash)]
+pub struct Parameter {
+    pub name: String,
+    pub values: Vec<Datum>,
+}
 
 /// An `Action` represents something your Entity can do, granted the LocalState
-/// is as defined in the `preconditions`. It has a list of `Effect`s that apply
-/// if the NPC successfully executed the task.
+/// is as defined in the `preconditions`. It has a list of `Effect`s, one of which
+/// applies once the NPC executed the task.
 #[derive(Reflect, Clone, Debug, PartialEq, Default)]
 pub struct Action {
     /// String like `eat_action`
     pub key: String,
-    // TODO arguments coupled with Effects, maybe
-    // pub argument: Option<Datum>,
+    /// Choices to make when taking this action, see [`Parameter`]
+    pub parameters: Vec<Parameter>,
     /// What preconditions need to be true before we can execute this action
     pub preconditions: Vec<(String, Compare)>,
-    /// What is the outcome from doing this action
-    // TODO temporarily plural effects, as maybe we want to implement arguments with many effects...
+    /// Preconditions that need more than a flat list, see [`Condition`]. They all have to
+    /// be true, together with the `preconditions`
+    pub conditions: Vec<Condition>,
+    /// The possible outcomes of doing this action. With more than one, the planner treats
+    /// each as a separate way the action can turn out, see
+    /// [`OutcomeMode`](crate::cost::OutcomeMode)
     pub effects: Vec<Effect>,
 }
 
 impl Hash for Action {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.key.hash(state);
+        self.parameters.hash(state);
         self.preconditions.hash(state);
+        self.conditions.hash(state);
         self.effects.hash(state);
     }
 }
@@ -35,16 +60,35 @@ impl Action {
     pub fn new(key: &str) -> Self {
         Self {
             key: key.to_string(),
+            parameters: vec![],
             preconditions: vec![],
+            conditions: vec![],
             effects: vec![],
         }
     }
 
+    pub fn with_parameter(mut self, name: &str, values: impl IntoIterator<Item = Datum>) -> Self {
+        self.parameters.push(Parameter {
+            name: name.to_string(),
+            values: values.into_iter().collect(),
+        });
+        self
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&Parameter> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+
     pub fn with_precondition(mut self, key: &str, compare: Compare) -> Self {
         self.preconditions.push((key.to_string(), compare));
         self
     }
 
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
     pub fn with_effect(mut self, effect: Effect) -> Self {
         self.effects.push(effect);
         self
@@ -55,9 +99,10 @@ impl Action {
         self
     }
 
-    // TODO currently only handles one effect
+    /// Adds the mutator to the first [`Effect`], creating it if needed. Use
+    /// [`Action::with_effect`] to add more outcomes
     pub fn add_mutator(mut self, mutator: Mutator) -> Self {
-        if self.effects.len() == 0 {
+        if self.effects.is_empty() {
             self.effects = vec![Effect::new(&self.key.clone()).with_mutator(mutator)];
         } else {
             let mut effect = self.effects[0].clone();
@@ -67,10 +112,177 @@ impl Action {
         self
     }
 
-    pub fn set_cost(mut self, new_cost: usize) -> Self {
+    /// Sets the cost of the first [`Effect`]
+    pub fn set_cost(mut self, new_cost: f64) -> Self {
         let mut effect = self.effects[0].clone();
         effect.cost = new_cost;
         self.effects[0] = effect;
         self
     }
+
+    /// Makes the action cost `cost` instead whenever `condition` is true in the state the
+    /// action is taken from, see [`Effect::with_cost_when`]
+    pub fn with_cost_when(mut self, condition: Condition, cost: f64) -> Self {
+        self.effects[0] = self.effects[0].clone().with_cost_when(condition, cost);
+        self
+    }
+
+    /// How likely each of the [`Effect`]s is to happen. Effects without a `probability`
+    /// share whatever is left once the others have been accounted for
+    pub fn outcome_probabilities(&self) -> Vec<f64> {
+        let given: f64 = self.effects.iter().filter_map(|e| e.probability).sum();
+        let unknown = self
+            .effects
+            .iter()
+            .filter(|e| e.probability.is_none())
+            .count();
+        let shared = ((1.0 - given) / unknown as f64).max(0.0);
+        self.effects
+            .iter()
+            .map(|effect| effect.probability.unwrap_or(shared))
+            .collect()
+    }
+
+    /// One action without parameters for every combination of parameter values, with the
+    /// references to each [`Parameter`] replaced by its value and the values recorded in
+    /// the `arguments` of every [`Effect`]. Combinations that don't meet a precondition on
+    /// the parameter itself are left out. An action without parameters is returned as is
+    pub fn ground(&self) -> Result<Vec<Action>, PlanError> {
+        let mut combinations: Vec<Vec<(String, Datum)>> = vec![vec![]];
+        for parameter in &self.parameters {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|arguments| {
+                    parameter.values.iter().map(move |value| {
+                        let mut arguments = arguments.clone();
+                        arguments.push((parameter.name.clone(), *value));
+                        arguments
+                    })
+                })
+                .collect();
+        }
+        let mut grounded = vec![];
+        for arguments in combinations {
+            grounded.extend(self.with_arguments(arguments)?);
+        }
+        Ok(grounded)
+    }
+
+    /// `None` if a precondition on one of the parameters isn't met by `arguments`
+    fn with_arguments(&self, arguments: Vec<(String, Datum)>) -> Result<Option<Action>, PlanError> {
+        if arguments.is_empty() {
+            return Ok(Some(self.clone()));
+        }
+        let argument = |key: &str| {
+            arguments
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| *value)
+        };
+        let compare = |compare: &Compare| match compare.other_key() {
+            Some(key) if argument(key).is_some() => Ok(compare.resolve(argument)?.into_owned()),
+            _ => Ok(compare.clone()),
+        };
+        // What a comparison of a parameter to a value turns out to be, once the value is known
+        let known = |key: &str, compare: &Compare| -> Result<Option<bool>, PlanError> {
+            let Some(value) = argument(key) else {
+                return Ok(None);
+            };
+            if compare.other_key().is_some() {
+                return Ok(None);
+            }
+            if compare.type_mismatch(&value).is_some() {
+                return Err(PlanError::mismatch(key, &value, compare));
+            }
+            Ok(Some(compare_values(compare, &value)))
+        };
+        let condition = |condition: &Condition| {
+            condition.try_map(&|key, c| {
+                let c = compare(c)?;
+                Ok(match known(key, &c)? {
+                    Some(true) => Condition::All(vec![]),
+                    Some(false) => Condition::Any(vec![]),
+                    None => Condition::Compare(key.to_string(), c),
+                })
+            })
+        };
+
+        let mut preconditions = vec![];
+        for (key, c) in &self.preconditions {
+            let c = compare(c).map_err(|e| e.in_action(&self.key))?;
+            match known(key, &c).map_err(|e| e.in_action(&self.key))? {
+                Some(true) => {}
+                Some(false) => return Ok(None),
+                None => preconditions.push((key.clone(), c)),
+            }
+        }
+        let conditions = self
+            .conditions
+            .iter()
+            .map(condition)
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.in_action(&self.key))?;
+        let effects = self
+            .effects
+            .iter()
+            .map(|effect| {
+                // Conditions that only depended on the arguments are known by now, so the
+                // first one that is true becomes the cost, unless an unknown one comes first
+                let mut cost = effect.cost;
+                let mut conditional_costs = vec![];
+                for (c, conditional_cost) in &effect.conditional_costs {
+                    let c = condition(c)?;
+                    if !c.keys().is_empty() {
+                        conditional_costs.push((c, *conditional_cost));
+                    } else if c.evaluate_with(&|_| None, MissingKeyBehavior::Error)? {
+                        if conditional_costs.is_empty() {
+                            cost = *conditional_cost;
+                        } else {
+                            conditional_costs.push((c, *conditional_cost));
+                        }
+                        break;
+                    }
+                }
+                let mutators = effect
+                    .mutators
+                    .iter()
+                    .map(|mutator| match mutator {
+                        Mutator::CopyFrom(key, source) => match argument(source) {
+                            Some(value) => Mutator::Set(key.clone(), value),
+                            None => mutator.clone(),
+                        },
+                        _ => mutator.clone(),
+                    })
+                    .collect();
+                Ok(Effect {
+                    mutators,
+                    cost,
+                    conditional_costs,
+                    arguments: arguments.clone(),
+                    ..effect.clone()
+                })
+            })
+            .collect::<Result<_, PlanError>>()
+            .map_err(|e| e.in_action(&self.key))?;
+        Ok(Some(Action {
+            key: self.key.clone(),
+            parameters: vec![],
+            preconditions,
+            conditions,
+            effects,
+        }))
+    }
+}
+
+/// Every action of `actions` grounded, see [`Action::ground`], without copying anything if
+/// none of them has parameters
+pub(crate) fn ground_actions(actions: &[Action]) -> Result<Cow<'_, [Action]>, PlanError> {
+    if actions.iter().all(|action| action.parameters.is_empty()) {
+        return Ok(Cow::Borrowed(actions));
+    }
+    let mut grounded = vec![];
+    for action in actions {
+        grounded.extend(action.ground()?);
+    }
+    Ok(Cow::Owned(grounded))
 }
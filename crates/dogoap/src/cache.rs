@@ -0,0 +1,357 @@
+// crate "dogoap" file cache.rs
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use indexmap::IndexMap;
+
+use crate::{
+    action::Action,
+    cost::OutcomeMode,
+    error::PlanError,
+    goal::Goal,
+    localstate::LocalState,
+    mutator::MissingKeyBehavior,
+    plan::Plan,
+    planner::{make_plan_with_options, PlanOptions, PlanningStrategy},
+    quantization::Quantization,
+};
+
+/// Remembers the plans found for the most recently used combinations of start
+/// [`LocalState`], [`Goal`] and set of [`Action`]s, for when lots of agents share the same
+/// actions and keep ending up in the same states.
+///
+/// Holds at most `capacity` plans, and forgets the one that was used the longest ago to make
+/// room for a new one. The order of the actions doesn't matter. Plans are only reused for
+/// the same [`PlanningStrategy`], [`MissingKeyBehavior`], [`OutcomeMode`] and
+/// [`Quantization`] they were made with. The heuristic isn't taken into account, so use the
+/// same one for every lookup. Planning with a [`PlanOptions::cost_model`] is never cached,
+/// as those costs come from outside the [`LocalState`].
+#[derive(Clone, Debug)]
+pub struct PlanCache {
+    capacity: usize,
+    /// From the least to the most recently used
+    plans: IndexMap<u64, Entry>,
+    hits: usize,
+    misses: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    // Everything the plan was cached under is kept, to tell apart combinations that happen
+    // to hash the same
+    options: CachedOptions,
+    start: LocalState,
+    goal: Goal,
+    actions: Vec<u64>,
+    plan: Plan,
+}
+
+/// The [`PlanOptions`] that change which plan is found
+#[derive(Clone, Debug, PartialEq, Hash)]
+struct CachedOptions {
+    strategy: PlanningStrategy,
+    missing_keys: MissingKeyBehavior,
+    outcomes: OutcomeMode,
+    quantization: Quantization,
+}
+
+impl CachedOptions {
+    /// `None` for options whose plans can't be cached
+    fn new(options: &PlanOptions) -> Option<Self> {
+        if options.cost_model.is_some() {
+            return None;
+        }
+        Some(Self {
+            strategy: options.strategy,
+            missing_keys: options.missing_keys,
+            outcomes: options.outcomes,
+            quantization: options.quantization.clone(),
+        })
+    }
+}
+
+impl PlanCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            plans: IndexMap::with_capacity(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The most plans this cache holds at once
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.plans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plans.is_empty()
+    }
+
+    /// How many lookups found a plan
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// How many lookups didn't find a plan
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Forgets every plan, but keeps counting hits and misses
+    pub fn clear(&mut self) {
+        self.plans.clear();
+    }
+
+    /// The plan cached for planning from `start` to `goal` with `actions`, if any, which
+    /// then counts as the most recently used one
+    pub fn get(
+        &mut self,
+        options: &PlanOptions,
+        start: &LocalState,
+        actions: &[Action],
+        goal: &Goal,
+    ) -> Option<Plan> {
+        let found = CachedOptions::new(options).and_then(|options| {
+            let actions = actions_fingerprint(actions);
+            let index = self
+                .plans
+                .get_index_of(&cache_key(&options, start, &actions, goal))?;
+            let entry = &self.plans[index];
+            if entry.options != options
+                || entry.start != *start
+                || entry.goal != *goal
+                || entry.actions != actions
+            {
+                return None;
+            }
+            let plan = entry.plan.clone();
+            let last = self.plans.len() - 1;
+            self.plans.move_index(index, last);
+            Some(plan)
+        });
+        match found {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        found
+    }
+
+    /// Remembers `plan` as the plan from `start` to `goal` with `actions`, forgetting the
+    /// least recently used plan if the cache is full
+    pub fn insert(
+        &mut self,
+        options: &PlanOptions,
+        start: &LocalState,
+        actions: &[Action],
+        goal: &Goal,
+        plan: Plan,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Some(options) = CachedOptions::new(options) else {
+            return;
+        };
+        let actions = actions_fingerprint(actions);
+        let key = cache_key(&options, start, &actions, goal);
+        let entry = Entry {
+            options,
+            start: start.clone(),
+            goal: goal.clone(),
+            actions,
+            plan,
+        };
+        // Replacing keeps the old position, so move it to the end as well
+        let (index, _) = self.plans.insert_full(key, entry);
+        let last = self.plans.len() - 1;
+        self.plans.move_index(index, last);
+        if self.plans.len() > self.capacity {
+            self.plans.shift_remove_index(0);
+        }
+    }
+
+    /// Like [`make_plan_with_options`], but returns the cached plan if there is one, and
+    /// caches the plan otherwise. Failing to find a plan is never cached, as it might
+    /// succeed with more budget
+    pub fn make_plan(
+        &mut self,
+        options: &PlanOptions,
+        start: &LocalState,
+        actions: &[Action],
+        goal: &Goal,
+    ) -> Result<Plan, PlanError> {
+        if let Some(plan) = self.get(options, start, actions, goal) {
+            return Ok(plan);
+        }
+        let plan = make_plan_with_options(options, start, actions, goal)?;
+        self.insert(options, start, actions, goal, plan.clone());
+        Ok(plan)
+    }
+}
+
+/// What plans are cached under, with `actions` from [`actions_fingerprint`]
+fn cache_key(options: &CachedOptions, start: &LocalState, actions: &[u64], goal: &Goal) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    options.hash(&mut hasher);
+    start.hash(&mut hasher);
+    goal.hash(&mut hasher);
+    actions.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The hash of each action, sorted, as the same actions in another order, like from
+/// iterating a HashMap, are the same set
+fn actions_fingerprint(actions: &[Action]) -> Vec<u64> {
+    let mut action_hashes: Vec<u64> = actions.iter().map(action_hash).collect();
+    action_hashes.sort_unstable();
+    action_hashes
+}
+
+/// Hashes everything about an action that can change the plan. The `Hash` of an
+/// [`Effect`](crate::effect::Effect) leaves out the costs and probability, as the planner
+/// only needs it to tell states apart
+fn action_hash(action: &Action) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    action.hash(&mut hasher);
+    for effect in &action.effects {
+        effect.cost.to_bits().hash(&mut hasher);
+        effect.probability.map(f64::to_bits).hash(&mut hasher);
+        for (condition, cost) in &effect.conditional_costs {
+            condition.hash(&mut hasher);
+            cost.to_bits().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compare::Compare,
+        datum::Datum,
+        simple::{simple_action, simple_increment_action},
+    };
+
+    fn domain(n: i64) -> (LocalState, Vec<Action>, Goal) {
+        let start = LocalState::new().with_datum("value", Datum::I64(n));
+        let actions = vec![simple_action("set", "value", Datum::I64(0))];
+        let goal = Goal::new().with_req("value", Compare::Equals(Datum::I64(0)));
+        (start, actions, goal)
+    }
+
+    #[test]
+    fn test_cache_hits_and_misses() {
+        let options = PlanOptions::new();
+        let mut cache = PlanCache::new(4);
+        let (start, actions, goal) = domain(1);
+
+        let plan = cache.make_plan(&options, &start, &actions, &goal).unwrap();
+        assert_eq!((0, 1, 1), (cache.hits(), cache.misses(), cache.len()));
+        assert_eq!(Some(plan), cache.get(&options, &start, &actions, &goal));
+        assert_eq!(1, cache.hits());
+
+        // Any difference in the actions or options is a different plan
+        let other_actions = vec![actions[0]
+            .clone()
+            .with_precondition("value", Compare::NotEquals(Datum::I64(0)))];
+        assert_eq!(None, cache.get(&options, &start, &other_actions, &goal));
+        let reversed = PlanOptions::new().with_strategy(PlanningStrategy::GoalToStart);
+        assert_eq!(None, cache.get(&reversed, &start, &actions, &goal));
+        assert_eq!(3, cache.misses());
+
+        // But the order of the actions doesn't matter
+        let mut more_actions = vec![simple_action("reset", "value", Datum::I64(1))];
+        more_actions.extend(actions.iter().cloned());
+        let plan = cache
+            .make_plan(&options, &start, &more_actions, &goal)
+            .unwrap();
+        more_actions.reverse();
+        assert_eq!(
+            Some(plan),
+            cache.get(&options, &start, &more_actions, &goal)
+        );
+    }
+
+    #[test]
+    fn test_cache_tells_costs_apart() {
+        let options = PlanOptions::new();
+        let mut cache = PlanCache::new(4);
+        let start = LocalState::new().with_datum("gold", Datum::I64(0));
+        let goal = Goal::new().with_req("gold", Compare::GreaterThanEquals(Datum::I64(2)));
+        let actions = |work_cost: f64| {
+            vec![
+                simple_increment_action("work", "gold", Datum::I64(1)).set_cost(work_cost),
+                simple_action("rob", "gold", Datum::I64(2)).set_cost(10.0),
+            ]
+        };
+        let keys = |plan: &Plan| -> Vec<String> {
+            plan.steps.iter().map(|step| step.action.clone()).collect()
+        };
+
+        let cheap = cache
+            .make_plan(&options, &start, &actions(1.0), &goal)
+            .unwrap();
+        assert_eq!(vec!["work", "work"], keys(&cheap));
+
+        // The same actions with another cost are another plan, not the cached one
+        let expensive = cache
+            .make_plan(&options, &start, &actions(20.0), &goal)
+            .unwrap();
+        assert_eq!(0, cache.hits());
+        assert_eq!(vec!["rob"], keys(&expensive));
+        assert_eq!(10.0, expensive.cost);
+    }
+
+    #[test]
+    fn test_cache_tells_colliding_keys_apart() {
+        let options = PlanOptions::new();
+        let mut cache = PlanCache::new(4);
+        let (start, mut actions, goal) = domain(1);
+        actions.push(simple_action("reset", "value", Datum::I64(1)));
+        cache.make_plan(&options, &start, &actions, &goal).unwrap();
+
+        // Pretend other actions and other options hash the same as the cached plan
+        let collide = |cache: &mut PlanCache, options: &PlanOptions, actions: &[Action]| {
+            let options = CachedOptions::new(options).unwrap();
+            let key = cache_key(&options, &start, &actions_fingerprint(actions), &goal);
+            let (_, entry) = cache.plans.pop().unwrap();
+            cache.plans.insert(key, entry);
+        };
+        collide(&mut cache, &options, &actions[..1]);
+        assert_eq!(None, cache.get(&options, &start, &actions[..1], &goal));
+        let reversed = PlanOptions::new().with_strategy(PlanningStrategy::GoalToStart);
+        collide(&mut cache, &reversed, &actions);
+        assert_eq!(None, cache.get(&reversed, &start, &actions, &goal));
+        assert_eq!(0, cache.hits());
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let options = PlanOptions::new();
+        let mut cache = PlanCache::new(2);
+        for n in 1..=2 {
+            let (start, actions, goal) = domain(n);
+            cache.make_plan(&options, &start, &actions, &goal).unwrap();
+        }
+        // Using the first plan makes the second one the least recently used
+        let (start, actions, goal) = domain(1);
+        assert!(cache.get(&options, &start, &actions, &goal).is_some());
+
+        let (start, actions, goal) = domain(3);
+        cache.make_plan(&options, &start, &actions, &goal).unwrap();
+        assert_eq!(2, cache.len());
+        let cached = |cache: &mut PlanCache, n| {
+            let (start, actions, goal) = domain(n);
+            cache.get(&options, &start, &actions, &goal).is_some()
+        };
+        assert!(cached(&mut cache, 1));
+        assert!(!cached(&mut cache, 2));
+        assert!(cached(&mut cache, 3));
+    }
+}
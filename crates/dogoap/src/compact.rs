@@ -0,0 +1,667 @@
+// crate "dogoap" file compact.rs
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    action::Action,
+    compare::{check_value, Compare},
+    condition::Condition,
+    datum::Datum,
+    effect::Effect,
+    error::PlanError,
+    goal::Goal,
+    localstate::LocalState,
+    mutator::{toggle, Change, MissingKeyBehavior, Mutator},
+    quantization::Quantization,
+};
+
+/// Maps every String key used by a domain (the start [`LocalState`], the [`Action`]s and
+/// the [`Goal`]) to a dense index, so states can be stored as a flat list of values
+/// while planning, instead of cloning all the String keys for every new state
+#[derive(Clone, Debug, Default)]
+pub struct KeyRegistry {
+    keys: Vec<String>,
+    indices: FxHashMap<String, usize>,
+    /// The [`Quantization`] step of each key, if it has one
+    steps: Vec<Option<f64>>,
+}
+
+impl KeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers every key that `start`, `actions` and `goal` refers to
+    pub fn from_domain(start: &LocalState, actions: &[Action], goal: &Goal) -> Self {
+        let mut registry = Self::new();
+        for key in start.data.keys() {
+            registry.intern(key);
+        }
+        for action in actions {
+            for (key, compare) in &action.preconditions {
+                registry.intern(key);
+                registry.intern_other_key(compare);
+            }
+            for condition in &action.conditions {
+                for key in condition.keys() {
+                    registry.intern(key);
+                }
+            }
+            for effect in &action.effects {
+                for mutator in &effect.mutators {
+                    registry.intern(mutator.key());
+                    if let Some(source) = mutator.source_key() {
+                        registry.intern(source);
+                    }
+                }
+            }
+        }
+        for (key, compare) in &goal.requirements {
+            registry.intern(key);
+            registry.intern_other_key(compare);
+        }
+        for condition in &goal.conditions {
+            for key in condition.keys() {
+                registry.intern(key);
+            }
+        }
+        registry
+    }
+
+    /// Returns the index of `key`, registering it first if needed
+    pub fn intern(&mut self, key: &str) -> usize {
+        if let Some(index) = self.indices.get(key) {
+            return *index;
+        }
+        let index = self.keys.len();
+        self.keys.push(key.to_string());
+        self.indices.insert(key.to_string(), index);
+        index
+    }
+
+    /// Makes [`CompactAction::apply`] round the values it changes to the steps of their
+    /// keys, for the keys registered so far
+    pub fn with_quantization(mut self, quantization: &Quantization) -> Self {
+        if !quantization.is_empty() {
+            self.steps = self.keys.iter().map(|key| quantization.step(key)).collect();
+        }
+        self
+    }
+
+    fn quantize(&self, index: usize, value: Datum) -> Datum {
+        match self.steps.get(index) {
+            Some(Some(step)) => value.quantize(*step),
+            _ => value,
+        }
+    }
+
+    fn intern_other_key(&mut self, compare: &Compare) {
+        if let Some(key) = compare.other_key() {
+            self.intern(key);
+        }
+    }
+
+    pub fn index(&self, key: &str) -> Option<usize> {
+        self.indices.get(key).copied()
+    }
+
+    pub fn key(&self, index: usize) -> &str {
+        &self.keys[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Like [`KeyRegistry::index`], but a key that was never registered is a
+    /// [`PlanError::MissingKey`]
+    fn require_index(&self, key: &str) -> Result<usize, PlanError> {
+        self.index(key).ok_or_else(|| PlanError::MissingKey {
+            key: key.to_string(),
+            action: None,
+        })
+    }
+}
+
+/// States with up to this many keys keep their values inline, as copying them is cheaper
+/// than sharing them
+const INLINE_VALUES: usize = 4;
+
+/// Larger states split their values up in chunks of this many
+const CHUNK_VALUES: usize = 8;
+
+type Chunk = [Option<Datum>; CHUNK_VALUES];
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Values {
+    Inline([Option<Datum>; INLINE_VALUES]),
+    /// Each chunk is shared by all the states that have the same values in it, so a new
+    /// state only copies the chunks with the values that changed
+    Chunks(Arc<[Arc<Chunk>]>),
+}
+
+/// A [`LocalState`] where the keys have been replaced by their index in a [`KeyRegistry`].
+/// Keys that aren't set in the state are `None`.
+///
+/// The search keeps a copy of every state it has seen, so cloning a state never allocates,
+/// and [`CompactAction::apply`] only copies the values that changed. Which way the values
+/// are stored only depends on the number of keys, so states of the same registry can be
+/// compared and hashed as they are
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CompactState {
+    values: Values,
+}
+
+impl CompactState {
+    /// Fails with [`PlanError::MissingKey`] if `state` has a key that isn't in `registry`
+    pub fn from_local_state(state: &LocalState, registry: &KeyRegistry) -> Result<Self, PlanError> {
+        let mut compact = Self::empty(registry.len());
+        for (key, value) in &state.data {
+            *compact.value_mut(registry.require_index(key)?) = Some(*value);
+        }
+        Ok(compact)
+    }
+
+    /// A state with `len` keys, none of them set
+    fn empty(len: usize) -> Self {
+        let values = match len {
+            len if len <= INLINE_VALUES => Values::Inline([None; INLINE_VALUES]),
+            len => Values::Chunks(
+                (0..len.div_ceil(CHUNK_VALUES))
+                    .map(|_| Arc::new([None; CHUNK_VALUES]))
+                    .collect(),
+            ),
+        };
+        Self { values }
+    }
+
+    pub fn to_local_state(&self, registry: &KeyRegistry) -> LocalState {
+        let mut state = LocalState::new();
+        for index in 0..registry.len() {
+            if let Some(value) = self.get(index) {
+                state.data.insert(registry.key(index).to_string(), *value);
+            }
+        }
+        state
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Datum> {
+        match &self.values {
+            Values::Inline(values) => values[index].as_ref(),
+            Values::Chunks(chunks) => chunks[index / CHUNK_VALUES][index % CHUNK_VALUES].as_ref(),
+        }
+    }
+
+    /// Copies the chunk of `index` first if another state shares it
+    fn value_mut(&mut self, index: usize) -> &mut Option<Datum> {
+        match &mut self.values {
+            Values::Inline(values) => &mut values[index],
+            Values::Chunks(chunks) => {
+                if Arc::get_mut(chunks).is_none() {
+                    *chunks = chunks.iter().cloned().collect();
+                }
+                let chunks = Arc::get_mut(chunks).expect("The chunks were just copied");
+                &mut Arc::make_mut(&mut chunks[index / CHUNK_VALUES])[index % CHUNK_VALUES]
+            }
+        }
+    }
+
+    /// See [`Compare::resolve`], with the other key looked up in this state
+    pub fn resolve<'c>(
+        &self,
+        compare: &'c Compare,
+        registry: &KeyRegistry,
+    ) -> Result<Cow<'c, Compare>, PlanError> {
+        compare.resolve(|key| {
+            registry
+                .index(key)
+                .and_then(|index| self.get(index).copied())
+        })
+    }
+}
+
+/// A read-only view of a state while planning, so a
+/// [`Heuristic`](crate::heuristic::Heuristic) can look up values by their key
+#[derive(Clone, Copy)]
+pub struct SearchState<'a> {
+    state: &'a CompactState,
+    registry: &'a KeyRegistry,
+}
+
+impl<'a> SearchState<'a> {
+    pub(crate) fn new(state: &'a CompactState, registry: &'a KeyRegistry) -> Self {
+        Self { state, registry }
+    }
+
+    /// Returns `None` if the key isn't set in this state
+    pub fn get(&self, key: &str) -> Option<&'a Datum> {
+        self.state.get(self.registry.index(key)?)
+    }
+
+    pub fn to_local_state(&self) -> LocalState {
+        self.state.to_local_state(self.registry)
+    }
+}
+
+/// Looks up the value at `index` and compares it, failing if the value is of a different
+/// type than the one in the [`Compare`], or is missing and `missing` doesn't read it
+fn check_compare(
+    state: &CompactState,
+    registry: &KeyRegistry,
+    index: usize,
+    compare: &Compare,
+    missing: MissingKeyBehavior,
+) -> Result<bool, PlanError> {
+    check_value(
+        registry.key(index),
+        state.get(index).copied(),
+        compare,
+        |key| {
+            registry
+                .index(key)
+                .and_then(|index| state.get(index).copied())
+        },
+        missing,
+    )
+}
+
+/// The ways the [`Condition`]s can all be fulfilled, with keys resolved against a
+/// [`KeyRegistry`], see [`Condition::alternatives`]
+pub type CompactAlternatives = Vec<Vec<(usize, Compare)>>;
+
+fn compact_alternatives(
+    conditions: &[Condition],
+    registry: &KeyRegistry,
+) -> Result<CompactAlternatives, PlanError> {
+    Condition::all(conditions.iter().cloned())
+        .alternatives()
+        .into_iter()
+        .map(|alternative| {
+            alternative
+                .into_iter()
+                .map(|(key, compare)| Ok((registry.require_index(&key)?, compare)))
+                .collect()
+        })
+        .collect()
+}
+
+/// If all requirements of at least one of the alternatives are fulfilled
+fn check_alternatives(
+    state: &CompactState,
+    registry: &KeyRegistry,
+    alternatives: &CompactAlternatives,
+    missing: MissingKeyBehavior,
+) -> Result<bool, PlanError> {
+    'alternatives: for alternative in alternatives {
+        for (index, compare) in alternative {
+            if !check_compare(state, registry, *index, compare, missing)? {
+                continue 'alternatives;
+            }
+        }
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// A [`Mutator`] with the keys resolved against a [`KeyRegistry`]
+#[derive(Clone, Debug)]
+pub enum CompactMutator {
+    Set(usize, Datum),
+    Increment(usize, Datum),
+    Decrement(usize, Datum),
+    Multiply(usize, Datum),
+    Divide(usize, Datum),
+    ClampMin(usize, Datum),
+    ClampMax(usize, Datum),
+    Toggle(usize),
+    CopyFrom(usize, usize),
+}
+
+impl CompactMutator {
+    /// The index of the key this mutator changes
+    pub fn index(&self) -> usize {
+        match self {
+            CompactMutator::Set(index, _)
+            | CompactMutator::Increment(index, _)
+            | CompactMutator::Decrement(index, _)
+            | CompactMutator::Multiply(index, _)
+            | CompactMutator::Divide(index, _)
+            | CompactMutator::ClampMin(index, _)
+            | CompactMutator::ClampMax(index, _)
+            | CompactMutator::Toggle(index)
+            | CompactMutator::CopyFrom(index, _) => *index,
+        }
+    }
+
+    fn new(mutator: &Mutator, registry: &KeyRegistry) -> Result<Self, PlanError> {
+        let index = |key: &str| registry.require_index(key);
+        Ok(match mutator {
+            Mutator::Set(key, value) => CompactMutator::Set(index(key)?, *value),
+            Mutator::Increment(key, value) => CompactMutator::Increment(index(key)?, *value),
+            Mutator::Decrement(key, value) => CompactMutator::Decrement(index(key)?, *value),
+            Mutator::Multiply(key, value) => CompactMutator::Multiply(index(key)?, *value),
+            Mutator::Divide(key, value) => CompactMutator::Divide(index(key)?, *value),
+            Mutator::ClampMin(key, value) => CompactMutator::ClampMin(index(key)?, *value),
+            Mutator::ClampMax(key, value) => CompactMutator::ClampMax(index(key)?, *value),
+            Mutator::Toggle(key) => CompactMutator::Toggle(index(key)?),
+            Mutator::CopyFrom(key, source) => CompactMutator::CopyFrom(index(key)?, index(source)?),
+        })
+    }
+}
+
+/// One outcome of an [`Action`], with its preconditions and the [`Effect`] of that outcome
+/// resolved against a [`KeyRegistry`]
+pub struct CompactAction<'a> {
+    action: &'a Action,
+    effect: &'a Effect,
+    probability: f64,
+    preconditions: Vec<(usize, &'a Compare)>,
+    alternatives: CompactAlternatives,
+    mutators: Vec<CompactMutator>,
+}
+
+impl<'a> CompactAction<'a> {
+    /// One for each [`Effect`] of the action, as each is a possible outcome. Empty for
+    /// actions without any, as they can't lead anywhere
+    pub fn outcomes(action: &'a Action, registry: &KeyRegistry) -> Result<Vec<Self>, PlanError> {
+        let in_action = |err: PlanError| err.in_action(&action.key);
+        let preconditions = action
+            .preconditions
+            .iter()
+            .map(|(key, compare)| Ok((registry.require_index(key)?, compare)))
+            .collect::<Result<Vec<_>, PlanError>>()
+            .map_err(in_action)?;
+        let alternatives = compact_alternatives(&action.conditions, registry).map_err(in_action)?;
+        action
+            .effects
+            .iter()
+            .zip(action.outcome_probabilities())
+            .map(|(effect, probability)| {
+                Ok(Self {
+                    action,
+                    effect,
+                    probability,
+                    preconditions: preconditions.clone(),
+                    alternatives: alternatives.clone(),
+                    mutators: effect
+                        .mutators
+                        .iter()
+                        .map(|mutator| CompactMutator::new(mutator, registry))
+                        .collect::<Result<_, _>>()
+                        .map_err(in_action)?,
+                })
+            })
+            .collect()
+    }
+
+    /// [`CompactAction::outcomes`] of all `actions`
+    pub fn all_outcomes(
+        actions: &'a [Action],
+        registry: &KeyRegistry,
+    ) -> Result<Vec<Self>, PlanError> {
+        let mut outcomes = vec![];
+        for action in actions {
+            outcomes.extend(Self::outcomes(action, registry)?);
+        }
+        Ok(outcomes)
+    }
+
+    pub fn action(&self) -> &'a Action {
+        self.action
+    }
+
+    pub fn preconditions(&self) -> &[(usize, &'a Compare)] {
+        &self.preconditions
+    }
+
+    /// The ways the [`Condition`]s of the action can be fulfilled, on top of the preconditions
+    pub fn alternatives(&self) -> &CompactAlternatives {
+        &self.alternatives
+    }
+
+    pub fn mutators(&self) -> &[CompactMutator] {
+        &self.mutators
+    }
+
+    pub fn effect(&self) -> &'a Effect {
+        self.effect
+    }
+
+    /// How likely this outcome is, see [`Action::outcome_probabilities`]
+    pub fn probability(&self) -> f64 {
+        self.probability
+    }
+
+    pub fn cost(&self) -> f64 {
+        self.effect.cost
+    }
+
+    /// `missing` decides what preconditions on keys that aren't in `state` read
+    pub fn check_preconditions(
+        &self,
+        state: &CompactState,
+        registry: &KeyRegistry,
+        missing: MissingKeyBehavior,
+    ) -> Result<bool, PlanError> {
+        for (index, compare) in &self.preconditions {
+            if !check_compare(state, registry, *index, compare, missing)
+                .map_err(|err| err.in_action(&self.action.key))?
+            {
+                return Ok(false);
+            }
+        }
+        check_alternatives(state, registry, &self.alternatives, missing)
+            .map_err(|err| err.in_action(&self.action.key))
+    }
+
+    /// Returns the state after the mutators of the [`Effect`] have been applied to `state`
+    pub fn apply(
+        &self,
+        state: &CompactState,
+        registry: &KeyRegistry,
+        missing: MissingKeyBehavior,
+    ) -> Result<CompactState, PlanError> {
+        let mut new_state = state.clone();
+        let in_action = |err: PlanError| err.in_action(&self.action.key);
+        for mutator in &self.mutators {
+            let (index, change, value) = match mutator {
+                CompactMutator::Set(index, value) => {
+                    *new_state.value_mut(*index) = Some(*value);
+                    continue;
+                }
+                CompactMutator::Toggle(index) => {
+                    let key = registry.key(*index);
+                    let value = match new_state.get(*index) {
+                        Some(current_value) => toggle(key, current_value),
+                        None => {
+                            missing.missing_value(key, Datum::Bool(true), |zero| toggle(key, zero))
+                        }
+                    };
+                    *new_state.value_mut(*index) = Some(value.map_err(in_action)?);
+                    continue;
+                }
+                CompactMutator::CopyFrom(index, source) => {
+                    let Some(value) = new_state.get(*source).copied() else {
+                        return Err(in_action(PlanError::MissingKey {
+                            key: registry.key(*source).to_string(),
+                            action: None,
+                        }));
+                    };
+                    *new_state.value_mut(*index) = Some(value);
+                    continue;
+                }
+                CompactMutator::Increment(index, value) => (*index, Change::Increment, value),
+                CompactMutator::Decrement(index, value) => (*index, Change::Decrement, value),
+                CompactMutator::Multiply(index, value) => (*index, Change::Multiply, value),
+                CompactMutator::Divide(index, value) => (*index, Change::Divide, value),
+                CompactMutator::ClampMin(index, value) => (*index, Change::ClampMin, value),
+                CompactMutator::ClampMax(index, value) => (*index, Change::ClampMax, value),
+            };
+            let key = registry.key(index);
+            let new_value = match new_state.get(index) {
+                Some(current_value) => change.apply(key, current_value, value),
+                None => missing.missing_value(key, *value, |zero| change.apply(key, zero, value)),
+            };
+            *new_state.value_mut(index) = Some(new_value.map_err(in_action)?);
+        }
+        // Only keys with a step need rounding, and usually none have one
+        if !registry.steps.is_empty() {
+            for mutator in &self.mutators {
+                let index = mutator.index();
+                if let Some(value) = new_state.get(index).copied() {
+                    *new_state.value_mut(index) = Some(registry.quantize(index, value));
+                }
+            }
+        }
+        Ok(new_state)
+    }
+}
+
+/// A [`Goal`] with its requirements resolved against a [`KeyRegistry`]
+pub struct CompactGoal<'a> {
+    requirements: Vec<(usize, &'a Compare)>,
+    alternatives: CompactAlternatives,
+}
+
+impl<'a> CompactGoal<'a> {
+    pub fn new(goal: &'a Goal, registry: &KeyRegistry) -> Result<Self, PlanError> {
+        Ok(Self {
+            requirements: goal
+                .requirements
+                .iter()
+                .map(|(key, compare)| Ok((registry.require_index(key)?, compare)))
+                .collect::<Result<_, PlanError>>()?,
+            alternatives: compact_alternatives(&goal.conditions, registry)?,
+        })
+    }
+
+    pub fn requirements(&self) -> &[(usize, &'a Compare)] {
+        &self.requirements
+    }
+
+    /// The ways the [`Condition`]s of the goal can be fulfilled, on top of the requirements
+    pub fn alternatives(&self) -> &CompactAlternatives {
+        &self.alternatives
+    }
+
+    /// `missing` decides what requirements on keys that aren't in `state` read
+    pub fn is_satisfied(
+        &self,
+        state: &CompactState,
+        registry: &KeyRegistry,
+        missing: MissingKeyBehavior,
+    ) -> Result<bool, PlanError> {
+        for (index, compare) in &self.requirements {
+            if !check_compare(state, registry, *index, compare, missing)? {
+                return Ok(false);
+            }
+        }
+        check_alternatives(state, registry, &self.alternatives, missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_local_state() {
+        let state = LocalState::new()
+            .with_datum("energy", Datum::I64(50))
+            .with_datum("is_hungry", Datum::Bool(true));
+        let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+        let registry = KeyRegistry::from_domain(&state, &[], &goal);
+        assert_eq!(3, registry.len());
+
+        let compact = CompactState::from_local_state(&state, &registry).unwrap();
+        assert_eq!(None, compact.get(registry.index("gold").unwrap()));
+        assert_eq!(state, compact.to_local_state(&registry));
+    }
+
+    #[test]
+    fn test_unregistered_key() {
+        let registry = KeyRegistry::from_domain(&LocalState::new(), &[], &Goal::new());
+        let state = LocalState::new().with_datum("energy", Datum::I64(50));
+        assert_eq!(
+            Err(PlanError::MissingKey {
+                key: "energy".to_string(),
+                action: None
+            }),
+            CompactState::from_local_state(&state, &registry)
+        );
+
+        let action = Action::new("eat")
+            .add_mutator(Mutator::Increment("energy".to_string(), Datum::I64(10)));
+        assert_eq!(
+            Some(PlanError::MissingKey {
+                key: "energy".to_string(),
+                action: Some("eat".to_string())
+            }),
+            CompactAction::outcomes(&action, &registry).err()
+        );
+    }
+
+    #[test]
+    fn test_apply_action() {
+        let state = LocalState::new().with_datum("energy", Datum::I64(50));
+        let action = Action::new("eat")
+            .add_mutator(Mutator::Increment("energy".to_string(), Datum::I64(10)))
+            .add_mutator(Mutator::Set("is_hungry".to_string(), Datum::Bool(false)));
+
+        let registry = KeyRegistry::from_domain(&state, &[action.clone()], &Goal::new());
+        let compact = CompactState::from_local_state(&state, &registry).unwrap();
+        let compact_action = CompactAction::outcomes(&action, &registry)
+            .unwrap()
+            .remove(0);
+
+        let expected = LocalState::new()
+            .with_datum("energy", Datum::I64(60))
+            .with_datum("is_hungry", Datum::Bool(false));
+        assert_eq!(
+            expected,
+            compact_action
+                .apply(&compact, &registry, MissingKeyBehavior::Error)
+                .unwrap()
+                .to_local_state(&registry)
+        );
+    }
+
+    #[test]
+    fn test_apply_shares_unchanged_values() {
+        let mut state = LocalState::new();
+        for i in 0..20 {
+            state = state.with_datum(&format!("key_{}", i), Datum::I64(i));
+        }
+        let action =
+            Action::new("bump").add_mutator(Mutator::Increment("key_0".to_string(), Datum::I64(1)));
+
+        let registry = KeyRegistry::from_domain(&state, &[action.clone()], &Goal::new());
+        let compact = CompactState::from_local_state(&state, &registry).unwrap();
+        let compact_action = CompactAction::outcomes(&action, &registry)
+            .unwrap()
+            .remove(0);
+        let new_state = compact_action
+            .apply(&compact, &registry, MissingKeyBehavior::Error)
+            .unwrap();
+
+        let (Values::Chunks(before), Values::Chunks(after)) = (&compact.values, &new_state.values)
+        else {
+            panic!("States with more than {INLINE_VALUES} keys are stored in chunks");
+        };
+        let changed = registry.index("key_0").unwrap() / CHUNK_VALUES;
+        for (index, (before, after)) in before.iter().zip(after.iter()).enumerate() {
+            assert_eq!(index != changed, Arc::ptr_eq(before, after));
+        }
+        assert_eq!(
+            Some(&Datum::I64(1)),
+            new_state.get(registry.index("key_0").unwrap())
+        );
+    }
+}
@@ -1,25 +1,203 @@
-use crate::{action::Action, datum::Datum, localstate::LocalState};
+use crate::{
+    action::Action, datum::Datum, error::PlanError, localstate::LocalState,
+    mutator::MissingKeyBehavior,
+};
 use bevy_reflect::Reflect;
+use std::borrow::Cow;
 use std::hash::{Hash, Hasher};
 
 /// Allows you to Compare [`Datum`] between each other. Used for Preconditions
 /// and in path finding until we reach our goal.
-#[derive(Reflect, Clone, Debug, PartialEq)]
+#[derive(Reflect, Clone, Debug, PartialEq, Eq)]
 pub enum Compare {
     Equals(Datum),
     NotEquals(Datum),
     GreaterThanEquals(Datum),
     LessThanEquals(Datum),
+    GreaterThan(Datum),
+    LessThan(Datum),
+    /// Between the two values, including both of them
+    InRange(Datum, Datum),
+    /// Equal to any of the values
+    OneOf(Vec<Datum>),
+    /// Compared to the value of another key in the [`LocalState`] instead of a fixed value,
+    /// like "gold >= price"
+    ToKey(Comparison, KeyOperand),
+}
+
+/// How the value is compared to the other key in [`Compare::ToKey`]
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Equals,
+    NotEquals,
+    GreaterThanEquals,
+    LessThanEquals,
+    GreaterThan,
+    LessThan,
+}
+
+impl Comparison {
+    /// The [`Compare`] doing this comparison against a fixed value
+    pub fn with(self, value: Datum) -> Compare {
+        match self {
+            Comparison::Equals => Compare::Equals(value),
+            Comparison::NotEquals => Compare::NotEquals(value),
+            Comparison::GreaterThanEquals => Compare::GreaterThanEquals(value),
+            Comparison::LessThanEquals => Compare::LessThanEquals(value),
+            Comparison::GreaterThan => Compare::GreaterThan(value),
+            Comparison::LessThan => Compare::LessThan(value),
+        }
+    }
+
+    /// The comparison that is true when this one isn't
+    pub fn negate(self) -> Self {
+        match self {
+            Comparison::Equals => Comparison::NotEquals,
+            Comparison::NotEquals => Comparison::Equals,
+            Comparison::GreaterThanEquals => Comparison::LessThan,
+            Comparison::LessThanEquals => Comparison::GreaterThan,
+            Comparison::GreaterThan => Comparison::LessThanEquals,
+            Comparison::LessThan => Comparison::GreaterThanEquals,
+        }
+    }
+
+    /// The same comparison with both sides swapped, so `a >= b` becomes `b <= a`
+    pub fn flip(self) -> Self {
+        match self {
+            Comparison::GreaterThanEquals => Comparison::LessThanEquals,
+            Comparison::LessThanEquals => Comparison::GreaterThanEquals,
+            Comparison::GreaterThan => Comparison::LessThan,
+            Comparison::LessThan => Comparison::GreaterThan,
+            Comparison::Equals | Comparison::NotEquals => self,
+        }
+    }
+}
+
+/// The other side of a [`Compare::ToKey`]: the value of `key` in the [`LocalState`], with
+/// `offset` added to it if set, for things like "energy >= cost + 10"
+#[derive(Reflect, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyOperand {
+    pub key: String,
+    pub offset: Option<Datum>,
+}
+
+impl KeyOperand {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            offset: None,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Datum) -> Self {
+        self.offset = Some(offset);
+        self
+    }
 }
 
 impl Compare {
-    pub fn value(&self) -> Datum {
+    /// Shorthand for a [`Compare::ToKey`] without any offset
+    pub fn to_key(comparison: Comparison, key: &str) -> Self {
+        Compare::ToKey(comparison, KeyOperand::new(key))
+    }
+
+    /// The other key this Compare refers to, only set for [`Compare::ToKey`]
+    pub fn other_key(&self) -> Option<&str> {
+        match self {
+            Compare::ToKey(_, operand) => Some(&operand.key),
+            _ => None,
+        }
+    }
+
+    /// Turns a [`Compare::ToKey`] into a Compare against the current value of the other key,
+    /// looked up with `get`. Every other Compare is returned as it is
+    pub(crate) fn resolve(
+        &self,
+        get: impl Fn(&str) -> Option<Datum>,
+    ) -> Result<Cow<'_, Compare>, PlanError> {
+        let Compare::ToKey(comparison, operand) = self else {
+            return Ok(Cow::Borrowed(self));
+        };
+        let value = get(&operand.key).ok_or_else(|| PlanError::MissingKey {
+            key: operand.key.clone(),
+            action: None,
+        })?;
+        let value = match &operand.offset {
+            Some(offset) => value
+                .checked_add(offset)
+                .ok_or_else(|| PlanError::TypeMismatch {
+                    key: operand.key.clone(),
+                    found: value,
+                    other: *offset,
+                    action: None,
+                })?,
+            None => value,
+        };
+        Ok(Cow::Owned(comparison.with(value)))
+    }
+
+    /// The first value this Compare is against, `None` for a [`Compare::ToKey`] that isn't
+    /// resolved or an empty [`Compare::OneOf`]
+    pub(crate) fn first_value(&self) -> Option<Datum> {
+        match self {
+            Compare::Equals(v)
+            | Compare::NotEquals(v)
+            | Compare::GreaterThanEquals(v)
+            | Compare::LessThanEquals(v)
+            | Compare::GreaterThan(v)
+            | Compare::LessThan(v)
+            | Compare::InRange(v, _) => Some(*v),
+            Compare::OneOf(values) => values.first().copied(),
+            Compare::ToKey(_, _) => None,
+        }
+    }
+
+    /// Returns the first value in this Compare that is of a different type than `value`, if any.
+    /// The other key of a [`Compare::ToKey`] isn't known here, so only its offset is checked
+    pub fn type_mismatch(&self, value: &Datum) -> Option<Datum> {
+        let mismatch = |v: &Datum| (!v.is_same_type(value)).then_some(*v);
         match self {
-            Compare::Equals(f) => *f,
-            Compare::NotEquals(f) => *f,
-            Compare::GreaterThanEquals(f) => *f,
-            Compare::LessThanEquals(f) => *f,
+            Compare::Equals(v)
+            | Compare::NotEquals(v)
+            | Compare::GreaterThanEquals(v)
+            | Compare::LessThanEquals(v)
+            | Compare::GreaterThan(v)
+            | Compare::LessThan(v) => mismatch(v),
+            Compare::InRange(min, max) => mismatch(min).or_else(|| mismatch(max)),
+            Compare::OneOf(values) => values.iter().find_map(mismatch),
+            Compare::ToKey(_, operand) => operand.offset.as_ref().and_then(mismatch),
+        }
+    }
+
+    /// How far away `value` is from fulfilling this Compare, used for guiding the planner.
+    /// `None` if the types don't match, see [`Compare::type_mismatch`].
+    /// A [`Compare::ToKey`] has to be resolved against the state first, otherwise it's
+    /// always one step away
+    pub fn distance(&self, value: &Datum) -> Option<u64> {
+        if self.type_mismatch(value).is_some() {
+            return None;
+        }
+        if !matches!(
+            self,
+            Compare::Equals(_)
+                | Compare::NotEquals(_)
+                | Compare::GreaterThanEquals(_)
+                | Compare::LessThanEquals(_)
+        ) && compare_values(self, value)
+        {
+            return Some(0);
         }
+        Some(match self {
+            Compare::Equals(v)
+            | Compare::NotEquals(v)
+            | Compare::GreaterThanEquals(v)
+            | Compare::LessThanEquals(v) => value.distance(v),
+            // One more step is needed to get past the value itself
+            Compare::GreaterThan(v) | Compare::LessThan(v) => value.distance(v).saturating_add(1),
+            Compare::InRange(min, max) => value.distance(min).min(value.distance(max)),
+            Compare::OneOf(values) => values.iter().map(|v| value.distance(v)).min().unwrap_or(1),
+            Compare::ToKey(_, _) => 1,
+        })
     }
 }
 
@@ -42,35 +220,144 @@ impl Hash for Compare {
                 3_u8.hash(state);
                 datum.hash(state);
             }
+            Compare::GreaterThan(datum) => {
+                4_u8.hash(state);
+                datum.hash(state);
+            }
+            Compare::LessThan(datum) => {
+                5_u8.hash(state);
+                datum.hash(state);
+            }
+            Compare::InRange(min, max) => {
+                6_u8.hash(state);
+                min.hash(state);
+                max.hash(state);
+            }
+            Compare::OneOf(data) => {
+                7_u8.hash(state);
+                data.hash(state);
+            }
+            Compare::ToKey(comparison, operand) => {
+                8_u8.hash(state);
+                comparison.hash(state);
+                operand.hash(state);
+            }
         }
     }
 }
 
+/// Checks `value` against `comparison`. A [`Compare::ToKey`] needs the rest of the state,
+/// so it's never true here, use [`check_compare`] for those
 pub fn compare_values(comparison: &Compare, value: &Datum) -> bool {
     match comparison {
         Compare::Equals(v) => value == v,
         Compare::NotEquals(v) => value != v,
         Compare::GreaterThanEquals(v) => value >= v,
         Compare::LessThanEquals(v) => value <= v,
+        Compare::GreaterThan(v) => value > v,
+        Compare::LessThan(v) => value < v,
+        Compare::InRange(min, max) => value >= min && value <= max,
+        Compare::OneOf(values) => values.contains(value),
+        Compare::ToKey(_, _) => false,
     }
 }
 
+/// Looks up `key` in the `LocalState` and compares it, failing if the value in the state is
+/// of a different type than the one in the [`Compare`], or is missing and `missing` doesn't
+/// read it
+pub fn check_compare(
+    state: &LocalState,
+    key: &str,
+    compare: &Compare,
+    missing: MissingKeyBehavior,
+) -> Result<bool, PlanError> {
+    let get = |key: &str| state.data.get(key).copied();
+    check_value(key, get(key), compare, get, missing)
+}
+
+/// Compares `found`, the value of `key`, with the other key of a [`Compare::ToKey`] looked
+/// up through `get`. A missing value on either side is read the way `missing` says, see
+/// [`MissingKeyBehavior::Zero`]
+pub(crate) fn check_value(
+    key: &str,
+    found: Option<Datum>,
+    compare: &Compare,
+    get: impl Fn(&str) -> Option<Datum>,
+    missing: MissingKeyBehavior,
+) -> Result<bool, PlanError> {
+    let missing_key = || PlanError::MissingKey {
+        key: key.to_string(),
+        action: None,
+    };
+    if found.is_none() && missing != MissingKeyBehavior::Zero {
+        return Err(missing_key());
+    }
+    // Zero is of the type of the other side
+    let compare = compare.resolve(|other| get(other).or_else(|| missing.read_missing(found)))?;
+    let value = match found {
+        Some(value) => value,
+        None => missing
+            .read_missing(compare.first_value())
+            .ok_or_else(missing_key)?,
+    };
+    if compare.type_mismatch(&value).is_some() {
+        return Err(PlanError::mismatch(key, &value, &compare));
+    }
+    Ok(compare_values(&compare, &value))
+}
+
+/// How far away the value of `key` is from fulfilling `compare`, with values looked up
+/// through `get`. A missing key on either side counts as one step away
+pub(crate) fn compare_distance(
+    key: &str,
+    compare: &Compare,
+    get: impl Fn(&str) -> Option<Datum>,
+) -> Result<u64, PlanError> {
+    let Some(value) = get(key) else {
+        return Ok(1); // Penalty for missing keys
+    };
+    let compare = match compare.resolve(&get) {
+        Err(PlanError::MissingKey { .. }) => return Ok(1),
+        compare => compare?,
+    };
+    compare
+        .distance(&value)
+        .ok_or_else(|| PlanError::mismatch(key, &value, &compare))
+}
+
 /// Checks all the preconditions from the `Action` against passed in `LocalState`
-/// Returns `true` if all the preconditions pass (or if there is none), otherwise `false`
-pub fn check_preconditions(state: &LocalState, action: &Action) -> bool {
-    action.preconditions.iter().all(|(key, value)| {
-        let state_value = state
-            .data
-            .get(key)
-            .unwrap_or_else(|| panic!("Couldn't find key {:#?} in LocalState", key));
-        compare_values(value, state_value)
-    })
+/// Returns `true` if all the preconditions pass (or if there is none), otherwise `false`.
+/// `missing` decides what preconditions on keys that aren't in the state read
+pub fn check_preconditions(
+    state: &LocalState,
+    action: &Action,
+    missing: MissingKeyBehavior,
+) -> Result<bool, PlanError> {
+    let get = |key: &str| state.data.get(key).copied();
+    for (key, compare) in &action.preconditions {
+        if !check_value(key, get(key), compare, get, missing)
+            .map_err(|err| err.in_action(&action.key))?
+        {
+            return Ok(false);
+        }
+    }
+    for condition in &action.conditions {
+        if !condition
+            .evaluate_with(&get, missing)
+            .map_err(|err| err.in_action(&action.key))?
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
 }
 
 #[cfg(test)]
 mod test {
+    use crate::compare::check_compare;
     use crate::compare::check_preconditions;
     use crate::compare::compare_values;
+    use crate::error::PlanError;
     use crate::prelude::*;
 
     #[test]
@@ -78,7 +365,7 @@ mod test {
         let state = LocalState::default().with_datum("is_hungry", Datum::Bool(true));
         let action = Action::default();
 
-        let result = check_preconditions(&state, &action);
+        let result = check_preconditions(&state, &action, MissingKeyBehavior::Error).unwrap();
         assert_eq!(result, true);
     }
 
@@ -88,7 +375,7 @@ mod test {
         let action =
             Action::default().with_precondition("is_hungry", Compare::Equals(Datum::Bool(true)));
 
-        let result = check_preconditions(&state, &action);
+        let result = check_preconditions(&state, &action, MissingKeyBehavior::Error).unwrap();
         assert_eq!(result, true);
     }
 
@@ -98,7 +385,7 @@ mod test {
         let action =
             Action::default().with_precondition("is_hungry", Compare::Equals(Datum::Bool(false)));
 
-        let result = check_preconditions(&state, &action);
+        let result = check_preconditions(&state, &action, MissingKeyBehavior::Error).unwrap();
         assert_eq!(result, false);
     }
 
@@ -111,7 +398,7 @@ mod test {
             .with_precondition("is_hungry", Compare::Equals(Datum::Bool(false)))
             .with_precondition("is_hungry", Compare::Equals(Datum::Bool(true)));
 
-        let result = check_preconditions(&state, &action);
+        let result = check_preconditions(&state, &action, MissingKeyBehavior::Error).unwrap();
         assert_eq!(result, false);
 
         // True + False
@@ -119,10 +406,64 @@ mod test {
             .with_precondition("is_hungry", Compare::Equals(Datum::Bool(true)))
             .with_precondition("is_hungry", Compare::Equals(Datum::Bool(false)));
 
-        let result = check_preconditions(&state, &action);
+        let result = check_preconditions(&state, &action, MissingKeyBehavior::Error).unwrap();
         assert_eq!(result, false);
     }
 
+    #[test]
+    fn test_check_preconditions_missing_key() {
+        let state = LocalState::default().with_datum("is_hungry", Datum::Bool(true));
+        let action =
+            Action::new("eat").with_precondition("is_hungy", Compare::Equals(Datum::Bool(true)));
+
+        let result = check_preconditions(&state, &action, MissingKeyBehavior::Error);
+        assert_eq!(
+            result,
+            Err(PlanError::MissingKey {
+                key: "is_hungy".to_string(),
+                action: Some("eat".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_compare_reads_missing_as_zero() {
+        let state = LocalState::default().with_datum("gold", Datum::I64(10));
+        let zero = MissingKeyBehavior::Zero;
+
+        let no_debt = Compare::LessThanEquals(Datum::I64(0));
+        assert_eq!(Ok(true), check_compare(&state, "debt", &no_debt, zero));
+        assert!(check_compare(&state, "debt", &no_debt, MissingKeyBehavior::Create).is_err());
+        // The other key of a ToKey reads as zero of the type of this one
+        let out_of_debt = Compare::to_key(Comparison::GreaterThan, "debt");
+        assert_eq!(Ok(true), check_compare(&state, "gold", &out_of_debt, zero));
+        assert_eq!(
+            Err(PlanError::MissingKey {
+                key: "debt".to_string(),
+                action: None
+            }),
+            check_compare(&state, "debt", &Compare::OneOf(vec![]), zero)
+        );
+    }
+
+    #[test]
+    fn test_check_preconditions_type_mismatch() {
+        let state = LocalState::default().with_datum("energy", Datum::I64(10));
+        let action = Action::new("run")
+            .with_precondition("energy", Compare::GreaterThanEquals(Datum::F64(5.0)));
+
+        let result = check_preconditions(&state, &action, MissingKeyBehavior::Error);
+        assert_eq!(
+            result,
+            Err(PlanError::TypeMismatch {
+                key: "energy".to_string(),
+                found: Datum::I64(10),
+                other: Datum::F64(5.0),
+                action: Some("run".to_string())
+            })
+        );
+    }
+
     #[test]
     fn test_greater_than_equals() {
         let cases = vec![
@@ -185,4 +526,121 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_strict_comparisons() {
+        let value = Datum::I64(10);
+        assert!(!compare_values(
+            &Compare::GreaterThan(Datum::I64(10)),
+            &value
+        ));
+        assert!(compare_values(&Compare::GreaterThan(Datum::I64(9)), &value));
+        assert!(!compare_values(&Compare::LessThan(Datum::I64(10)), &value));
+        assert!(compare_values(&Compare::LessThan(Datum::I64(11)), &value));
+    }
+
+    #[test]
+    fn test_in_range_and_one_of() {
+        let in_range = Compare::InRange(Datum::I64(5), Datum::I64(10));
+        assert!(compare_values(&in_range, &Datum::I64(5)));
+        assert!(compare_values(&in_range, &Datum::I64(10)));
+        assert!(!compare_values(&in_range, &Datum::I64(11)));
+
+        let one_of = Compare::OneOf(vec![Datum::Enum(1.into()), Datum::Enum(3.into())]);
+        assert!(compare_values(&one_of, &Datum::Enum(3.into())));
+        assert!(!compare_values(&one_of, &Datum::Enum(2.into())));
+        assert!(!compare_values(
+            &Compare::OneOf(vec![]),
+            &Datum::Enum(2.into())
+        ));
+    }
+
+    #[test]
+    fn test_to_key() {
+        let state = LocalState::default()
+            .with_datum("gold", Datum::I64(10))
+            .with_datum("price", Datum::I64(8))
+            .with_datum("discount", Datum::F64(0.5));
+
+        let affordable = Compare::to_key(Comparison::GreaterThanEquals, "price");
+        assert_eq!(
+            Ok(true),
+            check_compare(&state, "gold", &affordable, MissingKeyBehavior::Error)
+        );
+        assert_eq!(
+            Ok(false),
+            check_compare(
+                &state,
+                "gold",
+                &Compare::to_key(Comparison::LessThan, "price"),
+                MissingKeyBehavior::Error
+            )
+        );
+
+        let with_spare = Compare::ToKey(
+            Comparison::GreaterThanEquals,
+            KeyOperand::new("price").with_offset(Datum::I64(3)),
+        );
+        assert_eq!(
+            Ok(false),
+            check_compare(&state, "gold", &with_spare, MissingKeyBehavior::Error)
+        );
+
+        assert_eq!(
+            Err(PlanError::MissingKey {
+                key: "tax".to_string(),
+                action: None
+            }),
+            check_compare(
+                &state,
+                "gold",
+                &Compare::to_key(Comparison::LessThan, "tax"),
+                MissingKeyBehavior::Error
+            )
+        );
+        assert_eq!(
+            Err(PlanError::TypeMismatch {
+                key: "gold".to_string(),
+                found: Datum::I64(10),
+                other: Datum::F64(0.5),
+                action: None
+            }),
+            check_compare(
+                &state,
+                "gold",
+                &Compare::to_key(Comparison::Equals, "discount"),
+                MissingKeyBehavior::Error
+            )
+        );
+    }
+
+    #[test]
+    fn test_distance() {
+        let value = Datum::I64(10);
+        assert_eq!(
+            Some(0),
+            Compare::GreaterThan(Datum::I64(5)).distance(&value)
+        );
+        assert_eq!(
+            Some(1),
+            Compare::GreaterThan(Datum::I64(10)).distance(&value)
+        );
+        assert_eq!(Some(3), Compare::LessThan(Datum::I64(8)).distance(&value));
+        assert_eq!(
+            Some(2),
+            Compare::InRange(Datum::I64(0), Datum::I64(8)).distance(&value)
+        );
+        assert_eq!(
+            Some(4),
+            Compare::OneOf(vec![Datum::I64(2), Datum::I64(14)]).distance(&value)
+        );
+        assert_eq!(
+            None,
+            Compare::InRange(Datum::I64(0), Datum::F64(8.0)).distance(&value)
+        );
+        assert_eq!(
+            Some(Datum::F64(8.0)),
+            Compare::InRange(Datum::I64(0), Datum::F64(8.0)).type_mismatch(&value)
+        );
+    }
 }
@@ -0,0 +1,256 @@
+// crate "dogoap" file condition.rs
+use bevy_reflect::Reflect;
+
+use crate::{
+    compare::{check_value, compare_distance, Compare},
+    datum::Datum,
+    error::PlanError,
+    localstate::LocalState,
+    mutator::MissingKeyBehavior,
+};
+
+/// A tree of [`Compare`]s combined with All/Any, for preconditions and goals that can't
+/// be written as a flat list of requirements that all have to be true, like
+/// "has_axe or has_sword" or "energy >= 20 unless at_home".
+///
+/// There is no `Not` variant, as [`Condition::not`] turns the condition into its opposite
+/// right away, so the planner only ever has to deal with All and Any
+#[derive(Reflect, Clone, Debug, PartialEq, Eq, Hash)]
+// Condition contains itself, so bounding the fields on being reflectable can't be satisfied
+#[reflect(no_field_bounds)]
+pub enum Condition {
+    Compare(String, Compare),
+    /// True if all of the conditions are, which is the case for an empty list
+    All(Vec<Condition>),
+    /// True if at least one of the conditions is, which is never the case for an empty list
+    Any(Vec<Condition>),
+}
+
+/// One way of fulfilling a [`Condition`], as requirements that all have to be true
+pub type Alternative = Vec<(String, Compare)>;
+
+impl Condition {
+    pub fn compare(key: &str, compare: Compare) -> Self {
+        Condition::Compare(key.to_string(), compare)
+    }
+
+    pub fn all(conditions: impl IntoIterator<Item = Condition>) -> Self {
+        Condition::All(conditions.into_iter().collect())
+    }
+
+    pub fn any(conditions: impl IntoIterator<Item = Condition>) -> Self {
+        Condition::Any(conditions.into_iter().collect())
+    }
+
+    /// True when `condition` isn't, see [`Condition::negate`]
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(condition: Condition) -> Self {
+        condition.negate()
+    }
+
+    /// The opposite of this condition. All becomes Any and the other way around, and every
+    /// [`Compare`] is replaced by its opposite, like `GreaterThanEquals` by `LessThan`
+    pub fn negate(&self) -> Condition {
+        match self {
+            Condition::Compare(key, compare) => negate_compare(key, compare),
+            Condition::All(conditions) => {
+                Condition::Any(conditions.iter().map(Self::negate).collect())
+            }
+            Condition::Any(conditions) => {
+                Condition::All(conditions.iter().map(Self::negate).collect())
+            }
+        }
+    }
+
+    /// Every key this condition refers to, possibly more than once
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            Condition::Compare(key, compare) => std::iter::once(key.as_str())
+                .chain(compare.other_key())
+                .collect(),
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().flat_map(Self::keys).collect()
+            }
+        }
+    }
+
+    /// The same condition with every key and [`Compare`] replaced by the condition `f`
+    /// makes of them
+    pub(crate) fn try_map(
+        &self,
+        f: &impl Fn(&str, &Compare) -> Result<Condition, PlanError>,
+    ) -> Result<Condition, PlanError> {
+        Ok(match self {
+            Condition::Compare(key, compare) => f(key, compare)?,
+            Condition::All(conditions) => Condition::All(
+                conditions
+                    .iter()
+                    .map(|c| c.try_map(f))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Condition::Any(conditions) => Condition::Any(
+                conditions
+                    .iter()
+                    .map(|c| c.try_map(f))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    /// All the different ways this condition can be fulfilled. An empty list means it
+    /// never can be
+    pub fn alternatives(&self) -> Vec<Alternative> {
+        match self {
+            Condition::Compare(key, compare) => vec![vec![(key.clone(), compare.clone())]],
+            Condition::All(conditions) => {
+                conditions
+                    .iter()
+                    .fold(vec![vec![]], |alternatives, condition| {
+                        let mut combined = vec![];
+                        for alternative in &alternatives {
+                            for other in condition.alternatives() {
+                                let mut alternative = alternative.clone();
+                                alternative.extend(other);
+                                combined.push(alternative);
+                            }
+                        }
+                        combined
+                    })
+            }
+            Condition::Any(conditions) => conditions.iter().flat_map(Self::alternatives).collect(),
+        }
+    }
+
+    /// Checks the condition against `state`, failing if a key is missing or of the wrong type
+    pub fn evaluate(&self, state: &LocalState) -> Result<bool, PlanError> {
+        self.evaluate_with(
+            &|key| state.data.get(key).copied(),
+            MissingKeyBehavior::Error,
+        )
+    }
+
+    /// Same as [`Condition::evaluate`], with values looked up through `get` and missing keys
+    /// read the way `missing` says
+    pub(crate) fn evaluate_with(
+        &self,
+        get: &dyn Fn(&str) -> Option<Datum>,
+        missing: MissingKeyBehavior,
+    ) -> Result<bool, PlanError> {
+        match self {
+            Condition::Compare(key, compare) => check_value(key, get(key), compare, get, missing),
+            Condition::All(conditions) => {
+                for condition in conditions {
+                    if !condition.evaluate_with(get, missing)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Condition::Any(conditions) => {
+                for condition in conditions {
+                    if condition.evaluate_with(get, missing)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// How far away the state is from fulfilling the condition, like
+    /// [`LocalState::distance_to_goal`]. All conditions add up, while only the closest of
+    /// the Any conditions counts
+    pub(crate) fn distance_with(
+        &self,
+        get: &dyn Fn(&str) -> Option<Datum>,
+    ) -> Result<u64, PlanError> {
+        match self {
+            Condition::Compare(key, compare) => compare_distance(key, compare, get),
+            Condition::All(conditions) => conditions
+                .iter()
+                .map(|condition| condition.distance_with(get))
+                .sum(),
+            Condition::Any(conditions) => {
+                let mut closest = None;
+                for condition in conditions {
+                    let distance = condition.distance_with(get)?;
+                    closest = Some(closest.map_or(distance, |closest: u64| closest.min(distance)));
+                }
+                Ok(closest.unwrap_or(1))
+            }
+        }
+    }
+}
+
+fn negate_compare(key: &str, compare: &Compare) -> Condition {
+    let leaf = |compare| Condition::compare(key, compare);
+    match compare {
+        Compare::Equals(v) => leaf(Compare::NotEquals(*v)),
+        Compare::NotEquals(v) => leaf(Compare::Equals(*v)),
+        Compare::GreaterThanEquals(v) => leaf(Compare::LessThan(*v)),
+        Compare::LessThanEquals(v) => leaf(Compare::GreaterThan(*v)),
+        Compare::GreaterThan(v) => leaf(Compare::LessThanEquals(*v)),
+        Compare::LessThan(v) => leaf(Compare::GreaterThanEquals(*v)),
+        Compare::InRange(min, max) => Condition::Any(vec![
+            leaf(Compare::LessThan(*min)),
+            leaf(Compare::GreaterThan(*max)),
+        ]),
+        Compare::OneOf(values) => Condition::All(
+            values
+                .iter()
+                .map(|v| leaf(Compare::NotEquals(*v)))
+                .collect(),
+        ),
+        Compare::ToKey(comparison, operand) => {
+            leaf(Compare::ToKey(comparison.negate(), operand.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_weapon() -> Condition {
+        Condition::any([
+            Condition::compare("has_axe", Compare::Equals(Datum::Bool(true))),
+            Condition::compare("has_sword", Compare::Equals(Datum::Bool(true))),
+        ])
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let state = LocalState::new()
+            .with_datum("has_axe", Datum::Bool(false))
+            .with_datum("has_sword", Datum::Bool(true));
+        assert_eq!(Ok(true), has_weapon().evaluate(&state));
+        assert_eq!(Ok(false), Condition::not(has_weapon()).evaluate(&state));
+        assert_eq!(Ok(true), Condition::all([]).evaluate(&state));
+        assert_eq!(Ok(false), Condition::any([]).evaluate(&state));
+    }
+
+    #[test]
+    fn test_alternatives() {
+        // energy >= 20 unless at_home
+        let condition = Condition::any([
+            Condition::compare("at_home", Compare::Equals(Datum::Bool(true))),
+            Condition::compare("energy", Compare::GreaterThanEquals(Datum::I64(20))),
+        ]);
+        assert_eq!(2, condition.alternatives().len());
+        assert_eq!(
+            vec![vec![
+                ("at_home".to_string(), Compare::NotEquals(Datum::Bool(true))),
+                ("energy".to_string(), Compare::LessThan(Datum::I64(20))),
+            ]],
+            Condition::not(condition).alternatives()
+        );
+
+        let condition = Condition::all([has_weapon(), has_weapon()]);
+        assert_eq!(4, condition.alternatives().len());
+        assert!(Condition::any([]).alternatives().is_empty());
+        assert_eq!(
+            vec![Vec::<(String, Compare)>::new()],
+            Condition::all([]).alternatives()
+        );
+    }
+}
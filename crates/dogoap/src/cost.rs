@@ -0,0 +1,71 @@
+// crate "dogoap" file cost.rs
+use bevy_reflect::Reflect;
+
+use crate::{action::Action, compact::SearchState, effect::Effect, error::PlanError};
+
+/// How the planner deals with [`Action`]s that have more than one possible outcome, one
+/// per [`Effect`]
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OutcomeMode {
+    /// Each outcome is planned with as if it was certain, at its own cost. Plans might count
+    /// on outcomes that rarely happen
+    #[default]
+    BestBranch,
+    /// The cost of each outcome is divided by its probability, which is what it costs on
+    /// average to keep retrying the action until that outcome happens. A "fish" action that
+    /// only catches a fish every fourth time costs four times as much as one that always does
+    ExpectedCost,
+}
+
+impl OutcomeMode {
+    /// What planning with an outcome of `action` costs, `None` if the outcome never happens
+    pub(crate) fn cost(
+        self,
+        action: &str,
+        cost: f64,
+        probability: f64,
+    ) -> Result<Option<f64>, PlanError> {
+        let cost = check_cost(action, cost)?;
+        if probability.is_nan() || !(0.0..=1.0).contains(&probability) {
+            return Err(PlanError::InvalidProbability {
+                action: action.to_string(),
+                probability,
+            });
+        }
+        if probability == 0.0 {
+            return Ok(None);
+        }
+        Ok(Some(match self {
+            OutcomeMode::BestBranch => cost,
+            OutcomeMode::ExpectedCost => cost / probability,
+        }))
+    }
+}
+
+/// Decides what an [`Action`] costs when taken from a state, for costs that depend on more
+/// than the state itself, like the real distance between two places in the game world.
+///
+/// Gets called once for each [`Effect`] of the action, as each is a separate outcome.
+/// Without one, the planner uses [`Effect::cost_at`], which is the `cost` of the effect
+/// unless one of its `conditional_costs` matches. Implementations can call it themselves
+/// for the actions they don't know better about.
+///
+/// [`AdmissibleHeuristic`](crate::heuristic::AdmissibleHeuristic) assumes no action costs less
+/// than [`Effect::lowest_cost`], so return lower costs than that and it might not find the
+/// cheapest plan anymore. Costs that are negative or NaN stop the planning with
+/// [`PlanError::InvalidCost`]
+pub trait CostModel: Send + Sync {
+    fn cost(&self, action: &Action, effect: &Effect, state: &SearchState)
+        -> Result<f64, PlanError>;
+}
+
+/// Passes `cost` through if the search can work with it
+pub(crate) fn check_cost(action: &str, cost: f64) -> Result<f64, PlanError> {
+    if cost.is_nan() || cost < 0.0 {
+        return Err(PlanError::InvalidCost {
+            action: action.to_string(),
+            cost,
+        });
+    }
+    Ok(cost)
+}
@@ -1,16 +1,25 @@
+use std::cmp::Ordering;
 use std::fmt::Display;
 use std::hash::Hash;
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
 use bevy_reflect::Reflect;
 
-/// Represents one value of either `bool`, `i64`, `f64` or a `Enum` as `usize`.
-#[derive(Reflect, Clone, Debug, PartialOrd, Copy)]
+use crate::enum_value::EnumValue;
+use crate::symbol::Symbol;
+
+/// Represents one value of either `bool`, `i64`, `f64`, a variant of an `Enum` or an
+/// interned [`Symbol`] for names like item IDs.
+#[derive(Reflect, Clone, Debug, Copy)]
 pub enum Datum {
     Bool(bool),
     I64(i64),
     F64(f64),
-    Enum(usize),
+    /// Only the same type as variants of the same enum, see [`EnumValue`]
+    Enum(EnumValue),
+    /// Only ever equal or not to another symbol, so comparing with anything but
+    /// `Equals`, `NotEquals` and `OneOf` only holds for the very same symbol
+    Symbol(Symbol),
 }
 
 impl Hash for Datum {
@@ -21,6 +30,7 @@ impl Hash for Datum {
             Datum::I64(i) => i.hash(state),
             Datum::F64(f) => f.to_bits().hash(state),
             Datum::Enum(u) => u.hash(state),
+            Datum::Symbol(s) => s.hash(state),
         }
     }
 }
@@ -32,6 +42,7 @@ impl PartialEq for Datum {
             (Self::I64(l0), Self::I64(r0)) => l0 == r0,
             (Self::F64(l0), Self::F64(r0)) => l0 == r0,
             (Self::Enum(l0), Self::Enum(r0)) => l0 == r0,
+            (Self::Symbol(l0), Self::Symbol(r0)) => l0 == r0,
             _ => false,
         }
     }
@@ -39,26 +50,143 @@ impl PartialEq for Datum {
 
 impl Eq for Datum {}
 
+impl PartialOrd for Datum {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Bool(l0), Self::Bool(r0)) => l0.partial_cmp(r0),
+            (Self::I64(l0), Self::I64(r0)) => l0.partial_cmp(r0),
+            (Self::F64(l0), Self::F64(r0)) => l0.partial_cmp(r0),
+            (Self::Enum(l0), Self::Enum(r0)) => l0.partial_cmp(r0),
+            // The order symbols were interned in says nothing about their names
+            (Self::Symbol(l0), Self::Symbol(r0)) => (l0 == r0).then_some(Ordering::Equal),
+            _ => self.variant_index().partial_cmp(&other.variant_index()),
+        }
+    }
+}
+
 impl Datum {
+    /// Panics if the [`Datum`] are of different types, see [`Datum::checked_distance`]
     pub fn distance(&self, other: &Datum) -> u64 {
+        self.checked_distance(other)
+            .expect("Cannot calculate distance between different Datum types")
+    }
+
+    /// How far apart two [`Datum`] are, `None` if they're of different types
+    pub fn checked_distance(&self, other: &Datum) -> Option<u64> {
         match (self, other) {
-            (Datum::Bool(a), Datum::Bool(b)) => {
-                if a == b {
-                    0
-                } else {
-                    1
-                }
+            (Datum::Bool(a), Datum::Bool(b)) => Some(if a == b { 0 } else { 1 }),
+            (Datum::I64(a), Datum::I64(b)) => Some(a.abs_diff(*b)),
+            (Datum::F64(a), Datum::F64(b)) => Some((a - b).abs() as u64),
+            (Datum::Enum(a), Datum::Enum(b)) if a.is_same_enum(b) => {
+                Some(if a == b { 0 } else { 1 })
             }
-            (Datum::I64(a), Datum::I64(b)) => (a - b).unsigned_abs(),
-            (Datum::F64(a), Datum::F64(b)) => (a - b).abs() as u64,
-            (Datum::Enum(a), Datum::Enum(b)) => {
-                if a == b {
-                    0
-                } else {
-                    1
-                }
+            (Datum::Symbol(a), Datum::Symbol(b)) => Some(if a == b { 0 } else { 1 }),
+            _ => None,
+        }
+    }
+
+    /// Adds two [`Datum`], `None` if they're of different types, can't be added or the sum
+    /// of two `I64` overflows
+    pub fn checked_add(&self, other: &Datum) -> Option<Datum> {
+        match (self, other) {
+            (Datum::I64(a), Datum::I64(b)) => a.checked_add(*b).map(Datum::I64),
+            (Datum::F64(a), Datum::F64(b)) => Some(Datum::F64(a + b)),
+            _ => None,
+        }
+    }
+
+    /// Subtracts two [`Datum`], `None` if they're of different types, can't be subtracted or
+    /// the difference of two `I64` overflows
+    pub fn checked_sub(&self, other: &Datum) -> Option<Datum> {
+        match (self, other) {
+            (Datum::I64(a), Datum::I64(b)) => a.checked_sub(*b).map(Datum::I64),
+            (Datum::F64(a), Datum::F64(b)) => Some(Datum::F64(a - b)),
+            _ => None,
+        }
+    }
+
+    /// Multiplies two [`Datum`], `None` if they can't be multiplied. An `I64` can also be
+    /// multiplied by an `F64`, for things like percentages, and is then rounded to the
+    /// nearest whole number
+    pub fn checked_mul(&self, other: &Datum) -> Option<Datum> {
+        match (self, other) {
+            (Datum::I64(a), Datum::I64(b)) => Some(Datum::I64(a.saturating_mul(*b))),
+            (Datum::I64(a), Datum::F64(b)) => Some(Datum::I64((*a as f64 * b).round() as i64)),
+            (Datum::F64(a), Datum::F64(b)) => Some(Datum::F64(a * b)),
+            _ => None,
+        }
+    }
+
+    /// Divides two [`Datum`], `None` if they can't be divided or `other` is zero. Like
+    /// [`Datum::checked_mul`], an `I64` can be divided by an `F64`. Dividing two `I64`
+    /// rounds towards zero, and is `None` for `i64::MIN / -1`, which doesn't fit an `I64`
+    pub fn checked_div(&self, other: &Datum) -> Option<Datum> {
+        if other.is_zero() {
+            return None;
+        }
+        match (self, other) {
+            (Datum::I64(a), Datum::I64(b)) => a.checked_div(*b).map(Datum::I64),
+            (Datum::I64(a), Datum::F64(b)) => Some(Datum::I64((*a as f64 / b).round() as i64)),
+            (Datum::F64(a), Datum::F64(b)) => Some(Datum::F64(a / b)),
+            _ => None,
+        }
+    }
+
+    /// Zero of the same type, which is `false` for a `Bool`, the first variant of the same
+    /// enum for an `Enum` and the empty name for a `Symbol`
+    pub fn zero(&self) -> Datum {
+        match self {
+            Datum::Bool(_) => Datum::Bool(false),
+            Datum::I64(_) => Datum::I64(0),
+            Datum::F64(_) => Datum::F64(0.0),
+            Datum::Enum(v) => Datum::Enum(EnumValue::from_symbol(v.enum_name(), 0)),
+            Datum::Symbol(_) => Datum::Symbol(Symbol::default()),
+        }
+    }
+
+    /// If this is an `I64` or `F64` that is zero
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Datum::I64(v) => *v == 0,
+            Datum::F64(v) => *v == 0.0,
+            Datum::Bool(_) | Datum::Enum(_) | Datum::Symbol(_) => false,
+        }
+    }
+
+    /// If both [`Datum`] are the same variant, regardless of their values. An `Enum` is only
+    /// the same type as variants of the same enum
+    pub fn is_same_type(&self, other: &Datum) -> bool {
+        match (self, other) {
+            (Datum::Enum(a), Datum::Enum(b)) => a.is_same_enum(b),
+            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+        }
+    }
+
+    /// A `Symbol` with the name `name`, see [`Symbol::new`]
+    pub fn symbol(name: &str) -> Datum {
+        Datum::Symbol(Symbol::new(name))
+    }
+
+    /// For ordering different variants the way they're declared
+    fn variant_index(&self) -> u8 {
+        match self {
+            Datum::Bool(_) => 0,
+            Datum::I64(_) => 1,
+            Datum::F64(_) => 2,
+            Datum::Enum(_) => 3,
+            Datum::Symbol(_) => 4,
+        }
+    }
+
+    /// A `F64` rounded to the closest multiple of `step`, anything else as it is. A `step`
+    /// that isn't finite and above zero leaves the value as it is as well
+    pub fn quantize(&self, step: f64) -> Datum {
+        match self {
+            // Adding zero turns -0.0 into 0.0, which hashes differently
+            Datum::F64(v) if step > 0.0 && step.is_finite() => {
+                Datum::F64((v / step).round() * step + 0.0)
             }
-            _ => panic!("Cannot calculate distance between different Datum types"),
+            _ => *self,
         }
     }
 }
@@ -78,6 +206,9 @@ impl Display for Datum {
             Self::Enum(v) => {
                 write!(f, "Datum:Enum({})", v)
             }
+            Self::Symbol(v) => {
+                write!(f, "Datum:Symbol({})", v)
+            }
         }
     }
 }
@@ -86,14 +217,12 @@ impl Add for &Datum {
     type Output = Datum;
 
     fn add(self, other: &Datum) -> Datum {
-        match (self, other) {
-            (Datum::I64(a), Datum::I64(b)) => Datum::I64(a + b),
-            (Datum::F64(a), Datum::F64(b)) => Datum::F64(a + b),
-            _ => panic!(
+        self.checked_add(other).unwrap_or_else(|| {
+            panic!(
                 "Unsupported addition between Datum variants, {:?} - {:?}",
                 self, other
-            ),
-        }
+            )
+        })
     }
 }
 
@@ -110,14 +239,12 @@ impl Sub for &Datum {
     type Output = Datum;
 
     fn sub(self, other: &Datum) -> Datum {
-        match (self, other) {
-            (Datum::I64(a), Datum::I64(b)) => Datum::I64(a - b),
-            (Datum::F64(a), Datum::F64(b)) => Datum::F64(a - b),
-            _ => panic!(
+        self.checked_sub(other).unwrap_or_else(|| {
+            panic!(
                 "Unsupported negation between Datum variants, {:?} - {:?}",
                 self, other
-            ),
-        }
+            )
+        })
     }
 }
 
@@ -225,10 +352,67 @@ mod test {
         assert_eq!(Datum::F64(2.5).distance(&Datum::F64(-2.5)), 5);
         assert_eq!(Datum::F64(2.88).distance(&Datum::F64(1.03)), 1);
 
-        assert_eq!(Datum::Enum(0).distance(&Datum::Enum(0)), 0);
-        assert_eq!(Datum::Enum(1).distance(&Datum::Enum(1)), 0);
-        assert_eq!(Datum::Enum(0).distance(&Datum::Enum(1)), 1);
-        assert_eq!(Datum::Enum(1).distance(&Datum::Enum(0)), 1);
-        assert_eq!(Datum::Enum(1).distance(&Datum::Enum(5)), 1);
+        assert_eq!(Datum::Enum(0.into()).distance(&Datum::Enum(0.into())), 0);
+        assert_eq!(Datum::Enum(1.into()).distance(&Datum::Enum(1.into())), 0);
+        assert_eq!(Datum::Enum(0.into()).distance(&Datum::Enum(1.into())), 1);
+        assert_eq!(Datum::Enum(1.into()).distance(&Datum::Enum(0.into())), 1);
+        assert_eq!(Datum::Enum(1.into()).distance(&Datum::Enum(5.into())), 1);
+
+        let iron = Datum::symbol("iron_ore");
+        assert_eq!(iron.distance(&Datum::symbol("iron_ore")), 0);
+        assert_eq!(iron.distance(&Datum::symbol("gold_ore")), 1);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let iron = Datum::symbol("iron_ore");
+        let gold = Datum::symbol("gold_ore");
+        assert_eq!(iron, Datum::Symbol(Symbol::new("iron_ore")));
+        assert_ne!(iron, gold);
+        assert_eq!("Datum:Symbol(iron_ore)", iron.to_string());
+        // Symbols have no order, only the same symbol is both >= and <=
+        assert_eq!(Some(std::cmp::Ordering::Equal), iron.partial_cmp(&iron));
+        assert_eq!(None, iron.partial_cmp(&gold));
+        assert_eq!(None, iron.checked_add(&gold));
+        assert_eq!(Datum::symbol(""), iron.zero());
+    }
+
+    #[test]
+    fn test_typed_enums() {
+        let home = Datum::Enum(EnumValue::new("Location", 0));
+        let market = Datum::Enum(EnumValue::new("Location", 2));
+        let sword = Datum::Enum(EnumValue::new("Weapon", 0));
+        assert!(home.is_same_type(&market));
+        assert!(!home.is_same_type(&sword));
+        assert!(!home.is_same_type(&Datum::Enum(0.into())));
+        assert_ne!(home, sword);
+        assert!(home < market);
+        assert_eq!(None, home.partial_cmp(&sword));
+        assert_eq!(None, home.checked_distance(&sword));
+        assert_eq!(home, market.zero());
+        assert_eq!("Datum:Enum(Weapon::0)", sword.to_string());
+    }
+
+    #[test]
+    fn test_checked_mismatch() {
+        assert_eq!(Datum::Bool(true).checked_distance(&Datum::I64(1)), None);
+        assert_eq!(Datum::I64(1).checked_add(&Datum::F64(1.0)), None);
+        assert_eq!(Datum::Bool(true).checked_sub(&Datum::Bool(false)), None);
+        assert_eq!(
+            Datum::I64(1).checked_add(&Datum::I64(2)),
+            Some(Datum::I64(3))
+        );
+    }
+
+    #[test]
+    fn test_checked_overflow() {
+        let (min, max) = (Datum::I64(i64::MIN), Datum::I64(i64::MAX));
+        assert_eq!(max.checked_add(&Datum::I64(1)), None);
+        assert_eq!(min.checked_sub(&Datum::I64(1)), None);
+        assert_eq!(min.checked_div(&Datum::I64(-1)), None);
+        assert_eq!(min.checked_div(&Datum::I64(1)), Some(min));
+        // The distance always fits a u64
+        assert_eq!(min.checked_distance(&max), Some(u64::MAX));
+        assert_eq!(max.distance(&min), u64::MAX);
     }
 }
@@ -0,0 +1,629 @@
+// crate "dogoap" file domain.rs
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::{
+    action::Action, condition::Condition, cost::check_cost, datum::Datum, goal::Goal,
+    localstate::LocalState, mutator::Mutator, planner::PlanOptions, quantization::Quantization,
+};
+
+/// Everything needed to plan: the initial [`LocalState`], the available [`Action`]s and the
+/// [`Goal`]s we might want to reach. Mostly useful for checking with [`Domain::validate`]
+/// that everything fits together, before handing it over to the planner
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Domain {
+    pub state: LocalState,
+    pub actions: Vec<Action>,
+    pub goals: Vec<Goal>,
+    /// How finely the floats of each key are told apart, used by [`Domain::plan_options`]
+    pub quantization: Quantization,
+}
+
+/// Something wrong with a [`Domain`] that would make planning fail or behave unexpectedly
+#[derive(Clone, Debug, PartialEq)]
+pub enum DomainProblem {
+    /// A precondition refers to a key that isn't in the [`LocalState`]
+    MissingPreconditionKey { action: String, key: String },
+    /// A condition in the `conditional_costs` of an [`Effect`](crate::effect::Effect) refers
+    /// to a key that isn't in the [`LocalState`]
+    MissingCostKey { action: String, key: String },
+    /// A goal requirement refers to a key that isn't in the [`LocalState`]. `goal` is the
+    /// index of the [`Goal`] in [`Domain::goals`]
+    MissingGoalKey { goal: usize, key: String },
+    /// `Increment` or `Decrement` with a `Datum::Bool`, `Datum::Enum` or `Datum::Symbol`,
+    /// which can't be added
+    NonNumericMutator {
+        action: String,
+        key: String,
+        value: Datum,
+    },
+    /// A mutator uses a different type of [`Datum`] than the [`LocalState`] has for the key
+    MutatorTypeMismatch {
+        action: String,
+        key: String,
+        found: Datum,
+        other: Datum,
+    },
+    /// A `Divide` mutator that divides by zero
+    DivisionByZero { action: String, key: String },
+    /// A `Toggle` mutator on a key that isn't a `Datum::Bool`
+    NonBoolToggle {
+        action: String,
+        key: String,
+        found: Datum,
+    },
+    /// A `CopyFrom` mutator copies from a key that isn't in the [`LocalState`]
+    MissingCopySource {
+        action: String,
+        key: String,
+        source: String,
+    },
+    /// A mutator other than `Set` or `CopyFrom` changes a key that isn't in the
+    /// [`LocalState`], which it needs a value of to change
+    MissingMutatorKey { action: String, key: String },
+    /// An [`Effect`](crate::effect::Effect) with a cost that is negative or NaN, including its
+    /// conditional costs
+    InvalidCost { action: String, cost: f64 },
+    /// The probabilities of the [`Effect`](crate::effect::Effect)s of an action add up to more
+    /// than one, or one of them is NaN or outside of 0 to 1
+    InvalidProbabilities {
+        action: String,
+        probabilities: Vec<f64>,
+    },
+    /// An action without any [`Effect`](crate::effect::Effect), which the planner never uses
+    EmptyEffects { action: String },
+    /// More than one action with the same key
+    DuplicateActionKey { action: String },
+    /// A [`Quantization`] step that isn't finite and above zero, or for a key that isn't a
+    /// `Datum::F64` in the [`LocalState`], which does nothing
+    InvalidQuantizationStep { key: String, step: f64 },
+}
+
+impl Display for DomainProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DomainProblem::MissingPreconditionKey { action, key } => write!(
+                f,
+                "Action {:?} has a precondition on key {:?}, which isn't in LocalState",
+                action, key
+            ),
+            DomainProblem::MissingCostKey { action, key } => write!(
+                f,
+                "Action {:?} has a conditional cost on key {:?}, which isn't in LocalState",
+                action, key
+            ),
+            DomainProblem::MissingGoalKey { goal, key } => write!(
+                f,
+                "Goal #{} requires key {:?}, which isn't in LocalState",
+                goal, key
+            ),
+            DomainProblem::NonNumericMutator { action, key, value } => write!(
+                f,
+                "Action {:?} increments or decrements key {:?} with {}, which isn't a number",
+                action, key, value
+            ),
+            DomainProblem::MutatorTypeMismatch {
+                action,
+                key,
+                found,
+                other,
+            } => write!(
+                f,
+                "Action {:?} mutates key {:?} with {}, but LocalState has {}",
+                action, key, other, found
+            ),
+            DomainProblem::DivisionByZero { action, key } => {
+                write!(f, "Action {:?} divides key {:?} by zero", action, key)
+            }
+            DomainProblem::NonBoolToggle { action, key, found } => write!(
+                f,
+                "Action {:?} toggles key {:?}, but LocalState has {}, which isn't a bool",
+                action, key, found
+            ),
+            DomainProblem::MissingCopySource {
+                action,
+                key,
+                source,
+            } => write!(
+                f,
+                "Action {:?} copies key {:?} into {:?}, but it isn't in LocalState",
+                action, source, key
+            ),
+            DomainProblem::MissingMutatorKey { action, key } => write!(
+                f,
+                "Action {:?} changes key {:?}, which isn't in LocalState",
+                action, key
+            ),
+            DomainProblem::InvalidCost { action, cost } => write!(
+                f,
+                "Action {:?} costs {}, but costs can't be negative or NaN",
+                action, cost
+            ),
+            DomainProblem::InvalidProbabilities {
+                action,
+                probabilities,
+            } => write!(
+                f,
+                "Action {:?} has outcomes with probabilities {:?}, which don't add up to at most 1",
+                action, probabilities
+            ),
+            DomainProblem::EmptyEffects { action } => {
+                write!(
+                    f,
+                    "Action {:?} has no effects and will never be used",
+                    action
+                )
+            }
+            DomainProblem::DuplicateActionKey { action } => {
+                write!(f, "Action key {:?} is used more than once", action)
+            }
+            DomainProblem::InvalidQuantizationStep { key, step } => write!(
+                f,
+                "Key {:?} is quantized with a step of {}, but only floats can be quantized, with a step above zero",
+                key, step
+            ),
+        }
+    }
+}
+
+impl Domain {
+    pub fn new(state: LocalState) -> Self {
+        Self {
+            state,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn with_goal(mut self, goal: Goal) -> Self {
+        self.goals.push(goal);
+        self
+    }
+
+    pub fn with_quantization_step(mut self, key: &str, step: f64) -> Self {
+        self.quantization = self.quantization.with_step(key, step);
+        self
+    }
+
+    /// Default [`PlanOptions`] with the `quantization` of this domain
+    pub fn plan_options(&self) -> PlanOptions {
+        PlanOptions {
+            quantization: self.quantization.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Checks the domain for mistakes that would otherwise only show up while planning, if
+    /// at all. Returns an empty list if everything looks fine
+    pub fn validate(&self) -> Vec<DomainProblem> {
+        let mut problems = vec![];
+
+        let mut seen_keys = HashSet::new();
+        for action in &self.actions {
+            if !seen_keys.insert(action.key.as_str()) {
+                problems.push(DomainProblem::DuplicateActionKey {
+                    action: action.key.clone(),
+                });
+            }
+            if action.effects.is_empty() {
+                problems.push(DomainProblem::EmptyEffects {
+                    action: action.key.clone(),
+                });
+            }
+            let keys = action
+                .preconditions
+                .iter()
+                .flat_map(|(key, compare)| std::iter::once(key.as_str()).chain(compare.other_key()))
+                .chain(action.conditions.iter().flat_map(Condition::keys))
+                .filter(|key| action.parameter(key).is_none());
+            for key in self.missing_keys(keys) {
+                problems.push(DomainProblem::MissingPreconditionKey {
+                    action: action.key.clone(),
+                    key,
+                });
+            }
+            let keys = action
+                .effects
+                .iter()
+                .flat_map(|effect| &effect.conditional_costs)
+                .flat_map(|(condition, _)| condition.keys())
+                .filter(|key| action.parameter(key).is_none());
+            for key in self.missing_keys(keys) {
+                problems.push(DomainProblem::MissingCostKey {
+                    action: action.key.clone(),
+                    key,
+                });
+            }
+            let costs = action.effects.iter().flat_map(|effect| {
+                std::iter::once(effect.cost)
+                    .chain(effect.conditional_costs.iter().map(|(_, cost)| *cost))
+            });
+            for cost in costs.filter(|cost| check_cost(&action.key, *cost).is_err()) {
+                problems.push(DomainProblem::InvalidCost {
+                    action: action.key.clone(),
+                    cost,
+                });
+            }
+            let probabilities = action.outcome_probabilities();
+            let invalid = |p: &f64| p.is_nan() || !(0.0..=1.0).contains(p);
+            // Leaves some room for rounding errors, like 0.1 + 0.2 being more than 0.3
+            if probabilities.iter().any(invalid) || probabilities.iter().sum::<f64>() > 1.0 + 1e-9 {
+                problems.push(DomainProblem::InvalidProbabilities {
+                    action: action.key.clone(),
+                    probabilities,
+                });
+            }
+            for mutator in action.effects.iter().flat_map(|effect| &effect.mutators) {
+                let parameter = match mutator {
+                    Mutator::CopyFrom(_, source) => action.parameter(source),
+                    _ => None,
+                };
+                let Some(parameter) = parameter else {
+                    problems.extend(self.check_mutator(action, mutator));
+                    continue;
+                };
+                // Checked like setting each of the values the parameter can take, reporting
+                // only the first that doesn't fit
+                problems.extend(parameter.values.iter().find_map(|value| {
+                    let set = Mutator::Set(mutator.key().to_string(), *value);
+                    self.check_mutator(action, &set)
+                }));
+            }
+        }
+
+        for (index, goal) in self.goals.iter().enumerate() {
+            let keys = goal
+                .requirements
+                .iter()
+                .flat_map(|(key, compare)| std::iter::once(key.as_str()).chain(compare.other_key()))
+                .chain(goal.conditions.iter().flat_map(Condition::keys));
+            for key in self.missing_keys(keys) {
+                problems.push(DomainProblem::MissingGoalKey { goal: index, key });
+            }
+        }
+
+        for (key, step) in &self.quantization.steps {
+            let is_float = matches!(self.state.data.get(key), Some(Datum::F64(_)) | None);
+            if !(step.is_finite() && *step > 0.0 && is_float) {
+                problems.push(DomainProblem::InvalidQuantizationStep {
+                    key: key.clone(),
+                    step: *step,
+                });
+            }
+        }
+
+        problems
+    }
+
+    /// The keys that aren't in the state, each only once
+    fn missing_keys<'a>(&self, keys: impl Iterator<Item = &'a str>) -> Vec<String> {
+        let mut missing: Vec<String> = vec![];
+        for key in keys {
+            if !self.state.data.contains_key(key) && !missing.iter().any(|k| k == key) {
+                missing.push(key.to_string());
+            }
+        }
+        missing
+    }
+
+    fn check_mutator(&self, action: &Action, mutator: &Mutator) -> Option<DomainProblem> {
+        let key = mutator.key();
+        let missing = || DomainProblem::MissingMutatorKey {
+            action: action.key.clone(),
+            key: key.to_string(),
+        };
+        let value = match mutator {
+            Mutator::Set(_, value) | Mutator::ClampMin(_, value) | Mutator::ClampMax(_, value) => {
+                value
+            }
+            Mutator::Increment(_, value)
+            | Mutator::Decrement(_, value)
+            | Mutator::Multiply(_, value)
+            | Mutator::Divide(_, value) => {
+                if matches!(value, Datum::Bool(_) | Datum::Enum(_) | Datum::Symbol(_)) {
+                    return Some(DomainProblem::NonNumericMutator {
+                        action: action.key.clone(),
+                        key: key.to_string(),
+                        value: *value,
+                    });
+                }
+                if matches!(mutator, Mutator::Divide(_, _)) && value.is_zero() {
+                    return Some(DomainProblem::DivisionByZero {
+                        action: action.key.clone(),
+                        key: key.to_string(),
+                    });
+                }
+                value
+            }
+            Mutator::Toggle(_) => {
+                let Some(found) = self.state.data.get(key) else {
+                    return Some(missing());
+                };
+                return (!matches!(found, Datum::Bool(_))).then(|| DomainProblem::NonBoolToggle {
+                    action: action.key.clone(),
+                    key: key.to_string(),
+                    found: *found,
+                });
+            }
+            Mutator::CopyFrom(_, source) => {
+                let Some(value) = self.state.data.get(source) else {
+                    return Some(DomainProblem::MissingCopySource {
+                        action: action.key.clone(),
+                        key: key.to_string(),
+                        source: source.clone(),
+                    });
+                };
+                value
+            }
+        };
+        let Some(found) = self.state.data.get(key) else {
+            // Set and CopyFrom add the key, the others have nothing to change
+            return (!matches!(mutator, Mutator::Set(..) | Mutator::CopyFrom(..))).then(missing);
+        };
+        // Whole numbers can be multiplied and divided by decimal numbers too
+        let scales_integer = matches!(
+            (mutator, found, value),
+            (
+                Mutator::Multiply(_, _) | Mutator::Divide(_, _),
+                Datum::I64(_),
+                Datum::F64(_)
+            )
+        );
+        (!found.is_same_type(value) && !scales_integer).then(|| {
+            DomainProblem::MutatorTypeMismatch {
+                action: action.key.clone(),
+                key: key.to_string(),
+                found: *found,
+                other: *value,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compare::{Compare, Comparison},
+        effect::Effect,
+    };
+
+    #[test]
+    fn test_valid_domain() {
+        let domain = Domain::new(LocalState::new().with_datum("energy", Datum::I64(50)))
+            .with_action(
+                Action::new("eat")
+                    .with_precondition("energy", Compare::LessThanEquals(Datum::I64(10)))
+                    .add_mutator(Mutator::Increment("energy".to_string(), Datum::I64(10))),
+            )
+            .with_goal(Goal::new().with_req("energy", Compare::Equals(Datum::I64(100))));
+        assert_eq!(Vec::<DomainProblem>::new(), domain.validate());
+    }
+
+    #[test]
+    fn test_domain_problems() {
+        let state = LocalState::new()
+            .with_datum("energy", Datum::I64(50))
+            .with_datum("is_hungry", Datum::Bool(true));
+        let domain = Domain::new(state)
+            .with_action(
+                Action::new("eat")
+                    .with_precondition("has_food", Compare::Equals(Datum::Bool(true)))
+                    .add_mutator(Mutator::Increment(
+                        "is_hungry".to_string(),
+                        Datum::Bool(false),
+                    )),
+            )
+            .with_action(
+                Action::new("sleep")
+                    .add_mutator(Mutator::Set("energy".to_string(), Datum::F64(1.0)))
+                    .with_cost_when(
+                        Condition::compare("is_tired", Compare::Equals(Datum::Bool(true))),
+                        -1.0,
+                    ),
+            )
+            .with_action(Action::new("sleep"))
+            .with_goal(Goal::new().with_req("gold", Compare::Equals(Datum::I64(10))));
+
+        assert_eq!(
+            vec![
+                DomainProblem::MissingPreconditionKey {
+                    action: "eat".to_string(),
+                    key: "has_food".to_string()
+                },
+                DomainProblem::NonNumericMutator {
+                    action: "eat".to_string(),
+                    key: "is_hungry".to_string(),
+                    value: Datum::Bool(false)
+                },
+                DomainProblem::MissingCostKey {
+                    action: "sleep".to_string(),
+                    key: "is_tired".to_string()
+                },
+                DomainProblem::InvalidCost {
+                    action: "sleep".to_string(),
+                    cost: -1.0
+                },
+                DomainProblem::MutatorTypeMismatch {
+                    action: "sleep".to_string(),
+                    key: "energy".to_string(),
+                    found: Datum::I64(50),
+                    other: Datum::F64(1.0)
+                },
+                DomainProblem::DuplicateActionKey {
+                    action: "sleep".to_string()
+                },
+                DomainProblem::EmptyEffects {
+                    action: "sleep".to_string()
+                },
+                DomainProblem::MissingGoalKey {
+                    goal: 0,
+                    key: "gold".to_string()
+                },
+            ],
+            domain.validate()
+        );
+    }
+
+    #[test]
+    fn test_mutator_problems() {
+        let state = LocalState::new()
+            .with_datum("gold", Datum::I64(50))
+            .with_datum("is_lit", Datum::Bool(false));
+        let domain = Domain::new(state)
+            .with_action(
+                Action::new("invest")
+                    .add_mutator(Mutator::Multiply("gold".to_string(), Datum::F64(1.1)))
+                    .add_mutator(Mutator::Divide("gold".to_string(), Datum::I64(0))),
+            )
+            .with_action(
+                Action::new("flip")
+                    .add_mutator(Mutator::Toggle("is_lit".to_string()))
+                    .add_mutator(Mutator::Toggle("gold".to_string())),
+            )
+            .with_action(
+                Action::new("withdraw")
+                    .add_mutator(Mutator::CopyFrom("gold".to_string(), "savings".to_string()))
+                    .add_mutator(Mutator::CopyFrom("is_lit".to_string(), "gold".to_string())),
+            )
+            .with_action(
+                Action::new("earn")
+                    .add_mutator(Mutator::Increment("savings".to_string(), Datum::I64(10)))
+                    .add_mutator(Mutator::Toggle("is_rich".to_string()))
+                    .add_mutator(Mutator::ClampMax("debt".to_string(), Datum::I64(0)))
+                    .add_mutator(Mutator::Set("has_job".to_string(), Datum::Bool(true))),
+            );
+
+        assert_eq!(
+            vec![
+                DomainProblem::DivisionByZero {
+                    action: "invest".to_string(),
+                    key: "gold".to_string()
+                },
+                DomainProblem::NonBoolToggle {
+                    action: "flip".to_string(),
+                    key: "gold".to_string(),
+                    found: Datum::I64(50)
+                },
+                DomainProblem::MissingCopySource {
+                    action: "withdraw".to_string(),
+                    key: "gold".to_string(),
+                    source: "savings".to_string()
+                },
+                DomainProblem::MutatorTypeMismatch {
+                    action: "withdraw".to_string(),
+                    key: "is_lit".to_string(),
+                    found: Datum::Bool(false),
+                    other: Datum::I64(50)
+                },
+                DomainProblem::MissingMutatorKey {
+                    action: "earn".to_string(),
+                    key: "savings".to_string()
+                },
+                DomainProblem::MissingMutatorKey {
+                    action: "earn".to_string(),
+                    key: "is_rich".to_string()
+                },
+                DomainProblem::MissingMutatorKey {
+                    action: "earn".to_string(),
+                    key: "debt".to_string()
+                },
+            ],
+            domain.validate()
+        );
+    }
+
+    #[test]
+    fn test_invalid_probabilities() {
+        let catch = |probability| {
+            Effect::new("fish")
+                .with_mutator(Mutator::Set("has_fish".to_string(), Datum::Bool(true)))
+                .with_probability(probability)
+        };
+        let domain = Domain::new(LocalState::new().with_datum("has_fish", Datum::Bool(false)))
+            .with_action(
+                Action::new("fish")
+                    .with_effect(catch(0.3))
+                    .with_effect(Effect::new("fish")),
+            )
+            .with_action(
+                Action::new("cast")
+                    .with_effect(catch(0.6))
+                    .with_effect(catch(0.6)),
+            );
+
+        assert_eq!(
+            vec![DomainProblem::InvalidProbabilities {
+                action: "cast".to_string(),
+                probabilities: vec![0.6, 0.6]
+            }],
+            domain.validate()
+        );
+    }
+
+    #[test]
+    fn test_parameterized_action() {
+        let state = LocalState::new().with_datum("at_location", Datum::Enum(0.into()));
+        let go_to = |values: Vec<Datum>| {
+            Action::new("go_to")
+                .with_parameter("location", values)
+                .with_precondition(
+                    "at_location",
+                    Compare::to_key(Comparison::NotEquals, "location"),
+                )
+                .add_mutator(Mutator::CopyFrom(
+                    "at_location".to_string(),
+                    "location".to_string(),
+                ))
+        };
+        let domain = Domain::new(state.clone())
+            .with_action(go_to(vec![Datum::Enum(0.into()), Datum::Enum(1.into())]));
+        assert_eq!(Vec::<DomainProblem>::new(), domain.validate());
+
+        let domain = Domain::new(state).with_action(go_to(vec![
+            Datum::Enum(1.into()),
+            Datum::Bool(true),
+            Datum::Bool(false),
+        ]));
+        assert_eq!(
+            vec![DomainProblem::MutatorTypeMismatch {
+                action: "go_to".to_string(),
+                key: "at_location".to_string(),
+                found: Datum::Enum(0.into()),
+                other: Datum::Bool(true),
+            }],
+            domain.validate()
+        );
+    }
+
+    #[test]
+    fn test_quantization_steps() {
+        let state = LocalState::new()
+            .with_datum("hunger", Datum::F64(50.0))
+            .with_datum("gold", Datum::I64(3));
+        let domain = Domain::new(state).with_quantization_step("hunger", 0.5);
+        assert_eq!(Vec::<DomainProblem>::new(), domain.validate());
+        assert_eq!(Some(0.5), domain.plan_options().quantization.step("hunger"));
+
+        let domain = domain
+            .with_quantization_step("hunger", -1.0)
+            .with_quantization_step("gold", 1.0);
+        assert_eq!(
+            vec![
+                DomainProblem::InvalidQuantizationStep {
+                    key: "gold".to_string(),
+                    step: 1.0
+                },
+                DomainProblem::InvalidQuantizationStep {
+                    key: "hunger".to_string(),
+                    step: -1.0
+                },
+            ],
+            domain.validate()
+        );
+    }
+}
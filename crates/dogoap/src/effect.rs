@@ -1,4 +1,11 @@
-use crate::{localstate::LocalState, mutator::Mutator};
+use crate::{
+    compact::SearchState,
+    condition::Condition,
+    datum::Datum,
+    error::PlanError,
+    localstate::LocalState,
+    mutator::{MissingKeyBehavior, Mutator},
+};
 use bevy_reflect::Reflect;
 use std::hash::{Hash, Hasher};
 
@@ -6,12 +13,22 @@ use std::hash::{Hash, Hasher};
 /// It's separated from Action in order to separate the
 /// data structures for the Planner's Node that is used
 /// for the pathfinding part.
-#[derive(Reflect, Clone, Debug, PartialEq, Eq, Default)]
+#[derive(Reflect, Clone, Debug, PartialEq, Default)]
 pub struct Effect {
     pub action: String,
     pub mutators: Vec<Mutator>,
     pub state: LocalState,
-    pub cost: usize,
+    /// Can be fractional, but never negative or NaN
+    pub cost: f64,
+    /// Costs that replace `cost` when the state the action is taken from matches the
+    /// [`Condition`]. The first matching one is used
+    pub conditional_costs: Vec<(Condition, f64)>,
+    /// How likely this outcome is, when the [`Action`](crate::action::Action) has more than
+    /// one, see [`Action::outcome_probabilities`](crate::action::Action::outcome_probabilities)
+    pub probability: Option<f64>,
+    /// The values chosen for the [`Parameter`](crate::action::Parameter)s of the action, by
+    /// parameter name
+    pub arguments: Vec<(String, Datum)>,
 }
 
 impl Effect {
@@ -20,19 +37,79 @@ impl Effect {
             action: action_name.to_string(),
             mutators: vec![],
             state: LocalState::new(),
-            cost: 1,
+            cost: 1.0,
+            conditional_costs: vec![],
+            probability: None,
+            arguments: vec![],
         }
     }
     pub fn with_mutator(mut self, mutator: Mutator) -> Self {
         self.mutators.push(mutator);
         self
     }
+
+    pub fn with_cost(mut self, cost: f64) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn with_probability(mut self, probability: f64) -> Self {
+        self.probability = Some(probability);
+        self
+    }
+
+    /// Makes the effect cost `cost` whenever `condition` is true before the action is taken,
+    /// unless an earlier conditional cost already matched
+    pub fn with_cost_when(mut self, condition: Condition, cost: f64) -> Self {
+        self.conditional_costs.push((condition, cost));
+        self
+    }
+
+    /// The value chosen for the parameter called `name`, if the action has one
+    pub fn argument(&self, name: &str) -> Option<Datum> {
+        self.arguments
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// What the effect costs when taken from `state`, failing if a [`Condition`] in
+    /// `conditional_costs` refers to a key that is missing or of the wrong type
+    pub fn cost_in(&self, state: &LocalState) -> Result<f64, PlanError> {
+        self.cost_with(&|key| state.data.get(key).copied())
+    }
+
+    /// Same as [`Effect::cost_in`], for the states the planner works with
+    pub fn cost_at(&self, state: &SearchState) -> Result<f64, PlanError> {
+        self.cost_with(&|key| state.get(key).copied())
+    }
+
+    pub(crate) fn cost_with(&self, get: &dyn Fn(&str) -> Option<Datum>) -> Result<f64, PlanError> {
+        for (condition, cost) in &self.conditional_costs {
+            if condition.evaluate_with(get, MissingKeyBehavior::Error)? {
+                return Ok(*cost);
+            }
+        }
+        Ok(self.cost)
+    }
+
+    /// The lowest cost this effect can have in any state
+    pub fn lowest_cost(&self) -> f64 {
+        self.conditional_costs
+            .iter()
+            .map(|(_, cost)| *cost)
+            .fold(self.cost, f64::min)
+    }
 }
 
+// The planner refuses to use NaN costs and probabilities, see `PlanError::InvalidCost`
+impl Eq for Effect {}
+
 impl Hash for Effect {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.action.hash(state);
         self.mutators.hash(state);
         self.state.hash(state);
+        self.arguments.hash(state);
     }
 }
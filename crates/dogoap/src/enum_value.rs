@@ -0,0 +1,166 @@
+// crate "dogoap" file enum_value.rs
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::sync::{OnceLock, RwLock};
+
+use bevy_reflect::Reflect;
+use rustc_hash::FxHashMap;
+
+use crate::symbol::Symbol;
+
+/// One variant of an enum, as its index together with the name of the enum it's from, so
+/// `Location::Home` is never mistaken for the variant at the same index of another enum.
+/// Both are stored as numbers, the name as a [`Symbol`], so they're cheap to hash and
+/// compare while planning. The names are only looked up to show the variant.
+/// The name can be a path like `game::world::Location`, to tell apart enums with the same
+/// name in different modules, which is what `#[derive(EnumDatum)]` uses.
+/// Comparing variants of different enums is a type mismatch, like comparing a `Bool` with
+/// an `I64`.
+///
+/// Enums without a name are still supported with [`EnumValue::untyped`], those only match
+/// other untyped variants. The names of the variants can be registered with
+/// [`EnumValue::register`], which `#[derive(EnumDatum)]` does, to show `Location::Home`
+/// instead of the index.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnumValue {
+    /// The name or path of the enum, empty for untyped variants
+    enum_name: Symbol,
+    /// The index of the variant in the enum
+    index: u32,
+}
+
+fn variant_names() -> &'static RwLock<FxHashMap<Symbol, &'static [&'static str]>> {
+    static VARIANT_NAMES: OnceLock<RwLock<FxHashMap<Symbol, &'static [&'static str]>>> =
+        OnceLock::new();
+    VARIANT_NAMES.get_or_init(Default::default)
+}
+
+impl EnumValue {
+    pub fn new(enum_name: &str, index: usize) -> Self {
+        Self::from_symbol(Symbol::new(enum_name), index)
+    }
+
+    /// Like [`EnumValue::new`], with the name of the enum interned already
+    pub fn from_symbol(enum_name: Symbol, index: usize) -> Self {
+        Self {
+            enum_name,
+            index: u32::try_from(index).expect("Too many variants in enum"),
+        }
+    }
+
+    /// A variant that isn't from any enum in particular
+    pub fn untyped(index: usize) -> Self {
+        Self::from_symbol(Symbol::default(), index)
+    }
+
+    /// The name or path of the enum, the empty symbol for untyped variants
+    pub fn enum_name(&self) -> Symbol {
+        self.enum_name
+    }
+
+    /// The index of the variant in the enum
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn is_untyped(&self) -> bool {
+        self.enum_name == Symbol::default()
+    }
+
+    /// If both are variants of the same enum, regardless of which variant
+    pub fn is_same_enum(&self, other: &EnumValue) -> bool {
+        self.enum_name == other.enum_name
+    }
+
+    /// The name of the enum without the path of its module, like `Location`
+    pub fn short_enum_name(&self) -> String {
+        let name = self.enum_name.name();
+        name.rsplit("::").next().unwrap_or(&name).to_string()
+    }
+
+    /// Registers the names of the variants of the enum `enum_name`, ordered by their index.
+    /// Registering an enum again replaces its names
+    pub fn register(enum_name: &str, variants: &'static [&'static str]) {
+        let mut names = variant_names()
+            .write()
+            .unwrap_or_else(|err| err.into_inner());
+        names.insert(Symbol::new(enum_name), variants);
+    }
+
+    /// The registered name of this variant, if there is one
+    pub fn variant_name(&self) -> Option<&'static str> {
+        let names = variant_names()
+            .read()
+            .unwrap_or_else(|err| err.into_inner());
+        names
+            .get(&self.enum_name)
+            .and_then(|variants| variants.get(self.index()).copied())
+    }
+}
+
+impl From<usize> for EnumValue {
+    fn from(index: usize) -> Self {
+        EnumValue::untyped(index)
+    }
+}
+
+impl PartialOrd for EnumValue {
+    /// Variants of the same enum are ordered by their index, variants of different enums
+    /// aren't ordered at all
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.is_same_enum(other)
+            .then(|| self.index.cmp(&other.index))
+    }
+}
+
+impl Display for EnumValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_untyped() {
+            return write!(f, "{}", self.index);
+        }
+        let enum_name = self.short_enum_name();
+        match self.variant_name() {
+            Some(variant) => write!(f, "{}::{}", enum_name, variant),
+            None => write!(f, "{}::{}", enum_name, self.index),
+        }
+    }
+}
+
+impl std::fmt::Debug for EnumValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EnumValue({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enum_values() {
+        EnumValue::register("Location", &["Home", "Outside"]);
+        let home = EnumValue::new("Location", 0);
+        let outside = EnumValue::new("Location", 1);
+        assert_eq!("Location::Home", home.to_string());
+        assert_eq!("Location::Outside", outside.to_string());
+        assert_eq!("Location::5", EnumValue::new("Location", 5).to_string());
+        assert_eq!("EnumValue(1)", format!("{:?}", EnumValue::untyped(1)));
+        assert!(home < outside);
+
+        // The same index in another enum is another value, without any order
+        let sword = EnumValue::new("Weapon", 0);
+        assert!(!home.is_same_enum(&sword));
+        assert_ne!(home, sword);
+        assert_eq!(None, home.partial_cmp(&sword));
+        assert_ne!(EnumValue::untyped(0), home);
+        assert_eq!(EnumValue::untyped(0), 0.into());
+
+        assert_eq!(8, std::mem::size_of::<EnumValue>());
+
+        // Same name in another module is another enum, but shows the same
+        EnumValue::register("game::Location", &["Home", "Outside"]);
+        let game_home = EnumValue::new("game::Location", 0);
+        assert!(!game_home.is_same_enum(&home));
+        assert_eq!("Location::Home", game_home.to_string());
+    }
+}
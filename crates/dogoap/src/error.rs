@@ -0,0 +1,177 @@
+use std::fmt::Display;
+
+use crate::{compare::Compare, datum::Datum, plan::Plan};
+
+/// Returned by [`make_plan`](crate::planner::make_plan) and friends when planning couldn't
+/// be done, so you can log what went wrong and recover instead of crashing
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlanError {
+    /// A precondition, goal requirement or mutator refers to a key that isn't in the
+    /// [`LocalState`](crate::localstate::LocalState)
+    MissingKey {
+        key: String,
+        /// The [`Action`](crate::action::Action) that refers to the key, `None` if it's the [`Goal`](crate::goal::Goal)
+        action: Option<String>,
+    },
+    /// Two [`Datum`] of different types were compared or combined, like a `Datum::Bool` in the
+    /// state being compared to a `Datum::I64` in a precondition
+    TypeMismatch {
+        key: String,
+        /// The value found in the [`LocalState`](crate::localstate::LocalState)
+        found: Datum,
+        /// The value it was compared or combined with
+        other: Datum,
+        /// The [`Action`](crate::action::Action) this happened in, `None` if it's the [`Goal`](crate::goal::Goal)
+        action: Option<String>,
+    },
+    /// A `Divide` [`Mutator`](crate::mutator::Mutator) divided by zero
+    DivisionByZero {
+        key: String,
+        /// The [`Action`](crate::action::Action) with the mutator
+        action: Option<String>,
+    },
+    /// An [`Action`](crate::action::Action) cost less than zero or NaN, which the search can't
+    /// deal with
+    InvalidCost { action: String, cost: f64 },
+    /// One of the [`Effect`](crate::effect::Effect)s of an [`Action`](crate::action::Action)
+    /// has a probability that is NaN or outside of 0 to 1
+    InvalidProbability { action: String, probability: f64 },
+    /// The search went through every reachable state without reaching the [`Goal`](crate::goal::Goal)
+    NoPlanFound {
+        /// See [`PlanError::partial_plan`]
+        partial: Option<Plan>,
+    },
+    /// A [`Task`](crate::htn::Task) refers to an action or compound task that doesn't exist
+    UnknownTask { task: String },
+    /// The search ran out of its budget from [`PlanOptions`](crate::planner::PlanOptions) before
+    /// it could tell if the [`Goal`](crate::goal::Goal) can be reached or not
+    BudgetExhausted {
+        expanded_nodes: usize,
+        /// See [`PlanError::partial_plan`]
+        partial: Option<Plan>,
+    },
+}
+
+impl PlanError {
+    /// [`PlanError::TypeMismatch`] for when `found` in the state doesn't fit `compare`
+    pub(crate) fn mismatch(key: &str, found: &Datum, compare: &Compare) -> Self {
+        PlanError::TypeMismatch {
+            key: key.to_string(),
+            found: *found,
+            other: compare.type_mismatch(found).unwrap_or(*found),
+            action: None,
+        }
+    }
+
+    /// Attaches the name of the [`Action`](crate::action::Action) the error happened in, unless
+    /// the error already knows about it
+    pub fn in_action(mut self, name: &str) -> Self {
+        match &mut self {
+            PlanError::MissingKey { action, .. }
+            | PlanError::TypeMismatch { action, .. }
+            | PlanError::DivisionByZero { action, .. } => {
+                if action.is_none() {
+                    *action = Some(name.to_string());
+                }
+            }
+            PlanError::InvalidCost { .. }
+            | PlanError::InvalidProbability { .. }
+            | PlanError::NoPlanFound { .. }
+            | PlanError::UnknownTask { .. }
+            | PlanError::BudgetExhausted { .. } => {}
+        }
+        self
+    }
+
+    /// The start of a plan that gets as close to the [`Goal`](crate::goal::Goal) as the search
+    /// managed to get, measured by [`LocalState::distance_to_goal`](crate::localstate::LocalState::distance_to_goal).
+    ///
+    /// Only set when partial plans were asked for with
+    /// [`PlanOptions::with_partial_plans`](crate::planner::PlanOptions::with_partial_plans),
+    /// the search got closer than the start state and the strategy was
+    /// [`PlanningStrategy::StartToGoal`](crate::planner::PlanningStrategy::StartToGoal)
+    pub fn partial_plan(&self) -> Option<&Plan> {
+        match self {
+            PlanError::NoPlanFound { partial } | PlanError::BudgetExhausted { partial, .. } => {
+                partial.as_ref()
+            }
+            _ => None,
+        }
+    }
+
+    /// Like [`PlanError::partial_plan`], but takes ownership of the plan
+    pub fn into_partial_plan(self) -> Option<Plan> {
+        match self {
+            PlanError::NoPlanFound { partial } | PlanError::BudgetExhausted { partial, .. } => {
+                partial
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanError::MissingKey { key, action } => match action {
+                Some(action) => write!(
+                    f,
+                    "Couldn't find key {:?} in LocalState, used by action {:?}",
+                    key, action
+                ),
+                None => write!(
+                    f,
+                    "Couldn't find key {:?} in LocalState, used by the goal",
+                    key
+                ),
+            },
+            PlanError::TypeMismatch {
+                key,
+                found,
+                other,
+                action,
+            } => {
+                write!(
+                    f,
+                    "Type mismatch for key {:?}, found {} in LocalState but it was used with {}",
+                    key, found, other
+                )?;
+                match action {
+                    Some(action) => write!(f, " by action {:?}", action),
+                    None => write!(f, " by the goal"),
+                }
+            }
+            PlanError::DivisionByZero { key, action } => {
+                write!(f, "Division by zero for key {:?}", key)?;
+                match action {
+                    Some(action) => write!(f, " by action {:?}", action),
+                    None => Ok(()),
+                }
+            }
+            PlanError::InvalidCost { action, cost } => write!(
+                f,
+                "Action {:?} costs {}, but costs can't be negative or NaN",
+                action, cost
+            ),
+            PlanError::InvalidProbability {
+                action,
+                probability,
+            } => write!(
+                f,
+                "Action {:?} has an outcome with probability {}, which isn't between 0 and 1",
+                action, probability
+            ),
+            PlanError::NoPlanFound { .. } => write!(f, "No plan exists that reaches the goal"),
+            PlanError::UnknownTask { task } => {
+                write!(f, "Task {:?} isn't an action or compound task", task)
+            }
+            PlanError::BudgetExhausted { expanded_nodes, .. } => write!(
+                f,
+                "Ran out of planning budget after expanding {} nodes",
+                expanded_nodes
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
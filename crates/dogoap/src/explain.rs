@@ -0,0 +1,162 @@
+// crate "dogoap" file explain.rs
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::{
+    action::{ground_actions, Action},
+    compact::CompactState,
+    compare::{check_compare, Compare},
+    error::PlanError,
+    goal::Goal,
+    localstate::LocalState,
+    planner::{ForwardSearch, PlanOptions},
+    search::{astar, PlanStats, SearchResult},
+};
+
+/// What [`explain_no_plan`] found out about why the [`Goal`] couldn't be reached
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoPlanExplanation {
+    /// Goal requirements that aren't met in the start state, and that no [`Effect`](crate::effect::Effect)
+    /// of any [`Action`] changes, so nothing can ever fix them
+    pub untouched_requirements: Vec<(String, Compare)>,
+    /// Keys of the [`Action`]s whose preconditions weren't met in any of the explored states.
+    /// Actions without any [`Effect`](crate::effect::Effect) are never explored, and aren't listed
+    pub never_applicable: Vec<String>,
+    /// The explored state that got closest to the [`Goal`], measured by
+    /// [`LocalState::distance_to_goal`]
+    pub closest_state: LocalState,
+    /// Goal requirements that still weren't met in `closest_state`
+    pub unmet_requirements: Vec<(String, Compare)>,
+    /// `false` if the budget of the [`PlanOptions`] ran out before every reachable state was
+    /// explored, in which case the actions in `never_applicable` might still be usable
+    pub complete: bool,
+}
+
+impl Display for NoPlanExplanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "No plan found")?;
+        if !self.complete {
+            writeln!(f, "(ran out of budget, not every state was explored)")?;
+        }
+        if !self.untouched_requirements.is_empty() {
+            writeln!(f, "Goal requirements that no action changes:")?;
+            for (key, compare) in &self.untouched_requirements {
+                writeln!(f, "\t{}: {:?}", key, compare)?;
+            }
+        }
+        if !self.never_applicable.is_empty() {
+            writeln!(f, "Actions that could never be used:")?;
+            for key in &self.never_applicable {
+                writeln!(f, "\t{}", key)?;
+            }
+        }
+        writeln!(f, "Closest state reached: {:?}", self.closest_state)?;
+        writeln!(f, "Goal requirements still unmet there:")?;
+        for (key, compare) in &self.unmet_requirements {
+            writeln!(f, "\t{}: {:?}", key, compare)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the planner in a diagnostic mode, to help figuring out why [`make_plan`](crate::planner::make_plan)
+/// can't find a plan for a domain. Returns `Ok(None)` if there is a plan after all.
+///
+/// Always searches from the start towards the [`Goal`], using the [`Heuristic`](crate::heuristic::Heuristic)
+/// and budget of `options`
+pub fn explain_no_plan(
+    options: &PlanOptions,
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+) -> Result<Option<NoPlanExplanation>, PlanError> {
+    let actions = &ground_actions(actions)?;
+    let (start, actions, goal) = options.quantization.quantize_domain(start, actions, goal);
+    let (start, actions, goal) = (start.as_ref(), actions.as_ref(), goal.as_ref());
+    let search = ForwardSearch::new(start, actions, goal, options)?;
+    let start_state = CompactState::from_local_state(start, &search.registry)?;
+
+    // Every successor is reached through an action whose preconditions were met
+    let mut applicable = vec![false; search.actions.len()];
+    let result = astar(
+        std::slice::from_ref(&start_state),
+        |state, successors| {
+            let found = successors.len();
+            search.successors(state, successors)?;
+            for (_, action, _) in &successors[found..] {
+                applicable[*action] = true;
+            }
+            Ok(())
+        },
+        |state| search.heuristic(state),
+        |state| search.distance(state),
+        |state| search.is_goal(state),
+        &options.budget(),
+        &mut PlanStats::default(),
+    )?;
+    let ((path, _), complete) = match result {
+        SearchResult::Found(..) => return Ok(None),
+        SearchResult::NotFound { closest } => (closest, true),
+        SearchResult::BudgetExhausted { closest, .. } => (closest, false),
+    };
+
+    let closest_state = path
+        .last()
+        .expect("Path always has the start state")
+        .0
+        .to_local_state(&search.registry);
+    let is_met = |state: &LocalState, key: &str, compare| {
+        matches!(
+            check_compare(state, key, compare, options.missing_keys),
+            Ok(true)
+        )
+    };
+
+    Ok(Some(NoPlanExplanation {
+        untouched_requirements: goal
+            .requirements
+            .iter()
+            .filter(|(key, compare)| !is_met(start, key, compare))
+            .filter(|(key, compare)| {
+                let touches = |k: &str| k == key.as_str() || compare.other_key() == Some(k);
+                !actions.iter().any(|action| {
+                    action
+                        .effects
+                        .iter()
+                        .any(|effect| effect.mutators.iter().any(|m| touches(m.key())))
+                })
+            })
+            .map(|(key, compare)| (key.clone(), compare.clone()))
+            .collect(),
+        never_applicable: never_applicable(&search, &applicable),
+        unmet_requirements: goal
+            .requirements
+            .iter()
+            .filter(|(key, compare)| !is_met(&closest_state, key, compare))
+            .map(|(key, compare)| (key.clone(), compare.clone()))
+            .collect(),
+        closest_state,
+        complete,
+    }))
+}
+
+/// The actions where none of the outcomes were ever applicable, each only once
+fn never_applicable(search: &ForwardSearch, applicable: &[bool]) -> Vec<String> {
+    let applicable: HashSet<&str> = search
+        .actions
+        .iter()
+        .zip(applicable)
+        .filter(|(_, applicable)| **applicable)
+        .map(|(action, _)| action.action().key.as_str())
+        .collect();
+    let mut never_applicable: Vec<String> = search
+        .actions
+        .iter()
+        .map(|action| action.action().key.as_str())
+        .filter(|key| !applicable.contains(key))
+        .map(str::to_string)
+        .collect();
+    // Outcomes of the same action come right after each other
+    never_applicable.dedup();
+    never_applicable
+}
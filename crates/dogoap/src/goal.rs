@@ -4,13 +4,17 @@ use std::hash::{Hash, Hasher};
 use bevy_reflect::*;
 
 use crate::compare::Compare;
+use crate::condition::Condition;
 
 /// Goal is a map of what we want our final [`LocalState`](crate::localstate::LocalState) to be, using String as
 /// keys and [`Compare`] to assert what we want the [`Datum`](crate::datum::Datum) to be
-#[derive(Reflect, Clone, Debug, PartialEq)]
+#[derive(Reflect, Clone, Debug, PartialEq, Default)]
 pub struct Goal {
     /// All the requirements needed to be met in order to consider us to be at our final state
     pub requirements: BTreeMap<String, Compare>,
+    /// Requirements that need more than one [`Compare`] per key, see [`Condition`]. They
+    /// all have to be true, together with the `requirements`
+    pub conditions: Vec<Condition>,
 }
 
 impl Hash for Goal {
@@ -20,6 +24,7 @@ impl Hash for Goal {
             key.hash(state);
             value.hash(state);
         }
+        self.conditions.hash(state);
     }
 }
 
@@ -27,6 +32,7 @@ impl Goal {
     pub fn new() -> Self {
         Self {
             requirements: BTreeMap::new(),
+            conditions: vec![],
         }
     }
 
@@ -35,6 +41,11 @@ impl Goal {
         self
     }
 
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
     pub fn from_reqs(preconditions: &[(String, Compare)]) -> Goal {
         let mut goal = Goal::new();
         for (k, v) in preconditions {
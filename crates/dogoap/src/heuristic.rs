@@ -0,0 +1,294 @@
+// crate "dogoap" file heuristic.rs
+use std::collections::HashMap;
+
+use crate::{
+    action::Action,
+    compact::SearchState,
+    compare::{compare_distance, compare_values, Compare},
+    condition::Condition,
+    datum::Datum,
+    error::PlanError,
+    goal::Goal,
+    mutator::Mutator,
+};
+
+/// Estimates how much it'll cost to reach the [`Goal`] from a state, which guides the
+/// [`PlanningStrategy::StartToGoal`](crate::planner::PlanningStrategy::StartToGoal) search
+/// towards the goal.
+///
+/// If the estimate never is higher than the real cost, the planner always finds the cheapest
+/// plan. Higher estimates usually make the planner faster, but the plan might cost more
+pub trait Heuristic: Send + Sync {
+    fn estimate(&self, state: &SearchState, goal: &Goal) -> Result<f64, PlanError>;
+}
+
+/// Always estimates zero, which turns the search into Dijkstra's algorithm. Always finds
+/// the cheapest plan, but has to look at a lot more states to do so
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZeroHeuristic;
+
+impl Heuristic for ZeroHeuristic {
+    fn estimate(&self, _state: &SearchState, _goal: &Goal) -> Result<f64, PlanError> {
+        Ok(0.0)
+    }
+}
+
+/// Sums up how far away each value is from the [`Goal`], see
+/// [`LocalState::distance_to_goal`](crate::localstate::LocalState::distance_to_goal).
+/// This is the default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DistanceHeuristic;
+
+impl Heuristic for DistanceHeuristic {
+    fn estimate(&self, state: &SearchState, goal: &Goal) -> Result<f64, PlanError> {
+        let mut distance = 0;
+        for (key, compare) in &goal.requirements {
+            distance += compare_distance(key, compare, |key| state.get(key).copied())?;
+        }
+        for condition in &goal.conditions {
+            distance += condition.distance_with(&|key| state.get(key).copied())?;
+        }
+        Ok(distance as f64)
+    }
+}
+
+/// What the [`Action`]s can do to one key
+#[derive(Clone, Debug)]
+struct KeyChanges {
+    /// If any action can change the key in other ways than fixed steps, like `Set` or
+    /// `Multiply`, possibly reaching any value in one go
+    set: bool,
+    /// Largest Increment/Decrement of any action
+    largest_step: f64,
+    /// Cost of the cheapest action that changes the key
+    cheapest: f64,
+}
+
+/// Never estimates more than it'll actually cost, so the planner always finds the cheapest plan.
+///
+/// For each requirement that isn't met yet, the distance is divided by the largest change a
+/// single [`Action`] can do to that key, giving the least amount of actions needed. That is
+/// multiplied by the cost of the cheapest action that changes the key. One action can help
+/// with several requirements, so only the highest of those estimates is used
+#[derive(Clone, Debug, Default)]
+pub struct AdmissibleHeuristic {
+    keys: HashMap<String, KeyChanges>,
+}
+
+impl AdmissibleHeuristic {
+    /// Looks through every [`Effect`](crate::effect::Effect) of the [`Action`]s to figure
+    /// out how fast each key can change
+    pub fn new(actions: &[Action]) -> Self {
+        let mut keys: HashMap<String, KeyChanges> = HashMap::new();
+        for effect in actions.iter().flat_map(|action| &action.effects) {
+            for mutator in &effect.mutators {
+                let changes = keys.entry(mutator.key().to_string()).or_insert(KeyChanges {
+                    set: false,
+                    largest_step: 0.0,
+                    cheapest: effect.lowest_cost(),
+                });
+                changes.cheapest = changes.cheapest.min(effect.lowest_cost());
+                match mutator {
+                    Mutator::Increment(_, value) | Mutator::Decrement(_, value) => {
+                        changes.largest_step = changes.largest_step.max(magnitude(value));
+                    }
+                    _ => changes.set = true,
+                }
+            }
+        }
+        Self { keys }
+    }
+}
+
+impl AdmissibleHeuristic {
+    fn estimate_requirement(
+        &self,
+        state: &SearchState,
+        key: &str,
+        compare: &Compare,
+    ) -> Result<f64, PlanError> {
+        if let Compare::ToKey(_, operand) = compare {
+            return self.estimate_to_key(state, key, compare, &operand.key);
+        }
+        let value = state.get(key);
+        if let Some(value) = value {
+            if compare.type_mismatch(value).is_some() {
+                return Err(PlanError::mismatch(key, value, compare));
+            }
+            if compare_values(compare, value) {
+                return Ok(0.0);
+            }
+        }
+        let Some(changes) = self.keys.get(key) else {
+            // Nothing changes this key, so there is nothing to estimate
+            return Ok(0.0);
+        };
+        let actions_needed = match value {
+            Some(value) if !changes.set && changes.largest_step > 0.0 => {
+                (gap(value, compare) / changes.largest_step).ceil().max(1.0)
+            }
+            _ => 1.0,
+        };
+        Ok(actions_needed * changes.cheapest)
+    }
+
+    /// Both keys of a [`Compare::ToKey`] might move towards each other, so all we know is
+    /// that at least one action changing either of them is needed
+    fn estimate_to_key(
+        &self,
+        state: &SearchState,
+        key: &str,
+        compare: &Compare,
+        other: &str,
+    ) -> Result<f64, PlanError> {
+        if let Some(value) = state.get(key) {
+            match compare.resolve(|key| state.get(key).copied()) {
+                Ok(resolved) => {
+                    if resolved.type_mismatch(value).is_some() {
+                        return Err(PlanError::mismatch(key, value, &resolved));
+                    }
+                    if compare_values(&resolved, value) {
+                        return Ok(0.0);
+                    }
+                }
+                Err(PlanError::MissingKey { .. }) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok([key, other]
+            .into_iter()
+            .filter_map(|key| self.keys.get(key))
+            .map(|changes| changes.cheapest)
+            .reduce(f64::min)
+            .unwrap_or(0.0))
+    }
+
+    /// All conditions need to be fulfilled, so the highest estimate counts. For Any,
+    /// only the cheapest one has to be
+    fn estimate_condition(
+        &self,
+        state: &SearchState,
+        condition: &Condition,
+    ) -> Result<f64, PlanError> {
+        match condition {
+            Condition::Compare(key, compare) => self.estimate_requirement(state, key, compare),
+            Condition::All(conditions) => {
+                let mut estimate: f64 = 0.0;
+                for condition in conditions {
+                    estimate = estimate.max(self.estimate_condition(state, condition)?);
+                }
+                Ok(estimate)
+            }
+            Condition::Any(conditions) => {
+                let mut estimate = None;
+                for condition in conditions {
+                    let other = self.estimate_condition(state, condition)?;
+                    estimate = Some(estimate.map_or(other, |estimate: f64| estimate.min(other)));
+                }
+                Ok(estimate.unwrap_or(0.0))
+            }
+        }
+    }
+}
+
+impl Heuristic for AdmissibleHeuristic {
+    fn estimate(&self, state: &SearchState, goal: &Goal) -> Result<f64, PlanError> {
+        let mut estimate: f64 = 0.0;
+        for (key, compare) in &goal.requirements {
+            estimate = estimate.max(self.estimate_requirement(state, key, compare)?);
+        }
+        for condition in &goal.conditions {
+            estimate = estimate.max(self.estimate_condition(state, condition)?);
+        }
+        Ok(estimate)
+    }
+}
+
+/// How far `value` has to move to fulfill `compare`, without truncating `F64` values
+/// like [`Compare::distance`] does
+fn gap(value: &Datum, compare: &Compare) -> f64 {
+    let to = |target: &Datum| match (value, target) {
+        (Datum::F64(a), Datum::F64(b)) => (a - b).abs(),
+        _ => value.distance(target) as f64,
+    };
+    match compare {
+        Compare::Equals(v)
+        | Compare::GreaterThanEquals(v)
+        | Compare::LessThanEquals(v)
+        | Compare::GreaterThan(v)
+        | Compare::LessThan(v) => to(v),
+        // Any change at all is enough
+        Compare::NotEquals(_) => 0.0,
+        Compare::InRange(min, max) => to(min).min(to(max)),
+        Compare::OneOf(values) => values.iter().map(to).reduce(f64::min).unwrap_or(0.0),
+        // Handled by AdmissibleHeuristic::estimate_to_key instead
+        Compare::ToKey(_, _) => 0.0,
+    }
+}
+
+fn magnitude(value: &Datum) -> f64 {
+    match value {
+        Datum::I64(v) => v.unsigned_abs() as f64,
+        Datum::F64(v) => v.abs(),
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compact::{CompactState, KeyRegistry},
+        localstate::LocalState,
+    };
+
+    fn estimate(heuristic: &dyn Heuristic, state: &LocalState, goal: &Goal) -> f64 {
+        let registry = KeyRegistry::from_domain(state, &[], goal);
+        let compact = CompactState::from_local_state(state, &registry).unwrap();
+        heuristic
+            .estimate(&SearchState::new(&compact, &registry), goal)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_admissible_scales_by_step() {
+        let actions = [
+            Action::new("mine")
+                .add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(3)))
+                .set_cost(2.0),
+            Action::new("trade")
+                .add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(1)))
+                .set_cost(1.0),
+        ];
+        let heuristic = AdmissibleHeuristic::new(&actions);
+        let state = LocalState::new().with_datum("gold", Datum::I64(0));
+
+        // At least 4 actions, each costing at least 1
+        let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+        assert_eq!(4.0, estimate(&heuristic, &state, &goal));
+        assert_eq!(10.0, estimate(&DistanceHeuristic, &state, &goal));
+        assert_eq!(0.0, estimate(&ZeroHeuristic, &state, &goal));
+
+        let goal = Goal::new().with_req("gold", Compare::LessThanEquals(Datum::I64(10)));
+        assert_eq!(0.0, estimate(&heuristic, &state, &goal));
+    }
+
+    #[test]
+    fn test_admissible_takes_highest_requirement() {
+        let actions = [
+            Action::new("eat")
+                .add_mutator(Mutator::Set("is_hungry".to_string(), Datum::Bool(false))),
+            Action::new("work")
+                .add_mutator(Mutator::Increment("gold".to_string(), Datum::F64(2.5))),
+        ];
+        let heuristic = AdmissibleHeuristic::new(&actions);
+        let state = LocalState::new()
+            .with_datum("is_hungry", Datum::Bool(true))
+            .with_datum("gold", Datum::F64(0.0));
+
+        let goal = Goal::new()
+            .with_req("is_hungry", Compare::Equals(Datum::Bool(false)))
+            .with_req("gold", Compare::GreaterThanEquals(Datum::F64(6.0)));
+        assert_eq!(3.0, estimate(&heuristic, &state, &goal));
+    }
+}
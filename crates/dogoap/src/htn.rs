@@ -0,0 +1,278 @@
+// crate "dogoap" file htn.rs
+use bevy_reflect::Reflect;
+
+use crate::{
+    action::{ground_actions, Action},
+    compact::{CompactState, KeyRegistry, SearchState},
+    compare::{check_compare, check_preconditions, Compare},
+    effect::Effect,
+    error::PlanError,
+    goal::Goal,
+    localstate::LocalState,
+    mutator::{apply_mutator, MissingKeyBehavior},
+    plan::Plan,
+    planner::{make_plan_with_options, PlanOptions},
+};
+
+/// One step of a [`Method`], or of the whole plan given to [`make_htn_plan`]
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub enum Task {
+    /// The [`Action`] with this key, taken right away. Fails if its preconditions aren't met
+    Primitive(String),
+    /// The [`CompoundTask`] with this name, done by the first of its methods that works out
+    Compound(String),
+    /// Whatever actions reach the [`Goal`] the cheapest, found by the regular planner
+    Goal(Goal),
+}
+
+impl Task {
+    pub fn primitive(action: &str) -> Self {
+        Task::Primitive(action.to_string())
+    }
+
+    pub fn compound(name: &str) -> Self {
+        Task::Compound(name.to_string())
+    }
+}
+
+/// One way of doing a [`CompoundTask`], as a list of tasks done one after the other
+#[derive(Reflect, Clone, Debug, PartialEq, Default)]
+pub struct Method {
+    /// String like `from_scratch`, only used for telling methods apart while debugging
+    pub name: String,
+    /// What needs to be true in the [`LocalState`] for this method to be tried
+    pub preconditions: Vec<(String, Compare)>,
+    pub subtasks: Vec<Task>,
+}
+
+impl Method {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_precondition(mut self, key: &str, compare: Compare) -> Self {
+        self.preconditions.push((key.to_string(), compare));
+        self
+    }
+
+    pub fn with_subtask(mut self, task: Task) -> Self {
+        self.subtasks.push(task);
+        self
+    }
+}
+
+/// A task that is done by one of several [`Method`]s, tried in order
+#[derive(Reflect, Clone, Debug, PartialEq, Default)]
+pub struct CompoundTask {
+    pub name: String,
+    pub methods: Vec<Method>,
+}
+
+impl CompoundTask {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            methods: vec![],
+        }
+    }
+
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+}
+
+/// Where the decomposition is at: the tasks that are left, and the plan up until then
+struct Agenda {
+    tasks: Vec<Task>,
+    plan: Plan,
+}
+
+/// Plans the way designers spelled out, like "craft_sword = gather_ore, smelt, forge",
+/// instead of searching for any way to reach a [`Goal`]. Gaps in between can still be left
+/// to the regular planner with a [`Task::Goal`].
+///
+/// Plans `tasks` one after the other, decomposing every [`Task::Compound`] with the first of
+/// its methods that leads to a plan for the rest of the tasks as well, going back to try the
+/// next method when one doesn't.
+///
+/// Like with other HTN planners, the plan is the first one found this way, which isn't
+/// necessarily the cheapest. A compound task that uses itself as a subtask needs a method
+/// precondition that stops it at some point, or a budget in `options`, which counts every
+/// decomposed task as an expanded node. `options` is also used for the [`Task::Goal`]s.
+///
+/// The [`Plan`] is the same kind of plan [`make_plan`](crate::planner::make_plan) returns,
+/// so it can be executed the same way.
+///
+/// ```rust
+/// use dogoap::prelude::*;
+/// use dogoap::simple::simple_action;
+///
+/// let start = LocalState::new()
+///     .with_datum("has_ore", Datum::Bool(false))
+///     .with_datum("has_metal", Datum::Bool(false))
+///     .with_datum("has_sword", Datum::Bool(false));
+///
+/// let actions = [
+///     simple_action("gather_ore", "has_ore", Datum::Bool(true)),
+///     simple_action("smelt", "has_metal", Datum::Bool(true))
+///         .with_precondition("has_ore", Compare::Equals(Datum::Bool(true))),
+///     simple_action("forge", "has_sword", Datum::Bool(true))
+///         .with_precondition("has_metal", Compare::Equals(Datum::Bool(true))),
+/// ];
+///
+/// let craft_sword = CompoundTask::new("craft_sword").with_method(
+///     Method::new("from_scratch")
+///         .with_subtask(Task::primitive("gather_ore"))
+///         .with_subtask(Task::primitive("smelt"))
+///         .with_subtask(Task::primitive("forge")),
+/// );
+///
+/// let plan = make_htn_plan(
+///     &PlanOptions::new(),
+///     &start,
+///     &actions,
+///     &[craft_sword],
+///     &[Task::compound("craft_sword")],
+/// )
+/// .unwrap();
+/// assert_eq!(3, plan.steps.len());
+/// assert_eq!(3.0, plan.cost);
+/// ```
+pub fn make_htn_plan(
+    options: &PlanOptions,
+    start: &LocalState,
+    actions: &[Action],
+    compound_tasks: &[CompoundTask],
+    tasks: &[Task],
+) -> Result<Plan, PlanError> {
+    let actions = &ground_actions(actions)?;
+    let budget = options.budget();
+    let mut expanded_nodes = 0;
+
+    // Depth first, with the first alternative at the end so it's popped first
+    let mut agendas = vec![Agenda {
+        tasks: tasks.iter().rev().cloned().collect(),
+        plan: Plan::new(start.clone()),
+    }];
+    while let Some(mut agenda) = agendas.pop() {
+        let Some(task) = agenda.tasks.pop() else {
+            return Ok(agenda.plan);
+        };
+        if budget.is_exhausted(expanded_nodes) {
+            return Err(PlanError::BudgetExhausted {
+                expanded_nodes,
+                partial: None,
+            });
+        }
+        expanded_nodes += 1;
+
+        let mut alternatives = vec![];
+        match task {
+            Task::Primitive(key) => {
+                let mut found = false;
+                for action in actions.iter().filter(|action| action.key == key) {
+                    found = true;
+                    if !check_preconditions(
+                        agenda.plan.final_state(),
+                        action,
+                        options.missing_keys,
+                    )? {
+                        continue;
+                    }
+                    alternatives.extend(outcomes(options, action, &agenda)?);
+                }
+                if !found {
+                    return Err(PlanError::UnknownTask { task: key });
+                }
+            }
+            Task::Compound(name) => {
+                let compound = compound_tasks
+                    .iter()
+                    .find(|compound| compound.name == name)
+                    .ok_or(PlanError::UnknownTask { task: name })?;
+                for method in &compound.methods {
+                    if !method_applies(agenda.plan.final_state(), method, options.missing_keys)? {
+                        continue;
+                    }
+                    let mut tasks = agenda.tasks.clone();
+                    tasks.extend(method.subtasks.iter().rev().cloned());
+                    alternatives.push(Agenda {
+                        tasks,
+                        plan: agenda.plan.clone(),
+                    });
+                }
+            }
+            Task::Goal(goal) => {
+                match make_plan_with_options(options, agenda.plan.final_state(), actions, &goal) {
+                    Ok(plan) => {
+                        agenda.plan.steps.extend(plan.steps);
+                        agenda.plan.cost += plan.cost;
+                        alternatives.push(agenda);
+                    }
+                    Err(PlanError::NoPlanFound { .. }) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        agendas.extend(alternatives.into_iter().rev());
+    }
+    Err(PlanError::NoPlanFound { partial: None })
+}
+
+fn method_applies(
+    state: &LocalState,
+    method: &Method,
+    missing: MissingKeyBehavior,
+) -> Result<bool, PlanError> {
+    for (key, compare) in &method.preconditions {
+        if !check_compare(state, key, compare, missing)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The agendas after taking `action`, one for each of its outcomes, costed the same way the
+/// regular planner does
+fn outcomes(
+    options: &PlanOptions,
+    action: &Action,
+    agenda: &Agenda,
+) -> Result<Vec<Agenda>, PlanError> {
+    let before = agenda.plan.final_state();
+    let registry = KeyRegistry::from_domain(before, std::slice::from_ref(action), &Goal::new());
+    let compact = CompactState::from_local_state(before, &registry)?;
+    let search_state = SearchState::new(&compact, &registry);
+
+    let mut outcomes = vec![];
+    for (effect, probability) in action.effects.iter().zip(action.outcome_probabilities()) {
+        let cost = match &options.cost_model {
+            Some(model) => model.cost(action, effect, &search_state)?,
+            None => effect.cost_in(before)?,
+        };
+        let Some(cost) = options.outcomes.cost(&action.key, cost, probability)? else {
+            continue;
+        };
+        let mut state = before.clone();
+        for mutator in &effect.mutators {
+            apply_mutator(&mut state.data, mutator, options.missing_keys)
+                .map_err(|err| err.in_action(&action.key))?;
+        }
+        let mut plan = agenda.plan.clone();
+        plan.steps.push(Effect {
+            cost,
+            state,
+            ..effect.clone()
+        });
+        plan.cost += cost;
+        outcomes.push(Agenda {
+            tasks: agenda.tasks.clone(),
+            plan,
+        });
+    }
+    Ok(outcomes)
+}
@@ -1,12 +1,27 @@
 #![feature(trivial_bounds)]
 #![doc = include_str!("../README.md")]
 mod action;
+mod cache;
+mod compact;
 mod compare;
+mod condition;
+mod cost;
 mod datum;
+mod domain;
 mod effect;
+mod enum_value;
+mod error;
+mod explain;
 mod goal;
+mod heuristic;
+mod htn;
 mod localstate;
 mod mutator;
+mod plan;
+mod quantization;
+mod regression;
+mod search;
+mod symbol;
 
 pub mod planner;
 pub mod prelude;
@@ -16,13 +16,17 @@ use std::collections::BTreeMap;
 
 use bevy_reflect::Reflect;
 
+use crate::compare::compare_distance;
 use crate::datum::Datum;
+use crate::error::PlanError;
 use crate::goal::Goal;
 
 pub type InternalData = BTreeMap<String, Datum>;
 
 /// This is our internal state that the planner uses to progress in the path finding,
-/// until we reach our [`Goal`]
+/// until we reach our [`Goal`]. While searching, the planner replaces the keys with
+/// indices into a list of all keys used, and only turns the states in the final plan
+/// back into `LocalState`s
 #[derive(Reflect, Debug, Clone, Eq, PartialEq, Default)]
 pub struct LocalState {
     pub data: InternalData,
@@ -40,16 +44,17 @@ impl LocalState {
         self
     }
 
-    pub fn distance_to_goal(&self, goal: &Goal) -> u64 {
-        goal.requirements
-            .iter()
-            .map(|(key, goal_val)| {
-                match self.data.get(key) {
-                    Some(state_val) => state_val.distance(&goal_val.value()),
-                    None => 1, // Penalty for missing keys
-                }
-            })
-            .sum()
+    /// Sums up how far away each value is from what the [`Goal`] requires, failing if
+    /// the types in the state doesn't match the ones in the [`Goal`]
+    pub fn distance_to_goal(&self, goal: &Goal) -> Result<u64, PlanError> {
+        let mut distance = 0;
+        for (key, goal_val) in goal.requirements.iter() {
+            distance += compare_distance(key, goal_val, |key| self.data.get(key).copied())?;
+        }
+        for condition in &goal.conditions {
+            distance += condition.distance_with(&|key| self.data.get(key).copied())?;
+        }
+        Ok(distance)
     }
 }
 
@@ -72,12 +77,12 @@ mod tests {
     fn test_distance_to_goal() {
         let state = LocalState::new().with_datum("energy", Datum::I64(50));
         let goal_state = Goal::new().with_req("energy", Compare::Equals(Datum::I64(50)));
-        let distance = state.distance_to_goal(&goal_state.clone());
+        let distance = state.distance_to_goal(&goal_state.clone()).unwrap();
         assert_eq!(distance, 0);
 
         let state = LocalState::new().with_datum("energy", Datum::I64(25));
         let goal_state = Goal::new().with_req("energy", Compare::Equals(Datum::I64(50)));
-        let distance = state.distance_to_goal(&goal_state.clone());
+        let distance = state.distance_to_goal(&goal_state.clone()).unwrap();
         assert_eq!(distance, 25);
 
         let state = LocalState::new()
@@ -86,7 +91,7 @@ mod tests {
         let goal_state = Goal::new()
             .with_req("energy", Compare::Equals(Datum::I64(50)))
             .with_req("hunger", Compare::Equals(Datum::F64(50.0)));
-        let distance = state.distance_to_goal(&goal_state.clone());
+        let distance = state.distance_to_goal(&goal_state.clone()).unwrap();
         assert_eq!(distance, 50);
     }
 }
@@ -1,4 +1,4 @@
-use crate::{datum::Datum, localstate::InternalData};
+use crate::{datum::Datum, error::PlanError, localstate::InternalData};
 
 use bevy_reflect::*;
 
@@ -9,24 +9,192 @@ pub enum Mutator {
     Set(String, Datum),       // :key, :value
     Increment(String, Datum), // :key, :increment-by
     Decrement(String, Datum), // :key, :decrement-by
+    Multiply(String, Datum),  // :key, :multiply-by
+    Divide(String, Datum),    // :key, :divide-by
+    ClampMin(String, Datum),  // :key, :lowest-value
+    ClampMax(String, Datum),  // :key, :highest-value
+    Toggle(String),           // :key
+    CopyFrom(String, String), // :key, :key-to-copy-from
 }
 
-pub fn apply_mutator(data: &mut InternalData, mutator: &Mutator) {
+impl Mutator {
+    /// The key in the [`LocalState`](crate::localstate::LocalState) this [`Mutator`] changes
+    pub fn key(&self) -> &str {
+        match self {
+            Mutator::Set(key, _)
+            | Mutator::Increment(key, _)
+            | Mutator::Decrement(key, _)
+            | Mutator::Multiply(key, _)
+            | Mutator::Divide(key, _)
+            | Mutator::ClampMin(key, _)
+            | Mutator::ClampMax(key, _)
+            | Mutator::Toggle(key)
+            | Mutator::CopyFrom(key, _) => key,
+        }
+    }
+
+    /// The key this [`Mutator`] reads its value from, only set for [`Mutator::CopyFrom`]
+    pub fn source_key(&self) -> Option<&str> {
+        match self {
+            Mutator::CopyFrom(_, source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// What happens when the key isn't in the [`LocalState`](crate::localstate::LocalState),
+/// for the mutators that change the current value, like [`Mutator::Increment`], and for
+/// preconditions, conditions and goal requirements
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MissingKeyBehavior {
+    /// Fail with [`PlanError::MissingKey`]
+    #[default]
+    Error,
+    /// Read the missing value as zero everywhere, without adding the key. A precondition
+    /// like `debt <= 10` holds, zero being of the type it's compared with, and
+    /// `Decrement(key, 5)` ends up at -5. Toggling a missing key counts it as `false`.
+    /// The conditions of [`Effect::conditional_costs`](crate::effect::Effect) still fail
+    Zero,
+    /// Add the key once a mutator changes it, as if it had been zero, so `Decrement(key, 5)`
+    /// creates it as -5, `Multiply` creates it as zero and toggling creates it as `true`.
+    /// Until then, preconditions, conditions and goal requirements on the key fail like
+    /// with [`MissingKeyBehavior::Error`]
+    Create,
+}
+
+impl MissingKeyBehavior {
+    /// The value a missing key ends up with, where `value` is the value of the mutator
+    /// and `change` does the mutation from zero
+    pub(crate) fn missing_value(
+        self,
+        key: &str,
+        value: Datum,
+        change: impl FnOnce(&Datum) -> Result<Datum, PlanError>,
+    ) -> Result<Datum, PlanError> {
+        match self {
+            MissingKeyBehavior::Error => Err(PlanError::MissingKey {
+                key: key.to_string(),
+                action: None,
+            }),
+            MissingKeyBehavior::Zero | MissingKeyBehavior::Create => change(&value.zero()),
+        }
+    }
+
+    /// The value a condition reads for a missing key, zero of the same type as `like` for
+    /// [`MissingKeyBehavior::Zero`], and nothing otherwise
+    pub(crate) fn read_missing(self, like: Option<Datum>) -> Option<Datum> {
+        like.filter(|_| self == MissingKeyBehavior::Zero)
+            .map(|like| like.zero())
+    }
+}
+
+/// The mutators that compute the new value from the current one and the value
+/// in the [`Mutator`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Change {
+    Increment,
+    Decrement,
+    Multiply,
+    Divide,
+    ClampMin,
+    ClampMax,
+}
+
+impl Change {
+    /// Returns `current` after the change, failing if `by` doesn't fit with it
+    pub(crate) fn apply(self, key: &str, current: &Datum, by: &Datum) -> Result<Datum, PlanError> {
+        let result = match self {
+            Change::Increment => current.checked_add(by),
+            Change::Decrement => current.checked_sub(by),
+            Change::Multiply => current.checked_mul(by),
+            Change::Divide if by.is_zero() => {
+                return Err(PlanError::DivisionByZero {
+                    key: key.to_string(),
+                    action: None,
+                })
+            }
+            Change::Divide => current.checked_div(by),
+            Change::ClampMin => {
+                current
+                    .is_same_type(by)
+                    .then(|| if current < by { *by } else { *current })
+            }
+            Change::ClampMax => {
+                current
+                    .is_same_type(by)
+                    .then(|| if current > by { *by } else { *current })
+            }
+        };
+        result.ok_or_else(|| PlanError::TypeMismatch {
+            key: key.to_string(),
+            found: *current,
+            other: *by,
+            action: None,
+        })
+    }
+}
+
+/// Flips a `Datum::Bool`, failing for every other type
+pub(crate) fn toggle(key: &str, current: &Datum) -> Result<Datum, PlanError> {
+    match current {
+        Datum::Bool(value) => Ok(Datum::Bool(!value)),
+        _ => Err(PlanError::TypeMismatch {
+            key: key.to_string(),
+            found: *current,
+            other: Datum::Bool(true),
+            action: None,
+        }),
+    }
+}
+
+/// Applies the [`Mutator`] to the data, failing if the value in the data can't be
+/// changed by the one in the [`Mutator`].
+///
+/// `missing` decides what mutators that change the current value do if the key isn't in
+/// the data
+pub fn apply_mutator(
+    data: &mut InternalData,
+    mutator: &Mutator,
+    missing: MissingKeyBehavior,
+) -> Result<(), PlanError> {
+    let apply_change = |data: &mut InternalData, key: &str, change: Change, by: &Datum| {
+        if let Some(current_value) = data.get_mut(key) {
+            *current_value = change.apply(key, current_value, by)?;
+        } else {
+            let value = missing.missing_value(key, *by, |zero| change.apply(key, zero, by))?;
+            data.insert(key.to_string(), value);
+        }
+        Ok::<(), PlanError>(())
+    };
+
     match mutator {
         Mutator::Set(key, value) => {
             data.insert(key.to_string(), *value);
         }
-        Mutator::Increment(key, value) => {
+        Mutator::Increment(key, value) => apply_change(data, key, Change::Increment, value)?,
+        Mutator::Decrement(key, value) => apply_change(data, key, Change::Decrement, value)?,
+        Mutator::Multiply(key, value) => apply_change(data, key, Change::Multiply, value)?,
+        Mutator::Divide(key, value) => apply_change(data, key, Change::Divide, value)?,
+        Mutator::ClampMin(key, value) => apply_change(data, key, Change::ClampMin, value)?,
+        Mutator::ClampMax(key, value) => apply_change(data, key, Change::ClampMax, value)?,
+        Mutator::Toggle(key) => {
             if let Some(current_value) = data.get_mut(key) {
-                *current_value += *value;
+                *current_value = toggle(key, current_value)?;
+            } else {
+                let value =
+                    missing.missing_value(key, Datum::Bool(true), |zero| toggle(key, zero))?;
+                data.insert(key.to_string(), value);
             }
         }
-        Mutator::Decrement(key, value) => {
-            if let Some(current_value) = data.get_mut(key) {
-                *current_value -= *value;
-            }
+        Mutator::CopyFrom(key, source) => {
+            let value = *data.get(source).ok_or_else(|| PlanError::MissingKey {
+                key: source.to_string(),
+                action: None,
+            })?;
+            data.insert(key.to_string(), value);
         }
     }
+    Ok(())
 }
 
 pub fn print_mutators(mutators: Vec<Mutator>) {
@@ -41,6 +209,130 @@ pub fn print_mutators(mutators: Vec<Mutator>) {
             Mutator::Decrement(k, v) => {
                 println!("\t\t{} - {}", k, v);
             }
+            Mutator::Multiply(k, v) => {
+                println!("\t\t{} * {}", k, v);
+            }
+            Mutator::Divide(k, v) => {
+                println!("\t\t{} / {}", k, v);
+            }
+            Mutator::ClampMin(k, v) => {
+                println!("\t\t{} = max({}, {})", k, k, v);
+            }
+            Mutator::ClampMax(k, v) => {
+                println!("\t\t{} = min({}, {})", k, k, v);
+            }
+            Mutator::Toggle(k) => {
+                println!("\t\t{} = !{}", k, k);
+            }
+            Mutator::CopyFrom(k, source) => {
+                println!("\t\t{} = {}", k, source);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::localstate::LocalState;
+
+    #[test]
+    fn test_apply_mutators() {
+        let mut data = LocalState::new()
+            .with_datum("gold", Datum::I64(100))
+            .with_datum("savings", Datum::I64(7))
+            .with_datum("hunger", Datum::F64(2.0))
+            .with_datum("is_lit", Datum::Bool(false))
+            .data;
+        let mut apply = |mutator| {
+            apply_mutator(&mut data, &mutator, MissingKeyBehavior::Error).map(|_| data.clone())
+        };
+        let gold = |data: InternalData| data["gold"];
+
+        let multiply = Mutator::Multiply("gold".to_string(), Datum::F64(1.1));
+        assert_eq!(Ok(Datum::I64(110)), apply(multiply).map(gold));
+        let divide = Mutator::Divide("gold".to_string(), Datum::I64(3));
+        assert_eq!(Ok(Datum::I64(36)), apply(divide).map(gold));
+        let clamp = Mutator::ClampMax("gold".to_string(), Datum::I64(20));
+        assert_eq!(Ok(Datum::I64(20)), apply(clamp).map(gold));
+        let copy = Mutator::CopyFrom("gold".to_string(), "savings".to_string());
+        assert_eq!(Ok(Datum::I64(7)), apply(copy).map(gold));
+
+        apply(Mutator::Decrement("hunger".to_string(), Datum::F64(5.0))).unwrap();
+        let clamp = Mutator::ClampMin("hunger".to_string(), Datum::F64(0.0));
+        assert_eq!(Ok(Datum::F64(0.0)), apply(clamp).map(|data| data["hunger"]));
+        let toggle = Mutator::Toggle("is_lit".to_string());
+        assert_eq!(
+            Ok(Datum::Bool(true)),
+            apply(toggle).map(|data| data["is_lit"])
+        );
+
+        assert_eq!(
+            Err(PlanError::DivisionByZero {
+                key: "gold".to_string(),
+                action: None
+            }),
+            apply(Mutator::Divide("gold".to_string(), Datum::I64(0)))
+        );
+        assert_eq!(
+            Err(PlanError::TypeMismatch {
+                key: "gold".to_string(),
+                found: Datum::I64(7),
+                other: Datum::Bool(true),
+                action: None
+            }),
+            apply(Mutator::Toggle("gold".to_string()))
+        );
+        assert_eq!(
+            Err(PlanError::MissingKey {
+                key: "debt".to_string(),
+                action: None
+            }),
+            apply(Mutator::CopyFrom("gold".to_string(), "debt".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_missing_key_behavior() {
+        let decrement = Mutator::Decrement("gold".to_string(), Datum::I64(5));
+        let multiply = Mutator::Multiply("gold".to_string(), Datum::I64(5));
+        let divide = Mutator::Divide("gold".to_string(), Datum::I64(5));
+        let toggle = Mutator::Toggle("is_lit".to_string());
+        let apply = |mutator: &Mutator, missing| {
+            let mut data = InternalData::new();
+            apply_mutator(&mut data, mutator, missing).map(|_| data.get(mutator.key()).copied())
+        };
+
+        assert_eq!(
+            Err(PlanError::MissingKey {
+                key: "gold".to_string(),
+                action: None
+            }),
+            apply(&decrement, MissingKeyBehavior::Error)
+        );
+        assert_eq!(
+            Ok(Some(Datum::I64(-5))),
+            apply(&decrement, MissingKeyBehavior::Zero)
+        );
+        assert_eq!(
+            Ok(Some(Datum::I64(-5))),
+            apply(&decrement, MissingKeyBehavior::Create)
+        );
+        assert_eq!(
+            Ok(Some(Datum::I64(0))),
+            apply(&multiply, MissingKeyBehavior::Create)
+        );
+        assert_eq!(
+            Ok(Some(Datum::I64(0))),
+            apply(&divide, MissingKeyBehavior::Create)
+        );
+        assert_eq!(
+            Ok(Some(Datum::Bool(true))),
+            apply(&toggle, MissingKeyBehavior::Zero)
+        );
+        assert_eq!(
+            Ok(Some(Datum::Bool(true))),
+            apply(&toggle, MissingKeyBehavior::Create)
+        );
+    }
+}
@@ -0,0 +1,306 @@
+// crate "dogoap" file plan.rs
+use std::fmt::Display;
+
+use bevy_reflect::Reflect;
+
+use crate::{
+    action::{ground_actions, Action},
+    compare::check_preconditions,
+    effect::Effect,
+    error::PlanError,
+    localstate::LocalState,
+    mutator::{apply_mutator, MissingKeyBehavior},
+    planner::Node,
+    quantization::Quantization,
+};
+
+/// What the planner came up with: the [`Effect`]s of the [`Action`]s to take one after the
+/// other to get from the `start` state to the [`Goal`](crate::goal::Goal)
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub struct Plan {
+    /// The state the plan was made for
+    pub start: LocalState,
+    /// One [`Effect`] per action to take, each with the state after taking it
+    pub steps: Vec<Effect>,
+    /// The total cost of taking every step
+    pub cost: f64,
+}
+
+impl Plan {
+    pub fn new(start: LocalState) -> Self {
+        Self {
+            start,
+            steps: vec![],
+            cost: 0.0,
+        }
+    }
+
+    /// The state once every step was taken, the `start` if there are none
+    pub fn final_state(&self) -> &LocalState {
+        self.steps
+            .last()
+            .map(|effect| &effect.state)
+            .unwrap_or(&self.start)
+    }
+
+    /// The `start` followed by every step, as [`Node`]s
+    pub fn nodes(&self) -> Vec<Node> {
+        std::iter::once(Node::State(self.start.clone()))
+            .chain(self.steps.iter().cloned().map(Node::Effect))
+            .collect()
+    }
+
+    /// Checks if the plan can still be followed from `state`, which is usually the state of
+    /// the world once it changed after planning. Each step has to be one of the `actions`,
+    /// with the same arguments, and its preconditions have to hold in the state the steps
+    /// before it lead to.
+    ///
+    /// Keys missing from `state` fail the way [`MissingKeyBehavior::default`] does. This only
+    /// checks that the steps can be taken, not that the goal is still reached at the end
+    pub fn validate(&self, state: &LocalState, actions: &[Action]) -> Result<(), StepFailure> {
+        let actions =
+            ground_actions(actions).map_err(|error| StepFailure::Error { step: 0, error })?;
+        replay(
+            state,
+            &self.steps,
+            &actions,
+            MissingKeyBehavior::default(),
+            &Quantization::default(),
+        )
+        .map(drop)
+    }
+
+    /// Takes the steps one after the other starting from `state`, without checking any
+    /// preconditions, see [`Simulation`]
+    pub fn simulate(&self, state: &LocalState) -> Simulation<'_> {
+        Simulation {
+            steps: self.steps.iter(),
+            state: state.clone(),
+            failed: false,
+        }
+    }
+}
+
+/// Takes `steps` one after the other from `start` the way [`Plan::validate`] does, and
+/// returns them as a plan with the states they lead to now, rounded with `quantization`.
+/// The costs are kept as they were. `actions` have to be grounded and quantized already
+pub(crate) fn replay(
+    start: &LocalState,
+    steps: &[Effect],
+    actions: &[Action],
+    missing_keys: MissingKeyBehavior,
+    quantization: &Quantization,
+) -> Result<Plan, StepFailure> {
+    let mut plan = Plan::new(start.clone());
+    quantization.quantize_state(&mut plan.start);
+    for (step, effect) in steps.iter().enumerate() {
+        let action = step_action(actions, effect).ok_or_else(|| StepFailure::UnknownAction {
+            step,
+            action: effect.action.clone(),
+        })?;
+        let state = plan.final_state();
+        let met = check_preconditions(state, action, missing_keys)
+            .map_err(|error| StepFailure::Error { step, error })?;
+        if !met {
+            return Err(StepFailure::PreconditionsNotMet {
+                step,
+                action: effect.action.clone(),
+                state: state.clone(),
+            });
+        }
+        let mut state = state.clone();
+        apply_step(&mut state, effect, missing_keys)
+            .map_err(|error| StepFailure::Error { step, error })?;
+        quantization.quantize_state(&mut state);
+        plan.cost += effect.cost;
+        plan.steps.push(Effect {
+            state,
+            ..effect.clone()
+        });
+    }
+    Ok(plan)
+}
+
+/// The grounded action a step was planned with, found by its key and arguments
+pub(crate) fn step_action<'a>(actions: &'a [Action], effect: &Effect) -> Option<&'a Action> {
+    actions.iter().find(|action| {
+        action.key == effect.action
+            && action
+                .effects
+                .iter()
+                .any(|e| e.arguments == effect.arguments)
+    })
+}
+
+fn apply_step(
+    state: &mut LocalState,
+    effect: &Effect,
+    missing_keys: MissingKeyBehavior,
+) -> Result<(), PlanError> {
+    for mutator in &effect.mutators {
+        apply_mutator(&mut state.data, mutator, missing_keys)
+            .map_err(|err| err.in_action(&effect.action))?;
+    }
+    Ok(())
+}
+
+/// Goes through the steps of a [`Plan`], applying the mutators of each to the state the
+/// previous steps lead to. Yields every step together with the state after it, and stops
+/// after the first step whose mutators fail
+pub struct Simulation<'a> {
+    steps: std::slice::Iter<'a, Effect>,
+    state: LocalState,
+    failed: bool,
+}
+
+impl<'a> Simulation<'a> {
+    /// The state after the steps simulated so far
+    pub fn state(&self) -> &LocalState {
+        &self.state
+    }
+}
+
+impl<'a> Iterator for Simulation<'a> {
+    type Item = Result<(&'a Effect, LocalState), PlanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let effect = self.steps.next()?;
+        match apply_step(&mut self.state, effect, MissingKeyBehavior::default()) {
+            Ok(()) => Some(Ok((effect, self.state.clone()))),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Why a [`Plan`] can't be followed anymore, see [`Plan::validate`]. `step` is the index in
+/// [`Plan::steps`] of the first step that can't be taken
+#[derive(Clone, Debug, PartialEq)]
+pub enum StepFailure {
+    /// None of the actions has the key and arguments of the step
+    UnknownAction { step: usize, action: String },
+    /// The preconditions of the action don't hold in `state`, which is the state the steps
+    /// before lead to
+    PreconditionsNotMet {
+        step: usize,
+        action: String,
+        state: LocalState,
+    },
+    /// Checking the preconditions or applying the mutators of the step failed
+    Error { step: usize, error: PlanError },
+}
+
+impl StepFailure {
+    pub fn step(&self) -> usize {
+        match self {
+            StepFailure::UnknownAction { step, .. }
+            | StepFailure::PreconditionsNotMet { step, .. }
+            | StepFailure::Error { step, .. } => *step,
+        }
+    }
+}
+
+impl Display for StepFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepFailure::UnknownAction { step, action } => {
+                write!(f, "Step {} uses unknown action {:?}", step, action)
+            }
+            StepFailure::PreconditionsNotMet { step, action, .. } => write!(
+                f,
+                "Step {} can't be taken anymore, the preconditions of action {:?} aren't met",
+                step, action
+            ),
+            StepFailure::Error { step, error } => write!(f, "Step {} failed: {}", step, error),
+        }
+    }
+}
+
+impl std::error::Error for StepFailure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compare::Compare, datum::Datum, goal::Goal, mutator::Mutator, planner::make_plan,
+        simple::simple_action,
+    };
+
+    fn cook() -> (LocalState, Vec<Action>, Plan) {
+        let start = LocalState::new()
+            .with_datum("has_food", Datum::Bool(false))
+            .with_datum("has_meal", Datum::Bool(false));
+        let actions = vec![
+            simple_action("get_food", "has_food", Datum::Bool(true)),
+            simple_action("cook", "has_meal", Datum::Bool(true))
+                .with_precondition("has_food", Compare::Equals(Datum::Bool(true))),
+        ];
+        let goal = Goal::new().with_req("has_meal", Compare::Equals(Datum::Bool(true)));
+        let plan = make_plan(&start, &actions, &goal).unwrap();
+        (start, actions, plan)
+    }
+
+    #[test]
+    fn test_validate() {
+        let (start, actions, plan) = cook();
+        assert_eq!(&start, &plan.start);
+        assert_eq!(Ok(()), plan.validate(&start, &actions));
+
+        // Changes the steps don't depend on leave the plan valid
+        let fed = start.clone().with_datum("has_food", Datum::Bool(true));
+        assert_eq!(Ok(()), plan.validate(&fed, &actions));
+
+        let without_food = vec![actions[1].clone()];
+        assert_eq!(
+            Err(StepFailure::UnknownAction {
+                step: 0,
+                action: "get_food".to_string()
+            }),
+            plan.validate(&start, &without_food)
+        );
+
+        // Getting food now needs money we don't have
+        let broke = start.clone().with_datum("gold", Datum::I64(0));
+        let expensive = vec![
+            actions[0]
+                .clone()
+                .with_precondition("gold", Compare::GreaterThanEquals(Datum::I64(1))),
+            actions[1].clone(),
+        ];
+        let failure = plan.validate(&broke, &expensive).unwrap_err();
+        assert_eq!(0, failure.step());
+        assert!(matches!(
+            failure,
+            StepFailure::PreconditionsNotMet { state, .. } if state == broke
+        ));
+    }
+
+    #[test]
+    fn test_simulate() {
+        let (start, _, plan) = cook();
+        let states: Vec<LocalState> = plan.simulate(&start).map(|step| step.unwrap().1).collect();
+        let expected: Vec<LocalState> = plan.steps.iter().map(|e| e.state.clone()).collect();
+        assert_eq!(expected, states);
+        assert_eq!(plan.final_state(), states.last().unwrap());
+
+        // Simulating from somewhere else keeps whatever the steps don't change
+        let elsewhere = start.with_datum("gold", Datum::I64(3));
+        let mut simulation = plan.simulate(&elsewhere);
+        simulation.by_ref().for_each(drop);
+        assert_eq!(Some(&Datum::I64(3)), simulation.state().data.get("gold"));
+
+        // Steps stop at the first mutator that fails
+        let mut broken = plan.clone();
+        broken.steps[0]
+            .mutators
+            .push(Mutator::Increment("missing".to_string(), Datum::I64(1)));
+        let results: Vec<_> = broken.simulate(&LocalState::new()).collect();
+        assert_eq!(1, results.len());
+        assert!(results[0].is_err());
+    }
+}
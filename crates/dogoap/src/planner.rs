@@ -1,13 +1,24 @@
 use crate::{
-    action::Action,
-    compare::{check_preconditions, compare_values},
+    action::{ground_actions, Action},
+    compact::{CompactAction, CompactGoal, CompactState, KeyRegistry, SearchState},
+    compare::check_compare,
+    condition::Condition,
+    cost::{CostModel, OutcomeMode},
     effect::Effect,
+    error::PlanError,
     goal::Goal,
+    heuristic::{DistanceHeuristic, Heuristic},
     localstate::LocalState,
-    mutator::{apply_mutator, print_mutators},
+    mutator::{print_mutators, MissingKeyBehavior},
+    plan::{replay, step_action, Plan, StepFailure},
+    quantization::Quantization,
+    regression::{can_regress, plan_backwards},
+    search::{astar, Budget, Path, PlanStats, SearchResult},
 };
 
 use bevy_reflect::Reflect;
+use std::sync::Arc;
+use web_time::{Duration, Instant};
 
 /// A Node holds things can return a state, used for path finding
 /// It's either the Initial [`LocalState`], or the [`LocalState`] after applying
@@ -36,136 +47,678 @@ impl std::fmt::Debug for Node {
     }
 }
 
-fn heuristic(node: &Node, goal: &Goal) -> usize {
-    let distance = node.state().distance_to_goal(goal) as usize;
-    distance
+/// Searching from the start towards the goal works on [`CompactState`]s, which are cheap to
+/// clone and hash, and only turns the final path back into a [`Plan`]
+pub(crate) struct ForwardSearch<'a> {
+    pub(crate) registry: KeyRegistry,
+    pub(crate) actions: Vec<CompactAction<'a>>,
+    /// The cost of each action that costs the same from every state, worked out up front
+    fixed_costs: Vec<Option<Option<f64>>>,
+    goal: CompactGoal<'a>,
+    heuristic: &'a dyn Heuristic,
+    cost_model: Option<&'a dyn CostModel>,
+    outcomes: OutcomeMode,
+    missing_keys: MissingKeyBehavior,
+    original_goal: &'a Goal,
 }
 
-fn successors<'a>(
-    node: &'a Node,
-    actions: &'a [Action],
-) -> impl Iterator<Item = (Node, usize)> + 'a {
-    let state = node.state();
-    actions.iter().filter_map(move |action| {
-        if check_preconditions(state, action) && !action.effects.is_empty() {
-            let new_state = state.clone();
-            let first_effect = &action.effects[0];
+impl<'a> ForwardSearch<'a> {
+    pub(crate) fn new(
+        start: &LocalState,
+        actions: &'a [Action],
+        goal: &'a Goal,
+        options: &'a PlanOptions,
+    ) -> Result<Self, PlanError> {
+        let registry =
+            KeyRegistry::from_domain(start, actions, goal).with_quantization(&options.quantization);
+        let actions = CompactAction::all_outcomes(actions, &registry)?;
+        // Costs that are invalid are left to fail once the action is taken
+        let fixed_costs = actions
+            .iter()
+            .map(|action| match options.cost_model {
+                None if action.effect().conditional_costs.is_empty() => options
+                    .outcomes
+                    .cost(&action.action().key, action.cost(), action.probability())
+                    .ok(),
+                _ => None,
+            })
+            .collect();
+        Ok(Self {
+            actions,
+            fixed_costs,
+            goal: CompactGoal::new(goal, &registry)?,
+            heuristic: options.heuristic.as_ref(),
+            cost_model: options.cost_model.as_deref(),
+            outcomes: options.outcomes,
+            missing_keys: options.missing_keys,
+            original_goal: goal,
+            registry,
+        })
+    }
+
+    pub(crate) fn heuristic(&self, state: &CompactState) -> Result<f64, PlanError> {
+        self.heuristic
+            .estimate(&SearchState::new(state, &self.registry), self.original_goal)
+    }
+
+    /// How far `state` is from the [`Goal`], see [`DistanceHeuristic`]. Used to find the
+    /// state that got closest when there is no plan, whatever the [`Heuristic`] is
+    pub(crate) fn distance(&self, state: &CompactState) -> Result<f64, PlanError> {
+        DistanceHeuristic.estimate(&SearchState::new(state, &self.registry), self.original_goal)
+    }
 
-            let mut new_data = new_state.data.clone();
-            for mutator in &first_effect.mutators {
-                apply_mutator(&mut new_data, mutator);
+    /// Adds the states reachable from `state` to `states`, with the index of the action
+    /// that leads to each and its cost
+    pub(crate) fn successors(
+        &self,
+        state: &CompactState,
+        states: &mut Vec<(CompactState, usize, f64)>,
+    ) -> Result<(), PlanError> {
+        for (index, action) in self.actions.iter().enumerate() {
+            if action.check_preconditions(state, &self.registry, self.missing_keys)? {
+                let Some(cost) = self.cost(index, state)? else {
+                    continue;
+                };
+                let new_state = action.apply(state, &self.registry, self.missing_keys)?;
+                states.push((new_state, index, cost));
             }
+        }
+        Ok(())
+    }
 
-            let new_effect = Effect {
-                action: first_effect.action.clone(),
-                mutators: first_effect.mutators.clone(),
-                cost: first_effect.cost,
-                state: LocalState { data: new_data },
-            };
-            Some((Node::Effect(new_effect), first_effect.cost))
-        } else {
-            None
+    /// What taking `action` from `state` costs, see [`CostModel`] and [`OutcomeMode`].
+    /// `None` if the outcome never happens
+    fn cost(&self, index: usize, state: &CompactState) -> Result<Option<f64>, PlanError> {
+        if let Some(cost) = self.fixed_costs[index] {
+            return Ok(cost);
         }
-    })
-}
+        let action = &self.actions[index];
+        let key = &action.action().key;
+        let state = SearchState::new(state, &self.registry);
+        let cost = match self.cost_model {
+            Some(cost_model) => cost_model.cost(action.action(), action.effect(), &state),
+            None => action.effect().cost_at(&state),
+        }
+        .map_err(|err| err.in_action(key))?;
+        self.outcomes.cost(key, cost, action.probability())
+    }
+
+    pub(crate) fn is_goal(&self, state: &CompactState) -> Result<bool, PlanError> {
+        self.goal
+            .is_satisfied(state, &self.registry, self.missing_keys)
+    }
 
-fn is_goal(node: &Node, goal: &Goal) -> bool {
-    goal.requirements.iter().all(|(key, value)| {
-        if let Some(state_val) = node.state().data.get(key) {
-            compare_values(value, state_val)
-        } else {
-            panic!("Couldn't find key {:#?} in LocalState", key);
+    /// Turns a path of states, each with the index of the [`Action`] that lead to it, back into a [`Plan`]
+    fn to_plan(&self, start: &LocalState, path: Path<CompactState, usize>, cost: f64) -> Plan {
+        let mut steps = Vec::with_capacity(path.len() - 1);
+        for step in path.windows(2) {
+            let [(previous, _), (state, action)] = step else {
+                unreachable!("Windows are always two nodes")
+            };
+            let index = action.expect("Only the start has no action");
+            let effect = self.actions[index].effect();
+            // Spelled out, so the state of the effect isn't cloned only to be replaced
+            steps.push(Effect {
+                action: effect.action.clone(),
+                mutators: effect.mutators.clone(),
+                state: state.to_local_state(&self.registry),
+                // Worked out while searching already, so this only falls back if a CostModel
+                // fails for the same state the second time around
+                cost: self
+                    .cost(index, previous)
+                    .ok()
+                    .flatten()
+                    .unwrap_or(effect.cost),
+                conditional_costs: effect.conditional_costs.clone(),
+                probability: effect.probability,
+                arguments: effect.arguments.clone(),
+            });
         }
-    })
+        Plan {
+            start: start.clone(),
+            steps,
+            cost,
+        }
+    }
 }
 
-/// Use [`make_plan`] instead
+/// Use [`make_plan`] instead. To use another [`Heuristic`] than [`DistanceHeuristic`], see
+/// [`PlanOptions::with_heuristic`]
 pub fn make_plan_with_strategy(
     strategy: PlanningStrategy,
     start: &LocalState,
     actions: &[Action],
     goal: &Goal,
-) -> Option<(Vec<Node>, usize)> {
-    match strategy {
-        PlanningStrategy::StartToGoal => {
-            let start_node = Node::State(start.clone());
-            pathfinding::directed::astar::astar(
-                &start_node,
-                |node| successors(node, actions).collect::<Vec<_>>().into_iter(),
-                |node| heuristic(node, goal),
-                |node| is_goal(node, goal),
-            )
+) -> Result<Plan, PlanError> {
+    make_plan_with_options(
+        &PlanOptions::new().with_strategy(strategy),
+        start,
+        actions,
+        goal,
+    )
+}
+
+/// Like [`make_plan`], but lets you pick the [`PlanningStrategy`] and limit how much work
+/// the planner is allowed to do. Returns [`PlanError::BudgetExhausted`] if the limits are
+/// reached before a plan was found
+pub fn make_plan_with_options(
+    options: &PlanOptions,
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+) -> Result<Plan, PlanError> {
+    make_plan_with_stats(options, start, actions, goal).0
+}
+
+/// Same as [`make_plan_with_options`], but also returns [`PlanStats`] about how much work
+/// the planner did, whether it found a plan or not
+pub fn make_plan_with_stats(
+    options: &PlanOptions,
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+) -> (Result<Plan, PlanError>, PlanStats) {
+    let started = Instant::now();
+    let mut stats = PlanStats::default();
+    let plan = plan(options, start, actions, goal, &mut stats);
+    stats.elapsed = started.elapsed();
+    log::debug!(
+        "Planning with {:?} took {:?}, expanded {} nodes, generated {}, pruned {} duplicates, peak open set {}",
+        options.strategy,
+        stats.elapsed,
+        stats.expanded_nodes,
+        stats.generated_nodes,
+        stats.duplicates_pruned,
+        stats.peak_open_set,
+    );
+    (plan, stats)
+}
+
+/// Like [`make_plan_with_options`], but starts out from the `previous` plan, for when the
+/// state changed a bit since it was made. This is usually a lot cheaper than planning from
+/// scratch, as long as the plan can mostly still be followed:
+///
+/// 1. The shortest part at the end of `previous` that can be taken from `start` and still
+///    reaches the `goal` is used as it is. That also skips the steps already taken since.
+/// 2. Otherwise, the steps up until the first one whose preconditions don't hold anymore
+///    are kept, followed by a short search for how to make them hold again, limited to
+///    [`PlanOptions::max_repair_nodes`], and then the rest of `previous`.
+/// 3. If neither works out, it plans from scratch like [`make_plan_with_options`].
+///
+/// The steps that are kept keep the cost they were planned with, so a repaired plan isn't
+/// necessarily the cheapest one anymore. Plan from scratch every now and then if that matters.
+/// Like with [`make_plan_with_options`], the [`Plan::start`] is `start` as it was passed in,
+/// not rounded by the [`Quantization`]
+pub fn repair_plan(
+    options: &PlanOptions,
+    previous: &Plan,
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+) -> Result<Plan, PlanError> {
+    repair_plan_with_stats(options, previous, start, actions, goal).0
+}
+
+/// Same as [`repair_plan`], but also returns [`PlanStats`] about how much work went into
+/// searching. The nodes expanded while repairing count against
+/// [`PlanOptions::max_expanded_nodes`] when planning from scratch after all
+pub fn repair_plan_with_stats(
+    options: &PlanOptions,
+    previous: &Plan,
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+) -> (Result<Plan, PlanError>, PlanStats) {
+    let started = Instant::now();
+    let mut stats = PlanStats::default();
+    let plan = repair(options, previous, start, actions, goal, &mut stats);
+    stats.elapsed = started.elapsed();
+    log::debug!(
+        "Repairing plan of {} steps took {:?}, expanded {} nodes",
+        previous.steps.len(),
+        stats.elapsed,
+        stats.expanded_nodes,
+    );
+    (plan, stats)
+}
+
+fn repair(
+    options: &PlanOptions,
+    previous: &Plan,
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+    stats: &mut PlanStats,
+) -> Result<Plan, PlanError> {
+    let grounded = &ground_actions(actions)?;
+    let (quantized_start, grounded, quantized_goal) =
+        options.quantization.quantize_domain(start, grounded, goal);
+    let (quantized_start, grounded, quantized_goal) = (
+        quantized_start.as_ref(),
+        grounded.as_ref(),
+        quantized_goal.as_ref(),
+    );
+    let replay = |steps| {
+        replay(
+            quantized_start,
+            steps,
+            grounded,
+            options.missing_keys,
+            &options.quantization,
+        )
+    };
+    let steps = &previous.steps;
+
+    // Where the plan breaks when following it from each step, from the last step backwards.
+    // Steps that don't change anything anymore were most likely taken already, so the place
+    // to repair is the one after the most steps that still do something
+    let mut broken: Option<Break> = None;
+    for skipped in (0..=steps.len()).rev() {
+        match replay(&steps[skipped..]) {
+            Ok(plan) if reaches_goal(plan.final_state(), quantized_goal, options.missing_keys)? => {
+                log::debug!("Reusing the last {} steps of the plan", plan.steps.len());
+                return Ok(Plan {
+                    start: start.clone(),
+                    ..plan
+                });
+            }
+            Ok(_) => {}
+            Err(StepFailure::PreconditionsNotMet { step, state, .. }) => {
+                let at = skipped + step;
+                let kept =
+                    replay(&steps[skipped..at]).expect("Steps before the broken one can be taken");
+                let changes = kept
+                    .nodes()
+                    .windows(2)
+                    .filter(|nodes| nodes[0].state() != nodes[1].state())
+                    .count();
+                if broken
+                    .as_ref()
+                    .map_or(true, |broken| changes > broken.changes)
+                {
+                    broken = Some(Break {
+                        skipped,
+                        at,
+                        state,
+                        changes,
+                    });
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    if let Some(Break {
+        skipped, at, state, ..
+    }) = broken
+    {
+        let action = step_action(grounded, &steps[at]).expect("Replay found the action");
+        let mut bridge_goal = Goal::new();
+        for (key, compare) in &action.preconditions {
+            // A key can only be required once, the rest have to be conditions
+            bridge_goal = if bridge_goal.requirements.contains_key(key) {
+                bridge_goal.with_condition(Condition::compare(key, compare.clone()))
+            } else {
+                bridge_goal.with_req(key, compare.clone())
+            };
+        }
+        bridge_goal
+            .conditions
+            .extend(action.conditions.iter().cloned());
+
+        let mut bridge_options = options.clone().with_partial_plans(false);
+        bridge_options.max_expanded_nodes = Some(
+            options
+                .max_expanded_nodes
+                .map_or(options.max_repair_nodes, |max| {
+                    max.min(options.max_repair_nodes)
+                }),
+        );
+        match plan(&bridge_options, &state, actions, &bridge_goal, stats) {
+            Ok(bridge) => {
+                let spliced: Vec<Effect> = steps[skipped..at]
+                    .iter()
+                    .chain(&bridge.steps)
+                    .chain(&steps[at..])
+                    .cloned()
+                    .collect();
+                if let Ok(plan) = replay(&spliced) {
+                    if reaches_goal(plan.final_state(), quantized_goal, options.missing_keys)? {
+                        log::debug!(
+                            "Repaired the plan with {} steps before {:?}",
+                            bridge.steps.len(),
+                            action.key
+                        );
+                        return Ok(Plan {
+                            start: start.clone(),
+                            ..plan
+                        });
+                    }
+                }
+            }
+            Err(PlanError::NoPlanFound { .. } | PlanError::BudgetExhausted { .. }) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    log::debug!("Couldn't repair the plan, planning from scratch");
+    plan(options, start, actions, goal, stats)
+}
+
+/// A step of the plan [`repair`] could fix, after following it from step `skipped`
+struct Break {
+    skipped: usize,
+    /// The step whose preconditions don't hold
+    at: usize,
+    /// The state before step `at`
+    state: LocalState,
+    /// How many of the steps from `skipped` to `at` change the state
+    changes: usize,
+}
+
+/// If `state` fulfills the whole `goal`, where a missing key means it doesn't unless
+/// `missing` reads it
+fn reaches_goal(
+    state: &LocalState,
+    goal: &Goal,
+    missing: MissingKeyBehavior,
+) -> Result<bool, PlanError> {
+    let get = |key: &str| state.data.get(key).copied();
+    let met = |result: Result<bool, PlanError>| match result {
+        Err(PlanError::MissingKey { .. }) => Ok(false),
+        result => result,
+    };
+    for (key, compare) in &goal.requirements {
+        if !met(check_compare(state, key, compare, missing))? {
+            return Ok(false);
         }
-        PlanningStrategy::GoalToStart => {
-            panic!("PlanningStrategy::GoalToStart hasn't been implemented yet!");
+    }
+    for condition in &goal.conditions {
+        if !met(condition.evaluate_with(&get, missing))? {
+            return Ok(false);
         }
     }
+    Ok(true)
 }
 
-/// Currently, only [`PlanningStrategy::StartToGoal`] is supported, which tries to find the chain of
-/// [`Effect`]s that lead to our [`Goal`] state
-#[derive(Default)]
+fn plan(
+    options: &PlanOptions,
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+    stats: &mut PlanStats,
+) -> Result<Plan, PlanError> {
+    let budget = options.budget();
+    let actions = &ground_actions(actions)?;
+    let backwards = match options.strategy {
+        PlanningStrategy::StartToGoal => false,
+        PlanningStrategy::GoalToStart => match backwards_fallback(options, start, actions) {
+            Some(reason) => {
+                log::debug!("Can't plan with GoalToStart {reason}, searching StartToGoal instead");
+                stats.searched_forward_instead = true;
+                false
+            }
+            None => true,
+        },
+    };
+    match options.strategy {
+        PlanningStrategy::GoalToStart if backwards => plan_backwards(
+            start,
+            actions,
+            goal,
+            &budget,
+            options.missing_keys,
+            options.outcomes,
+            stats,
+        ),
+        PlanningStrategy::StartToGoal | PlanningStrategy::GoalToStart => {
+            let (quantized_start, actions, goal) =
+                options.quantization.quantize_domain(start, actions, goal);
+            let search = ForwardSearch::new(&quantized_start, &actions, &goal, options)?;
+            let start_state = CompactState::from_local_state(&quantized_start, &search.registry)?;
+            let result = astar(
+                std::slice::from_ref(&start_state),
+                |state, states| search.successors(state, states),
+                |state| search.heuristic(state),
+                // Only needed to find the partial plan
+                |state| match options.partial_plans {
+                    true => search.distance(state),
+                    false => Ok(0.0),
+                },
+                |state| search.is_goal(state),
+                &budget,
+                stats,
+            )?;
+            // A path with only the start state in it doesn't get us anywhere
+            let partial = |(path, cost): (Path<CompactState, usize>, f64)| {
+                (options.partial_plans && path.len() > 1).then(|| search.to_plan(start, path, cost))
+            };
+            match result {
+                SearchResult::Found(path, cost) => Ok(search.to_plan(start, path, cost)),
+                SearchResult::NotFound { closest } => Err(PlanError::NoPlanFound {
+                    partial: partial(closest),
+                }),
+                SearchResult::BudgetExhausted {
+                    expanded_nodes,
+                    closest,
+                } => Err(PlanError::BudgetExhausted {
+                    expanded_nodes,
+                    partial: partial(closest),
+                }),
+            }
+        }
+    }
+}
+
+/// Why [`PlanningStrategy::GoalToStart`] can't search backwards with these options and
+/// actions, if it can't
+fn backwards_fallback(
+    options: &PlanOptions,
+    start: &LocalState,
+    actions: &[Action],
+) -> Option<&'static str> {
+    if options.cost_model.is_some() {
+        Some("with a cost model")
+    } else if !options.quantization.is_empty() {
+        Some("with quantization")
+    } else if !can_regress(start, actions) {
+        Some("as the mutators or costs of some actions can't be undone")
+    } else {
+        None
+    }
+}
+
+/// Options for [`make_plan_with_options`]. By default there are no limits, and the planner
+/// searches until it either finds a plan or has tried every possible [`Action`]
+#[derive(Clone)]
+pub struct PlanOptions {
+    pub strategy: PlanningStrategy,
+    /// Guides the [`PlanningStrategy::StartToGoal`] search, defaults to [`DistanceHeuristic`].
+    /// [`PlanningStrategy::GoalToStart`] has its own estimate based on the requirements
+    /// that are left, and doesn't use this
+    pub heuristic: Arc<dyn Heuristic>,
+    /// Give up after expanding this many nodes
+    pub max_expanded_nodes: Option<usize>,
+    /// Give up once this point in time has passed
+    pub deadline: Option<Instant>,
+    /// When no plan is found, include the plan that got closest to the [`Goal`] in the
+    /// [`PlanError`], see [`PlanError::partial_plan`]
+    pub partial_plans: bool,
+    /// What mutators like `Increment`, preconditions and goals do with keys that aren't in the
+    /// state
+    pub missing_keys: MissingKeyBehavior,
+    /// Decides what each [`Action`] costs in the state it's taken from. Without one, the
+    /// costs of the [`Effect`]s are used
+    pub cost_model: Option<Arc<dyn CostModel>>,
+    /// How [`Action`]s with more than one [`Effect`] are planned with
+    pub outcomes: OutcomeMode,
+    /// How many nodes [`repair_plan`] may expand to get past a step that can't be taken
+    /// anymore, before it plans from scratch instead
+    pub max_repair_nodes: usize,
+    /// How finely the floats of each key are told apart, see [`Quantization`]. Setting any
+    /// step makes [`PlanningStrategy::GoalToStart`] search from the start instead
+    pub quantization: Quantization,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            strategy: PlanningStrategy::default(),
+            heuristic: Arc::new(DistanceHeuristic),
+            max_expanded_nodes: None,
+            deadline: None,
+            partial_plans: false,
+            missing_keys: MissingKeyBehavior::default(),
+            cost_model: None,
+            outcomes: OutcomeMode::default(),
+            max_repair_nodes: 100,
+            quantization: Quantization::default(),
+        }
+    }
+}
+
+impl std::fmt::Debug for PlanOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlanOptions")
+            .field("strategy", &self.strategy)
+            .field("max_expanded_nodes", &self.max_expanded_nodes)
+            .field("deadline", &self.deadline)
+            .field("partial_plans", &self.partial_plans)
+            .field("missing_keys", &self.missing_keys)
+            .field("outcomes", &self.outcomes)
+            .field("max_repair_nodes", &self.max_repair_nodes)
+            .field("quantization", &self.quantization)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PlanOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_strategy(mut self, strategy: PlanningStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_heuristic(mut self, heuristic: impl Heuristic + 'static) -> Self {
+        self.heuristic = Arc::new(heuristic);
+        self
+    }
+
+    pub fn with_max_expanded_nodes(mut self, max_expanded_nodes: usize) -> Self {
+        self.max_expanded_nodes = Some(max_expanded_nodes);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_partial_plans(mut self, partial_plans: bool) -> Self {
+        self.partial_plans = partial_plans;
+        self
+    }
+
+    pub fn with_missing_keys(mut self, missing_keys: MissingKeyBehavior) -> Self {
+        self.missing_keys = missing_keys;
+        self
+    }
+
+    pub fn with_cost_model(mut self, cost_model: impl CostModel + 'static) -> Self {
+        self.cost_model = Some(Arc::new(cost_model));
+        self
+    }
+
+    pub fn with_outcomes(mut self, outcomes: OutcomeMode) -> Self {
+        self.outcomes = outcomes;
+        self
+    }
+
+    pub fn with_max_repair_nodes(mut self, max_repair_nodes: usize) -> Self {
+        self.max_repair_nodes = max_repair_nodes;
+        self
+    }
+
+    /// Rounds the floats of `key` to multiples of `step` while planning, see [`Quantization`]
+    pub fn with_quantization_step(mut self, key: &str, step: f64) -> Self {
+        self.quantization = self.quantization.with_step(key, step);
+        self
+    }
+
+    /// Sets the deadline to `timeout` from now
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    pub(crate) fn budget(&self) -> Budget {
+        Budget {
+            max_expanded_nodes: self.max_expanded_nodes,
+            deadline: self.deadline,
+        }
+    }
+}
+
+/// Decides in what direction the planner searches for the chain of [`Effect`]s that lead
+/// to our [`Goal`] state
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub enum PlanningStrategy {
     #[default]
     /// StartToGoal begins with our current state, and finds the most optimal path to the goal, based on the costs
-    /// Might take longer time than GoalToStart, but finds the path with the lowest cost
+    /// This is the faster strategy for most domains, and finds the path with the lowest cost
     StartToGoal,
-    /// GoalToStart begins with the goal state, and works backwards from there, in order to find a path as quick as possible
-    /// Might lead to less-than-optimial paths, but should find a valid path quicker
+    /// GoalToStart begins with the goal state, and works backwards from there
+    /// Only the requirements that matter for reaching the goal are tracked, which keeps the search
+    /// space small when there are lots of unrelated keys and actions in the [`LocalState`], and
+    /// that's the only case where it's faster. In the `long_plan` benchmark, where every key
+    /// matters, it takes over ten times as long as StartToGoal
+    ///
+    /// Its estimate of what's left is the most actions any one requirement still needs, times
+    /// the cost of the cheapest action. That's never more than what's really left, so it
+    /// finds the cheapest plan
+    ///
+    /// Actions that Multiply, Divide, ClampMin or ClampMax can't be searched backwards, so if
+    /// any action has one of those mutators, or changes a key that isn't in the start state,
+    /// the planner searches like StartToGoal instead. The same goes for costs that depend on
+    /// the state, from `conditional_costs` or [`PlanOptions::cost_model`], as the state before
+    /// an action isn't known when searching backwards, and for a [`PlanOptions::quantization`].
+    /// When that happens, [`PlanStats::searched_forward_instead`] is set and the reason is logged
     GoalToStart,
 }
 
 /// Returns a path of [`Node`]s that leads from our start [`LocalState`] to our
-/// [`Goal`] state
-pub fn make_plan(
-    start: &LocalState,
-    actions: &[Action],
-    goal: &Goal,
-) -> Option<(Vec<Node>, usize)> {
+/// [`Goal`] state, or a [`PlanError`] explaining why there isn't one
+pub fn make_plan(start: &LocalState, actions: &[Action], goal: &Goal) -> Result<Plan, PlanError> {
     // Default to using Start -> Goal planning
     make_plan_with_strategy(PlanningStrategy::StartToGoal, start, actions, goal)
 }
 
 /// Returns a Vector of all [`Effect`]s from a given plan
-pub fn get_effects_from_plan(plan: Vec<Node>) -> Vec<Effect> {
-    let mut nodes = vec![];
-
-    for node in plan {
-        match node {
-            Node::Effect(c) => nodes.push(c),
-            Node::State(_s) => {}
-        }
-    }
-
-    nodes
+pub fn get_effects_from_plan(plan: Plan) -> Vec<Effect> {
+    plan.steps
 }
 
 /// Prints a human-readable version of a plan from [`make_plan`] that shows
 /// what [`Action`]s needs to be executed and what the results of each Action is
-pub fn print_plan(plan: (Vec<Node>, usize)) {
-    let nodes = plan.0;
-    let cost = plan.1;
-    let mut last_state = LocalState::new();
-    for node in nodes {
-        match node {
-            Node::Effect(effect) => {
-                println!("\t\t= DO ACTION {:#?}", effect.action);
-                println!("\t\tMUTATES:");
-                print_mutators(effect.mutators);
-                last_state = effect.state.clone();
-            }
-            Node::State(s) => {
-                println!("\t\t= INITIAL STATE");
-                for (k, v) in &s.data {
-                    println!("\t\t{} = {}", k, v);
-                }
-                last_state = s.clone();
-            }
+pub fn print_plan(plan: Plan) {
+    println!("\t\t= INITIAL STATE");
+    for (k, v) in &plan.start.data {
+        println!("\t\t{} = {}", k, v);
+    }
+    println!("\n\t\t---\n");
+    for effect in &plan.steps {
+        println!("\t\t= DO ACTION {:#?}", effect.action);
+        for (name, value) in &effect.arguments {
+            println!("\t\t{} = {}", name, value);
         }
+        println!("\t\tMUTATES:");
+        print_mutators(effect.mutators.clone());
         println!("\n\t\t---\n");
     }
-    println!("\t\t= FINAL STATE (COST: {})", cost);
-    for (k, v) in &last_state.data {
+    println!("\t\t= FINAL STATE (COST: {})", plan.cost);
+    for (k, v) in &plan.final_state().data {
         println!("\t\t{} = {}", k, v);
     }
 }
@@ -1,11 +1,27 @@
 // crate "dogoap" src/prelude.rs
-pub use crate::action::Action;
-pub use crate::compare::Compare;
+pub use crate::action::{Action, Parameter};
+pub use crate::cache::PlanCache;
+pub use crate::compact::SearchState;
+pub use crate::compare::{Compare, Comparison, KeyOperand};
+pub use crate::condition::Condition;
+pub use crate::cost::{CostModel, OutcomeMode};
 pub use crate::datum::Datum;
+pub use crate::domain::{Domain, DomainProblem};
 pub use crate::effect::Effect;
+pub use crate::enum_value::EnumValue;
+pub use crate::error::PlanError;
+pub use crate::explain::{explain_no_plan, NoPlanExplanation};
 pub use crate::goal::Goal;
+pub use crate::heuristic::{AdmissibleHeuristic, DistanceHeuristic, Heuristic, ZeroHeuristic};
+pub use crate::htn::{make_htn_plan, CompoundTask, Method, Task};
 pub use crate::localstate::LocalState;
-pub use crate::mutator::Mutator;
+pub use crate::mutator::{MissingKeyBehavior, Mutator};
+pub use crate::plan::{Plan, Simulation, StepFailure};
 pub use crate::planner::{
-    get_effects_from_plan, make_plan, make_plan_with_strategy, print_plan, Node, PlanningStrategy,
+    get_effects_from_plan, make_plan, make_plan_with_options, make_plan_with_stats,
+    make_plan_with_strategy, print_plan, repair_plan, repair_plan_with_stats, Node, PlanOptions,
+    PlanningStrategy,
 };
+pub use crate::quantization::Quantization;
+pub use crate::search::PlanStats;
+pub use crate::symbol::Symbol;
@@ -0,0 +1,246 @@
+// crate "dogoap" file quantization.rs
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use bevy_reflect::Reflect;
+
+use crate::{
+    action::Action, compare::Compare, condition::Condition, datum::Datum, effect::Effect,
+    goal::Goal, localstate::LocalState,
+};
+
+/// How finely the [`Datum::F64`] values of each key are told apart while planning. Values
+/// are rounded to the closest multiple of the step of their key, in every state as well as
+/// in what they're compared with, so with a step of `0.01`, a hunger of `49.9999` is the
+/// same state as `50.0` and fulfills `Compare::Equals(Datum::F64(50.0))`.
+///
+/// Without it, every float that comes out slightly different after a few increments is a
+/// new state for the planner to explore. Keys without a step are left as they are.
+///
+/// Searching backwards can't round the values it works out, so with any step set,
+/// [`PlanningStrategy::GoalToStart`](crate::planner::PlanningStrategy::GoalToStart) searches
+/// from the start instead, see [`PlanStats::searched_forward_instead`](crate::search::PlanStats::searched_forward_instead).
+/// The [`Plan::start`](crate::plan::Plan::start) is still the state that was planned from,
+/// as it was before rounding
+#[derive(Reflect, Clone, Debug, Default, PartialEq)]
+pub struct Quantization {
+    /// The step of each key, which has to be finite and above zero
+    pub steps: BTreeMap<String, f64>,
+}
+
+impl Hash for Quantization {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.steps.len().hash(state);
+        for (key, step) in &self.steps {
+            key.hash(state);
+            step.to_bits().hash(state);
+        }
+    }
+}
+
+impl Quantization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_step(mut self, key: &str, step: f64) -> Self {
+        self.steps.insert(key.to_string(), step);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn step(&self, key: &str) -> Option<f64> {
+        self.steps.get(key).copied()
+    }
+
+    /// `value` rounded to the step of `key`, see [`Datum::quantize`]
+    pub fn quantize(&self, key: &str, value: Datum) -> Datum {
+        match self.step(key) {
+            Some(step) => value.quantize(step),
+            None => value,
+        }
+    }
+
+    /// Rounds every value in `state` that has a step
+    pub fn quantize_state(&self, state: &mut LocalState) {
+        for (key, step) in &self.steps {
+            if let Some(value) = state.data.get_mut(key) {
+                *value = value.quantize(*step);
+            }
+        }
+    }
+
+    /// `compare` with the values it compares the value of `key` with rounded to the step of
+    /// `key`. The value of another key in a [`Compare::ToKey`] is rounded to its own step
+    pub fn quantize_compare(&self, key: &str, compare: &Compare) -> Compare {
+        let Some(step) = self.step(key) else {
+            return compare.clone();
+        };
+        let q = |value: &Datum| value.quantize(step);
+        match compare {
+            Compare::Equals(value) => Compare::Equals(q(value)),
+            Compare::NotEquals(value) => Compare::NotEquals(q(value)),
+            Compare::GreaterThanEquals(value) => Compare::GreaterThanEquals(q(value)),
+            Compare::LessThanEquals(value) => Compare::LessThanEquals(q(value)),
+            Compare::GreaterThan(value) => Compare::GreaterThan(q(value)),
+            Compare::LessThan(value) => Compare::LessThan(q(value)),
+            Compare::InRange(min, max) => Compare::InRange(q(min), q(max)),
+            Compare::OneOf(values) => Compare::OneOf(values.iter().map(q).collect()),
+            Compare::ToKey(..) => compare.clone(),
+        }
+    }
+
+    fn quantize_condition(&self, condition: &Condition) -> Condition {
+        let quantized = condition.try_map(&|key, compare| {
+            Ok(Condition::compare(key, self.quantize_compare(key, compare)))
+        });
+        quantized.expect("Quantizing never fails")
+    }
+
+    /// `action` with everything it compares rounded, including the conditions of its
+    /// `conditional_costs`. What the mutators change is rounded while planning instead
+    pub fn quantize_action(&self, action: &Action) -> Action {
+        Action {
+            preconditions: action
+                .preconditions
+                .iter()
+                .map(|(key, compare)| (key.clone(), self.quantize_compare(key, compare)))
+                .collect(),
+            conditions: action
+                .conditions
+                .iter()
+                .map(|condition| self.quantize_condition(condition))
+                .collect(),
+            effects: action
+                .effects
+                .iter()
+                .map(|effect| Effect {
+                    conditional_costs: effect
+                        .conditional_costs
+                        .iter()
+                        .map(|(condition, cost)| (self.quantize_condition(condition), *cost))
+                        .collect(),
+                    ..effect.clone()
+                })
+                .collect(),
+            ..action.clone()
+        }
+    }
+
+    pub fn quantize_goal(&self, goal: &Goal) -> Goal {
+        Goal {
+            requirements: goal
+                .requirements
+                .iter()
+                .map(|(key, compare)| (key.clone(), self.quantize_compare(key, compare)))
+                .collect(),
+            conditions: goal
+                .conditions
+                .iter()
+                .map(|condition| self.quantize_condition(condition))
+                .collect(),
+        }
+    }
+
+    /// The start state, actions and goal quantized, or borrowed as they are if there
+    /// aren't any steps
+    pub(crate) fn quantize_domain<'a>(
+        &self,
+        start: &'a LocalState,
+        actions: &'a [Action],
+        goal: &'a Goal,
+    ) -> (Cow<'a, LocalState>, Cow<'a, [Action]>, Cow<'a, Goal>) {
+        if self.is_empty() {
+            return (
+                Cow::Borrowed(start),
+                Cow::Borrowed(actions),
+                Cow::Borrowed(goal),
+            );
+        }
+        let mut start = start.clone();
+        self.quantize_state(&mut start);
+        (
+            Cow::Owned(start),
+            Cow::Owned(
+                actions
+                    .iter()
+                    .map(|action| self.quantize_action(action))
+                    .collect(),
+            ),
+            Cow::Owned(self.quantize_goal(goal)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize() {
+        let quantization = Quantization::new().with_step("hunger", 0.5);
+        assert_eq!(
+            Datum::F64(50.0),
+            quantization.quantize("hunger", Datum::F64(49.9999))
+        );
+        assert_eq!(
+            Datum::F64(49.5),
+            quantization.quantize("hunger", Datum::F64(49.7))
+        );
+        // Other keys and types are left alone
+        assert_eq!(
+            Datum::F64(49.9999),
+            quantization.quantize("thirst", Datum::F64(49.9999))
+        );
+        assert_eq!(
+            Datum::I64(3),
+            quantization.quantize("hunger", Datum::I64(3))
+        );
+        // Rounding to zero from below is still the same zero
+        assert_eq!(
+            Datum::F64(0.0).quantize(0.5),
+            quantization.quantize("hunger", Datum::F64(-0.1))
+        );
+        let zero = |value: f64| Datum::F64(value).quantize(0.5);
+        let hash = |value: Datum| {
+            let mut hasher = std::hash::DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(zero(0.1)), hash(zero(-0.1)));
+    }
+
+    #[test]
+    fn test_quantize_compare() {
+        let quantization = Quantization::new().with_step("hunger", 0.1);
+        assert_eq!(
+            Compare::InRange(Datum::F64(0.0), Datum::F64(10.0)),
+            quantization.quantize_compare(
+                "hunger",
+                &Compare::InRange(Datum::F64(0.04), Datum::F64(9.96))
+            )
+        );
+        let goal = Goal::new()
+            .with_req("hunger", Compare::Equals(Datum::F64(0.01)))
+            .with_condition(Condition::compare(
+                "hunger",
+                Compare::LessThan(Datum::F64(2.02)),
+            ));
+        let goal = quantization.quantize_goal(&goal);
+        assert_eq!(
+            Some(&Compare::Equals(Datum::F64(0.0))),
+            goal.requirements.get("hunger")
+        );
+        assert_eq!(
+            vec![Condition::compare(
+                "hunger",
+                Compare::LessThan(Datum::F64(2.0))
+            )],
+            goal.conditions
+        );
+    }
+}
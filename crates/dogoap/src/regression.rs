@@ -0,0 +1,840 @@
+// crate "dogoap" file regression.rs
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    action::Action,
+    compact::{CompactAction, CompactGoal, CompactMutator, CompactState, KeyRegistry},
+    compare::{check_preconditions, check_value, compare_values, Compare, KeyOperand},
+    cost::OutcomeMode,
+    datum::Datum,
+    effect::Effect,
+    error::PlanError,
+    goal::Goal,
+    localstate::LocalState,
+    mutator::{apply_mutator, MissingKeyBehavior, Mutator},
+    plan::Plan,
+    search::{astar, Budget, PlanStats, SearchResult},
+};
+
+/// A set of requirements that has to be true before executing the [`Action`]s found so far.
+/// Keys are indices in the [`KeyRegistry`]. Sorted by key, with at most one inclusive bound in each
+/// direction per key
+type Requirements = Vec<(usize, Compare)>;
+
+/// Moves a [`Compare`] from "after the Mutator" to "before the Mutator".
+/// Returns `None` if the Mutator can't ever lead to the comparison holding.
+///
+/// For [`Compare::ToKey`] this is the Mutator of the key being compared, so the offset on the
+/// other side is moved instead
+fn shift_compare(compare: &Compare, by: &Datum, increment: bool) -> Option<Compare> {
+    let shift = |value: &Datum| -> Option<Datum> {
+        match (value, by) {
+            (Datum::I64(a), Datum::I64(b)) => {
+                Some(Datum::I64(if increment { a - b } else { a + b }))
+            }
+            (Datum::F64(a), Datum::F64(b)) => {
+                Some(Datum::F64(if increment { a - b } else { a + b }))
+            }
+            _ => None,
+        }
+    };
+    Some(match compare {
+        Compare::Equals(v) => Compare::Equals(shift(v)?),
+        Compare::NotEquals(v) => Compare::NotEquals(shift(v)?),
+        Compare::GreaterThanEquals(v) => Compare::GreaterThanEquals(shift(v)?),
+        Compare::LessThanEquals(v) => Compare::LessThanEquals(shift(v)?),
+        Compare::GreaterThan(v) => Compare::GreaterThan(shift(v)?),
+        Compare::LessThan(v) => Compare::LessThan(shift(v)?),
+        Compare::InRange(min, max) => Compare::InRange(shift(min)?, shift(max)?),
+        Compare::OneOf(values) => Compare::OneOf(values.iter().map(shift).collect::<Option<_>>()?),
+        Compare::ToKey(comparison, operand) => {
+            let offset = match (&operand.offset, by) {
+                (Some(offset), _) => *offset,
+                (None, Datum::I64(_)) => Datum::I64(0),
+                (None, Datum::F64(_)) => Datum::F64(0.0),
+                (None, _) => return None,
+            };
+            Compare::ToKey(
+                *comparison,
+                KeyOperand {
+                    key: operand.key.clone(),
+                    offset: Some(shift(&offset)?),
+                },
+            )
+        }
+    })
+}
+
+/// Moves a [`Compare`] on a `Datum::Bool` from "after a Toggle" to "before the Toggle".
+/// Returns `None` for Compares that only make sense for numbers
+fn toggle_compare(compare: &Compare) -> Option<Compare> {
+    let flip = |value: &Datum| match value {
+        Datum::Bool(value) => Some(Datum::Bool(!value)),
+        _ => None,
+    };
+    Some(match compare {
+        Compare::Equals(v) => Compare::Equals(flip(v)?),
+        Compare::NotEquals(v) => Compare::NotEquals(flip(v)?),
+        Compare::OneOf(values) => Compare::OneOf(values.iter().map(flip).collect::<Option<_>>()?),
+        _ => return None,
+    })
+}
+
+/// If every [`Mutator`] of the `actions` can be undone while searching backwards.
+/// Multiply, Divide, ClampMin and ClampMax lose track of what the value was before them,
+/// so there's no single requirement to regress to. The same goes for changing keys that
+/// are missing from the `start`, as what happens to those depends on the
+/// [`MissingKeyBehavior`]. Conditional costs need the state before the action, which
+/// isn't known either
+pub fn can_regress(start: &LocalState, actions: &[Action]) -> bool {
+    actions
+        .iter()
+        .flat_map(|action| &action.effects)
+        .all(|effect| {
+            effect.conditional_costs.is_empty()
+                && effect.mutators.iter().all(|mutator| match mutator {
+                    Mutator::Multiply(_, _)
+                    | Mutator::Divide(_, _)
+                    | Mutator::ClampMin(_, _)
+                    | Mutator::ClampMax(_, _) => false,
+                    Mutator::Increment(key, _)
+                    | Mutator::Decrement(key, _)
+                    | Mutator::Toggle(key) => start.data.contains_key(key),
+                    Mutator::Set(_, _) | Mutator::CopyFrom(_, _) => true,
+                })
+        })
+}
+
+/// Figures out what requirements need to hold before `action`, in order for `requirements`
+/// to hold after it, with one set of requirements for each way the conditions of the action
+/// can be fulfilled. Empty if the action is irrelevant to (or conflicts with) the requirements
+fn regress(
+    requirements: &Requirements,
+    action: &CompactAction,
+    registry: &KeyRegistry,
+) -> Vec<Requirements> {
+    let Some(regressed) = regress_mutators(requirements, action, registry) else {
+        return vec![];
+    };
+    action
+        .alternatives()
+        .iter()
+        .filter_map(|alternative| {
+            let mut requirements = regressed.clone();
+            requirements.extend(alternative.iter().cloned());
+            simplify(requirements)
+        })
+        .collect()
+}
+
+/// Undoes the mutators of `action` on `requirements` and adds the preconditions, before
+/// any [`Condition`](crate::condition::Condition)s and simplifying
+fn regress_mutators(
+    requirements: &Requirements,
+    action: &CompactAction,
+    registry: &KeyRegistry,
+) -> Option<Requirements> {
+    let mut relevant = false;
+    let mut regressed: Requirements = vec![];
+
+    for (key, compare) in requirements {
+        // Setting one side of a Compare::ToKey turns it into a requirement on the other
+        // key, so the key can change along the way
+        let mut current = Some((*key, compare.clone()));
+        // Mutators are applied in order, so we have to undo them in reverse
+        for mutator in action.mutators().iter().rev() {
+            let Some((key, compare)) = &current else {
+                break;
+            };
+            let other = compare.other_key().and_then(|other| registry.index(other));
+            match (mutator, compare) {
+                (CompactMutator::Set(k, value), Compare::ToKey(comparison, operand))
+                    if k == key =>
+                {
+                    // "value >= other + offset" is the same as "other <= value - offset"
+                    let value = match &operand.offset {
+                        Some(offset) => value.checked_sub(offset)?,
+                        None => *value,
+                    };
+                    relevant = true;
+                    current = Some((other?, comparison.flip().with(value)));
+                }
+                (CompactMutator::Set(k, value), _) if k == key => {
+                    if !compare_values(compare, value) {
+                        // The action would break this requirement
+                        return None;
+                    }
+                    relevant = true;
+                    current = None;
+                }
+                (CompactMutator::Set(k, value), _) if Some(*k) == other => {
+                    relevant = true;
+                    let compare = compare.resolve(|_| Some(*value)).ok()?.into_owned();
+                    current = Some((*key, compare));
+                }
+                (CompactMutator::Increment(k, value), _) if k == key => {
+                    relevant = true;
+                    current = Some((*key, shift_compare(compare, value, true)?));
+                }
+                (CompactMutator::Decrement(k, value), _) if k == key => {
+                    relevant = true;
+                    current = Some((*key, shift_compare(compare, value, false)?));
+                }
+                // The other side moving up is the same as this side moving down
+                (CompactMutator::Increment(k, value), _) if Some(*k) == other => {
+                    relevant = true;
+                    current = Some((*key, shift_compare(compare, value, false)?));
+                }
+                (CompactMutator::Decrement(k, value), _) if Some(*k) == other => {
+                    relevant = true;
+                    current = Some((*key, shift_compare(compare, value, true)?));
+                }
+                (CompactMutator::Toggle(k), _) if k == key => {
+                    relevant = true;
+                    current = Some((*key, toggle_compare(compare)?));
+                }
+                (CompactMutator::CopyFrom(k, source), _) if k == key => {
+                    // Whatever the key has to be after copying, the source has to be before
+                    relevant = true;
+                    current = Some((*source, compare.clone()));
+                }
+                (CompactMutator::CopyFrom(k, source), Compare::ToKey(comparison, operand))
+                    if Some(*k) == other =>
+                {
+                    relevant = true;
+                    let operand = KeyOperand {
+                        key: registry.key(*source).to_string(),
+                        offset: operand.offset,
+                    };
+                    current = Some((*key, Compare::ToKey(*comparison, operand)));
+                }
+                // The rest can't be undone, see can_regress
+                (mutator, _) if mutator.index() == *key || Some(mutator.index()) == other => {
+                    return None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(requirement) = current {
+            regressed.push(requirement);
+        }
+    }
+
+    if !relevant {
+        return None;
+    }
+
+    regressed.extend(
+        action
+            .preconditions()
+            .iter()
+            .map(|(key, compare)| (*key, (*compare).clone())),
+    );
+    Some(regressed)
+}
+
+/// Merges all requirements for the same key into as few as possible, so that the same
+/// requirements reached in different ways end up as the same node.
+/// Returns `None` if the requirements for one key can never be true at the same time
+fn simplify(mut requirements: Requirements) -> Option<Requirements> {
+    requirements.sort_by_key(|(key, _)| *key);
+
+    let mut simplified = vec![];
+    for group in requirements.chunk_by(|(a, _), (b, _)| a == b) {
+        let key = group[0].0;
+        // Comparisons to other keys can't be merged with anything, so they're only deduplicated
+        let mut to_keys: Vec<&Compare> = vec![];
+        for (_, compare) in group {
+            if matches!(compare, Compare::ToKey(_, _)) && !to_keys.contains(&compare) {
+                to_keys.push(compare);
+            }
+        }
+        simplified.extend(to_keys.into_iter().map(|compare| (key, compare.clone())));
+        let compares = group
+            .iter()
+            .map(|(_, compare)| compare)
+            .filter(|compare| !matches!(compare, Compare::ToKey(_, _)));
+
+        if let Some(Compare::Equals(value)) = compares
+            .clone()
+            .find(|compare| matches!(compare, Compare::Equals(_)))
+        {
+            // Everything else is redundant as long as it agrees with the exact value
+            if !compares
+                .clone()
+                .all(|compare| compare_values(compare, value))
+            {
+                return None;
+            }
+            simplified.push((key, Compare::Equals(*value)));
+            continue;
+        }
+
+        let mut lower: Option<Datum> = None;
+        let mut upper: Option<Datum> = None;
+        let mut not_equals: Vec<Datum> = vec![];
+        // Kept as they are, only without duplicates
+        let mut others: Vec<&Compare> = vec![];
+        for compare in compares {
+            match compare {
+                Compare::GreaterThanEquals(v) => {
+                    if lower.map_or(true, |lower| *v > lower) {
+                        lower = Some(*v);
+                    }
+                }
+                Compare::LessThanEquals(v) => {
+                    if upper.map_or(true, |upper| *v < upper) {
+                        upper = Some(*v);
+                    }
+                }
+                Compare::NotEquals(v) => {
+                    if !not_equals.contains(v) {
+                        not_equals.push(*v);
+                    }
+                }
+                Compare::Equals(_) | Compare::ToKey(_, _) => unreachable!("handled above"),
+                Compare::GreaterThan(_)
+                | Compare::LessThan(_)
+                | Compare::InRange(_, _)
+                | Compare::OneOf(_) => {
+                    if !others.contains(&compare) {
+                        others.push(compare);
+                    }
+                }
+            }
+        }
+
+        if let (Some(lower), Some(upper)) = (lower, upper) {
+            if lower > upper {
+                return None;
+            }
+        }
+
+        if let Some(lower) = lower {
+            simplified.push((key, Compare::GreaterThanEquals(lower)));
+        }
+        if let Some(upper) = upper {
+            simplified.push((key, Compare::LessThanEquals(upper)));
+        }
+        for value in not_equals {
+            simplified.push((key, Compare::NotEquals(value)));
+        }
+        for compare in others {
+            simplified.push((key, compare.clone()));
+        }
+    }
+
+    Some(simplified)
+}
+
+/// `costs` has the cost of each action, `None` for outcomes that never happen
+fn successors<'a>(
+    requirements: &'a Requirements,
+    actions: &'a [CompactAction],
+    costs: &'a [Option<f64>],
+    registry: &'a KeyRegistry,
+) -> impl Iterator<Item = (Requirements, usize, f64)> + 'a {
+    actions
+        .iter()
+        .zip(costs)
+        .enumerate()
+        .filter_map(|(index, (action, cost))| Some((index, action, (*cost)?)))
+        .flat_map(move |(index, action, cost)| {
+            regress(requirements, action, registry)
+                .into_iter()
+                .map(move |regressed| (regressed, index, cost))
+        })
+}
+
+fn is_satisfied(
+    start: &CompactState,
+    registry: &KeyRegistry,
+    key: usize,
+    compare: &Compare,
+    missing: MissingKeyBehavior,
+) -> bool {
+    let get = |other: &str| {
+        registry
+            .index(other)
+            .and_then(|index| start.get(index).copied())
+    };
+    let found = start.get(key).copied();
+    matches!(
+        check_value(registry.key(key), found, compare, get, missing),
+        Ok(true)
+    )
+}
+
+/// How far each key can move with a single action, used to estimate how many
+/// actions are left before the start state fulfills a requirement
+struct StepSizes {
+    /// Largest Increment/Decrement of any action, per key
+    largest: HashMap<usize, u64>,
+    /// Keys some action changes in other ways than fixed steps, like `Set`, which might
+    /// fulfill a requirement in one go
+    jumps: HashSet<usize>,
+}
+
+impl StepSizes {
+    fn new(actions: &[CompactAction]) -> Self {
+        let mut largest: HashMap<usize, u64> = HashMap::new();
+        let mut jumps = HashSet::new();
+        for action in actions {
+            for mutator in action.mutators() {
+                if let CompactMutator::Increment(key, value)
+                | CompactMutator::Decrement(key, value) = mutator
+                {
+                    let step = match value {
+                        Datum::I64(v) => v.unsigned_abs(),
+                        Datum::F64(v) => v.abs().ceil() as u64,
+                        _ => 1,
+                    }
+                    .max(1);
+                    let entry = largest.entry(*key).or_insert(step);
+                    *entry = (*entry).max(step);
+                } else {
+                    jumps.insert(mutator.index());
+                }
+            }
+        }
+        Self { largest, jumps }
+    }
+
+    /// Estimates the amount of actions needed to go from `value` to fulfilling `compare`
+    fn actions_needed(
+        &self,
+        key: usize,
+        value: &Datum,
+        compare: &Compare,
+        registry: &KeyRegistry,
+    ) -> Result<usize, PlanError> {
+        let distance = compare
+            .distance(value)
+            .ok_or_else(|| PlanError::mismatch(registry.key(key), value, compare))?
+            .max(1);
+        Ok(match self.largest.get(&key) {
+            Some(step) if !self.jumps.contains(&key) => distance.div_ceil(*step) as usize,
+            // Set can change this key, which fulfills the requirement in one go
+            _ => 1,
+        })
+    }
+}
+
+/// Estimates how many actions are left until the start state fulfills the requirements.
+/// One action can help with several requirements, so this is the most actions any one of
+/// them needs, not the sum, which keeps the estimate from ever being too high
+fn heuristic(
+    requirements: &Requirements,
+    start: &CompactState,
+    steps: &StepSizes,
+    registry: &KeyRegistry,
+    missing: MissingKeyBehavior,
+) -> Result<usize, PlanError> {
+    requirements
+        .iter()
+        .filter(|(key, compare)| !is_satisfied(start, registry, *key, compare, missing))
+        .map(|(key, compare)| match start.get(*key) {
+            Some(value) => match start.resolve(compare, registry) {
+                Ok(compare) => steps.actions_needed(*key, value, &compare, registry),
+                Err(PlanError::MissingKey { .. }) => Ok(1),
+                Err(err) => Err(err),
+            },
+            None => Ok(1), // Penalty for missing keys
+        })
+        .try_fold(0, |most, needed| Ok(needed?.max(most)))
+}
+
+fn is_start(
+    requirements: &Requirements,
+    start: &CompactState,
+    registry: &KeyRegistry,
+    missing: MissingKeyBehavior,
+) -> bool {
+    requirements
+        .iter()
+        .all(|(key, compare)| is_satisfied(start, registry, *key, compare, missing))
+}
+
+/// Searches from the [`Goal`] towards the start [`LocalState`], by "undoing" [`Action`]s and
+/// figuring out what would have to be true before each one, until the start [`LocalState`]
+/// satisfies the regressed requirements.
+///
+/// Returns the path in the same shape as the forward search does, beginning with the start state
+pub fn plan_backwards(
+    start: &LocalState,
+    actions: &[Action],
+    goal: &Goal,
+    budget: &Budget,
+    missing_keys: MissingKeyBehavior,
+    outcomes: OutcomeMode,
+    stats: &mut PlanStats,
+) -> Result<Plan, PlanError> {
+    let registry = KeyRegistry::from_domain(start, actions, goal);
+    let compact_actions = CompactAction::all_outcomes(actions, &registry)?;
+    let compact_start = CompactState::from_local_state(start, &registry)?;
+
+    // Every way of fulfilling the goal conditions is a place to start searching from
+    let compact_goal = CompactGoal::new(goal, &registry)?;
+    let goal_requirements: Vec<Requirements> = compact_goal
+        .alternatives()
+        .iter()
+        .filter_map(|alternative| {
+            let mut requirements: Requirements = compact_goal
+                .requirements()
+                .iter()
+                .map(|(key, compare)| (*key, (*compare).clone()))
+                .collect();
+            requirements.extend(alternative.iter().cloned());
+            simplify(requirements)
+        })
+        .collect();
+    if goal_requirements.is_empty() {
+        return Err(PlanError::NoPlanFound { partial: None });
+    }
+
+    let mut costs = Vec::with_capacity(compact_actions.len());
+    for action in &compact_actions {
+        costs.push(outcomes.cost(&action.action().key, action.cost(), action.probability())?);
+    }
+    let steps = StepSizes::new(&compact_actions);
+    // Every action that's left costs at least this much
+    let cheapest = costs
+        .iter()
+        .flatten()
+        .copied()
+        .reduce(f64::min)
+        .unwrap_or(0.0);
+
+    // Paths found so far lead backwards from the goal, and don't start at our start state, so
+    // there is no partial plan to give back when the search fails
+    let (path, cost) = match astar(
+        &goal_requirements,
+        |requirements, found| {
+            found.extend(successors(
+                requirements,
+                &compact_actions,
+                &costs,
+                &registry,
+            ));
+            Ok(())
+        },
+        |requirements| {
+            heuristic(
+                requirements,
+                &compact_start,
+                &steps,
+                &registry,
+                missing_keys,
+            )
+            .map(|actions| actions as f64 * cheapest)
+        },
+        // There is no partial plan backwards, so we don't need to know what got closest
+        |_| Ok(0.0),
+        |requirements| {
+            Ok(is_start(
+                requirements,
+                &compact_start,
+                &registry,
+                missing_keys,
+            ))
+        },
+        budget,
+        stats,
+    )? {
+        SearchResult::Found(path, cost) => (path, cost),
+        SearchResult::NotFound { .. } => return Err(PlanError::NoPlanFound { partial: None }),
+        SearchResult::BudgetExhausted { expanded_nodes, .. } => {
+            return Err(PlanError::BudgetExhausted {
+                expanded_nodes,
+                partial: None,
+            })
+        }
+    };
+
+    // The path goes from the goal to the start, so we walk it backwards to get the order
+    // to execute things in
+    let mut state = start.clone();
+    let mut steps = vec![];
+    for (_, action) in path.into_iter().skip(1).rev() {
+        let index = action.expect("Only the goal has no action");
+        let action = &compact_actions[index];
+        debug_assert!(
+            matches!(
+                check_preconditions(&state, action.action(), missing_keys),
+                Ok(true)
+            ),
+            "Regressed requirements should make the preconditions hold"
+        );
+
+        let effect = action.effect();
+        for mutator in &effect.mutators {
+            apply_mutator(&mut state.data, mutator, missing_keys)
+                .map_err(|err| err.in_action(&effect.action))?;
+        }
+        steps.push(Effect {
+            state: state.clone(),
+            cost: costs[index].expect("Outcomes that never happen aren't searched"),
+            ..effect.clone()
+        });
+    }
+
+    Ok(Plan {
+        start: start.clone(),
+        steps,
+        cost,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compare::Comparison, condition::Condition, mutator::Mutator};
+
+    /// Like [`regress`], but with String keys to keep the tests readable
+    fn regress_keys(
+        requirements: Vec<(&str, Compare)>,
+        action: &Action,
+    ) -> Option<Vec<(String, Compare)>> {
+        let mut regressed = regress_all_keys(requirements, action);
+        assert!(regressed.len() <= 1, "Expected a single way to regress");
+        regressed.pop()
+    }
+
+    fn regress_all_keys(
+        requirements: Vec<(&str, Compare)>,
+        action: &Action,
+    ) -> Vec<Vec<(String, Compare)>> {
+        let mut registry = KeyRegistry::from_domain(
+            &LocalState::new(),
+            std::slice::from_ref(action),
+            &Goal::new(),
+        );
+        let requirements: Requirements = requirements
+            .into_iter()
+            .map(|(key, compare)| {
+                if let Some(other) = compare.other_key() {
+                    registry.intern(other);
+                }
+                (registry.intern(key), compare)
+            })
+            .collect();
+        let action = CompactAction::outcomes(action, &registry)
+            .unwrap()
+            .remove(0);
+        regress(&requirements, &action, &registry)
+            .into_iter()
+            .map(|regressed| {
+                regressed
+                    .into_iter()
+                    .map(|(key, compare)| (registry.key(key).to_string(), compare))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_regress_any_condition() {
+        let requirements = vec![("wood", Compare::GreaterThanEquals(Datum::I64(1)))];
+        let chop = Action::new("chop")
+            .add_mutator(Mutator::Increment("wood".to_string(), Datum::I64(1)))
+            .with_condition(Condition::any([
+                Condition::compare("has_axe", Compare::Equals(Datum::Bool(true))),
+                Condition::compare("has_sword", Compare::Equals(Datum::Bool(true))),
+            ]));
+
+        assert_eq!(
+            regress_all_keys(requirements, &chop),
+            vec![
+                vec![
+                    ("has_axe".to_string(), Compare::Equals(Datum::Bool(true))),
+                    (
+                        "wood".to_string(),
+                        Compare::GreaterThanEquals(Datum::I64(0))
+                    ),
+                ],
+                vec![
+                    ("has_sword".to_string(), Compare::Equals(Datum::Bool(true))),
+                    (
+                        "wood".to_string(),
+                        Compare::GreaterThanEquals(Datum::I64(0))
+                    ),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_regress_set() {
+        let requirements = vec![("is_hungry", Compare::Equals(Datum::Bool(false)))];
+        let eat = Action::new("eat")
+            .add_mutator(Mutator::Set("is_hungry".to_string(), Datum::Bool(false)))
+            .with_precondition("has_food", Compare::Equals(Datum::Bool(true)));
+
+        assert_eq!(
+            regress_keys(requirements, &eat),
+            Some(vec![(
+                "has_food".to_string(),
+                Compare::Equals(Datum::Bool(true))
+            )])
+        );
+    }
+
+    #[test]
+    fn test_regress_conflicting_set() {
+        let requirements = vec![("is_hungry", Compare::Equals(Datum::Bool(false)))];
+        let run = Action::new("run")
+            .add_mutator(Mutator::Set("is_hungry".to_string(), Datum::Bool(true)));
+
+        assert_eq!(regress_keys(requirements, &run), None);
+    }
+
+    #[test]
+    fn test_regress_irrelevant() {
+        let requirements = vec![("is_hungry", Compare::Equals(Datum::Bool(false)))];
+        let sleep = Action::new("sleep")
+            .add_mutator(Mutator::Set("is_tired".to_string(), Datum::Bool(false)));
+
+        assert_eq!(regress_keys(requirements, &sleep), None);
+    }
+
+    #[test]
+    fn test_regress_increment() {
+        let requirements = vec![("energy", Compare::GreaterThanEquals(Datum::I64(50)))];
+        let eat = Action::new("eat")
+            .add_mutator(Mutator::Increment("energy".to_string(), Datum::I64(10)));
+
+        assert_eq!(
+            regress_keys(requirements, &eat),
+            Some(vec![(
+                "energy".to_string(),
+                Compare::GreaterThanEquals(Datum::I64(40))
+            )])
+        );
+    }
+
+    #[test]
+    fn test_regress_increment_range() {
+        let requirements = vec![("energy", Compare::InRange(Datum::I64(50), Datum::I64(60)))];
+        let eat = Action::new("eat")
+            .add_mutator(Mutator::Increment("energy".to_string(), Datum::I64(10)));
+
+        assert_eq!(
+            regress_keys(requirements, &eat),
+            Some(vec![(
+                "energy".to_string(),
+                Compare::InRange(Datum::I64(40), Datum::I64(50))
+            )])
+        );
+    }
+
+    #[test]
+    fn test_regress_to_key() {
+        let requirements = || {
+            vec![(
+                "gold",
+                Compare::to_key(Comparison::GreaterThanEquals, "price"),
+            )]
+        };
+
+        let work =
+            Action::new("work").add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(2)));
+        assert_eq!(
+            regress_keys(requirements(), &work),
+            Some(vec![(
+                "gold".to_string(),
+                Compare::ToKey(
+                    Comparison::GreaterThanEquals,
+                    KeyOperand::new("price").with_offset(Datum::I64(-2))
+                )
+            )])
+        );
+
+        // Setting the price turns it into a plain requirement on gold
+        let haggle =
+            Action::new("haggle").add_mutator(Mutator::Set("price".to_string(), Datum::I64(1)));
+        assert_eq!(
+            regress_keys(requirements(), &haggle),
+            Some(vec![(
+                "gold".to_string(),
+                Compare::GreaterThanEquals(Datum::I64(1))
+            )])
+        );
+
+        // And setting gold turns it into one on the price
+        let rob = Action::new("rob").add_mutator(Mutator::Set("gold".to_string(), Datum::I64(5)));
+        assert_eq!(
+            regress_keys(requirements(), &rob),
+            Some(vec![(
+                "price".to_string(),
+                Compare::LessThanEquals(Datum::I64(5))
+            )])
+        );
+    }
+
+    #[test]
+    fn test_regress_toggle_and_copy() {
+        let toggle = Action::new("flip").add_mutator(Mutator::Toggle("is_lit".to_string()));
+        assert_eq!(
+            regress_keys(
+                vec![("is_lit", Compare::Equals(Datum::Bool(true)))],
+                &toggle
+            ),
+            Some(vec![(
+                "is_lit".to_string(),
+                Compare::Equals(Datum::Bool(false))
+            )])
+        );
+
+        let requirements = || vec![("gold", Compare::GreaterThanEquals(Datum::I64(10)))];
+        let withdraw = Action::new("withdraw")
+            .add_mutator(Mutator::CopyFrom("gold".to_string(), "savings".to_string()));
+        assert_eq!(
+            regress_keys(requirements(), &withdraw),
+            Some(vec![(
+                "savings".to_string(),
+                Compare::GreaterThanEquals(Datum::I64(10))
+            )])
+        );
+
+        // There's no telling what gold was before doubling it
+        let invest =
+            Action::new("invest").add_mutator(Mutator::Multiply("gold".to_string(), Datum::I64(2)));
+        assert_eq!(regress_keys(requirements(), &invest), None);
+    }
+
+    #[test]
+    fn test_simplify_bounds() {
+        let requirements = vec![
+            (0, Compare::GreaterThanEquals(Datum::I64(25))),
+            (0, Compare::GreaterThanEquals(Datum::I64(50))),
+            (0, Compare::LessThanEquals(Datum::I64(80))),
+        ];
+
+        assert_eq!(
+            simplify(requirements),
+            Some(vec![
+                (0, Compare::GreaterThanEquals(Datum::I64(50))),
+                (0, Compare::LessThanEquals(Datum::I64(80))),
+            ])
+        );
+
+        let impossible = vec![
+            (0, Compare::GreaterThanEquals(Datum::I64(50))),
+            (0, Compare::LessThanEquals(Datum::I64(40))),
+        ];
+        assert_eq!(simplify(impossible), None);
+    }
+
+    #[test]
+    fn test_regress_inconsistent_preconditions() {
+        let requirements = vec![
+            ("gold", Compare::Equals(Datum::I64(1))),
+            ("is_tired", Compare::Equals(Datum::Bool(false))),
+        ];
+        let rob = Action::new("rob")
+            .add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(1)))
+            .with_precondition("is_tired", Compare::Equals(Datum::Bool(true)));
+
+        assert_eq!(regress_keys(requirements, &rob), None);
+    }
+}
@@ -0,0 +1,256 @@
+// crate "dogoap" file search.rs
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::hash::{BuildHasherDefault, Hash};
+
+use indexmap::map::Entry::{Occupied, Vacant};
+use indexmap::IndexMap;
+use rustc_hash::FxHasher;
+use web_time::{Duration, Instant};
+
+use crate::error::PlanError;
+
+type FxIndexMap<K, V> = IndexMap<K, V, BuildHasherDefault<FxHasher>>;
+
+/// Most searches only see a few dozen nodes, so the search starts out with room for that
+/// many instead of growing its maps over and over
+const INITIAL_CAPACITY: usize = 64;
+
+/// Entry in the open set, ordered so the [`BinaryHeap`] pops the lowest estimated
+/// cost first, preferring the one we've come furthest with when tied. Costs are compared
+/// with [`f64::total_cmp`], so a NaN estimate can't break the ordering of the heap and
+/// just ends up last
+struct SmallestCostHolder {
+    estimated_cost: f64,
+    cost: f64,
+    index: usize,
+}
+
+impl PartialEq for SmallestCostHolder {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SmallestCostHolder {}
+
+impl PartialOrd for SmallestCostHolder {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallestCostHolder {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match other.estimated_cost.total_cmp(&self.estimated_cost) {
+            Ordering::Equal => self.cost.total_cmp(&other.cost),
+            s => s,
+        }
+    }
+}
+
+/// How much work a search is allowed to do before giving up with [`PlanError::BudgetExhausted`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Budget {
+    pub max_expanded_nodes: Option<usize>,
+    pub deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub(crate) fn is_exhausted(&self, expanded_nodes: usize) -> bool {
+        if let Some(max) = self.max_expanded_nodes {
+            if expanded_nodes >= max {
+                return true;
+            }
+        }
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+}
+
+/// Numbers about how much work the planner did, see
+/// [`make_plan_with_stats`](crate::planner::make_plan_with_stats)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlanStats {
+    /// States whose successors were looked at
+    pub expanded_nodes: usize,
+    /// Successor states produced by expanding, including the ones already seen before
+    pub generated_nodes: usize,
+    /// The most states that were waiting to be expanded at the same time
+    pub peak_open_set: usize,
+    /// Generated states that were skipped, as they had already been reached at least as cheaply
+    pub duplicates_pruned: usize,
+    /// Time spent planning in total
+    pub elapsed: Duration,
+    /// [`PlanningStrategy::GoalToStart`](crate::planner::PlanningStrategy::GoalToStart) was
+    /// asked for, but couldn't be used, so the planner searched from the start instead
+    pub searched_forward_instead: bool,
+}
+
+/// The nodes from the start of a search to some node, each with the edge (like the
+/// index of an [`Action`](crate::action::Action)) that lead to it. The start has no edge
+pub type Path<N, E> = Vec<(N, Option<E>)>;
+
+/// How a search ended, when none of the callbacks failed
+#[derive(Debug)]
+pub enum SearchResult<N, E> {
+    /// Path from the start to the first node where `success` returned `true`, with the total cost
+    Found(Path<N, E>, f64),
+    /// Every reachable node was visited. `closest` is the path to the node with the lowest distance
+    NotFound { closest: (Path<N, E>, f64) },
+    /// The [`Budget`] ran out. `closest` is the path to the node with the lowest distance so far
+    BudgetExhausted {
+        expanded_nodes: usize,
+        closest: (Path<N, E>, f64),
+    },
+}
+
+/// How we got to a node, and the lowest cost so far of getting there
+struct Parent<E> {
+    index: usize,
+    edge: Option<E>,
+    cost: f64,
+}
+
+/// A* search where every callback can fail, stopping the search with that error.
+/// The search begins from all of `starts` at once, which must not be empty.
+/// `successors` adds the nodes reachable from a node to the list it's given, with the edge
+/// that leads to each and the cost of taking it, which must not be negative. The list is
+/// reused for every node, so it doesn't need to allocate every time.
+///
+/// Keeps track of the node with the lowest `distance` seen, so callers can make use of how
+/// far the search got even when it didn't succeed. That is measured apart from `heuristic`,
+/// as a heuristic estimating zero everywhere would never let us get any closer. Counts the
+/// work done in `stats`, except for [`PlanStats::elapsed`]
+#[allow(clippy::too_many_arguments)]
+pub fn astar<N, E, FN, FH, FD, FS>(
+    starts: &[N],
+    mut successors: FN,
+    mut heuristic: FH,
+    mut distance: FD,
+    mut success: FS,
+    budget: &Budget,
+    stats: &mut PlanStats,
+) -> Result<SearchResult<N, E>, PlanError>
+where
+    N: Eq + Hash + Clone,
+    E: Clone,
+    FN: FnMut(&N, &mut Vec<(N, E, f64)>) -> Result<(), PlanError>,
+    FH: FnMut(&N) -> Result<f64, PlanError>,
+    FD: FnMut(&N) -> Result<f64, PlanError>,
+    FS: FnMut(&N) -> Result<bool, PlanError>,
+{
+    debug_assert!(!starts.is_empty(), "Search needs somewhere to start");
+    let mut to_see = BinaryHeap::with_capacity(INITIAL_CAPACITY);
+    // Every node we've seen, with how we reached it
+    let mut parents: FxIndexMap<N, Parent<E>> =
+        FxIndexMap::with_capacity_and_hasher(INITIAL_CAPACITY, Default::default());
+    // Index and distance of the node closest to success
+    let mut closest = (0, f64::INFINITY);
+    let mut successors_found = vec![];
+
+    for start in starts {
+        let (index, _) = parents.insert_full(
+            start.clone(),
+            Parent {
+                index: usize::MAX,
+                edge: None,
+                cost: 0.0,
+            },
+        );
+        let h = heuristic(start)?;
+        let d = distance(start)?;
+        if d < closest.1 {
+            closest = (index, d);
+        }
+        to_see.push(SmallestCostHolder {
+            estimated_cost: h,
+            cost: 0.0,
+            index,
+        });
+    }
+    stats.peak_open_set = stats.peak_open_set.max(to_see.len());
+
+    while let Some(SmallestCostHolder { cost, index, .. }) = to_see.pop() {
+        {
+            let (node, parent) = parents.get_index(index).unwrap();
+            if success(node)? {
+                return Ok(SearchResult::Found(reverse_path(&parents, index), cost));
+            }
+            // We might have pushed the same node more than once if we found a cheaper
+            // way to it, so skip the outdated ones
+            if cost > parent.cost {
+                continue;
+            }
+            if budget.is_exhausted(stats.expanded_nodes) {
+                return Ok(SearchResult::BudgetExhausted {
+                    expanded_nodes: stats.expanded_nodes,
+                    closest: (reverse_path(&parents, closest.0), parents[closest.0].cost),
+                });
+            }
+            stats.expanded_nodes += 1;
+            successors(node, &mut successors_found)?;
+        }
+
+        for (successor, edge, move_cost) in successors_found.drain(..) {
+            stats.generated_nodes += 1;
+            let new_cost = cost + move_cost;
+            let parent = Parent {
+                index,
+                edge: Some(edge),
+                cost: new_cost,
+            };
+            let h;
+            let d;
+            let n;
+            match parents.entry(successor) {
+                Vacant(e) => {
+                    h = heuristic(e.key())?;
+                    d = distance(e.key())?;
+                    n = e.index();
+                    e.insert(parent);
+                }
+                Occupied(mut e) => {
+                    if e.get().cost > new_cost {
+                        h = heuristic(e.key())?;
+                        d = distance(e.key())?;
+                        n = e.index();
+                        e.insert(parent);
+                    } else {
+                        stats.duplicates_pruned += 1;
+                        continue;
+                    }
+                }
+            }
+
+            if d < closest.1 {
+                closest = (n, d);
+            }
+            to_see.push(SmallestCostHolder {
+                estimated_cost: new_cost + h,
+                cost: new_cost,
+                index: n,
+            });
+        }
+        stats.peak_open_set = stats.peak_open_set.max(to_see.len());
+    }
+
+    Ok(SearchResult::NotFound {
+        closest: (reverse_path(&parents, closest.0), parents[closest.0].cost),
+    })
+}
+
+fn reverse_path<N: Clone, E: Clone>(
+    parents: &FxIndexMap<N, Parent<E>>,
+    mut index: usize,
+) -> Path<N, E> {
+    let mut path = vec![];
+    while let Some((node, parent)) = parents.get_index(index) {
+        path.push((node.clone(), parent.edge.clone()));
+        index = parent.index;
+    }
+    path.reverse();
+    path
+}
@@ -19,12 +19,17 @@ where
 
     Action {
         key: name.to_string(),
+        parameters: vec![],
         preconditions: vec![],
+        conditions: vec![],
         effects: vec![Effect {
             action: name.to_string(),
             mutators,
             state: LocalState::new(),
-            cost: 1,
+            cost: 1.0,
+            conditional_costs: vec![],
+            probability: None,
+            arguments: vec![],
         }],
     }
 }
@@ -41,7 +46,10 @@ where
             from_value.into(),
         )],
         state: LocalState::new(),
-        cost: 1,
+        cost: 1.0,
+        conditional_costs: vec![],
+        probability: None,
+        arguments: vec![],
     }];
     action
 }
@@ -58,7 +66,10 @@ where
             from_value.into(),
         )],
         state: LocalState::new(),
-        cost: 1,
+        cost: 1.0,
+        conditional_costs: vec![],
+        probability: None,
+        arguments: vec![],
     }];
     action
 }
@@ -0,0 +1,113 @@
+// crate "dogoap" file symbol.rs
+use std::fmt::Display;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use bevy_reflect::{prelude::ReflectDefault, Reflect};
+use rustc_hash::FxHashMap;
+
+/// A name like `iron_ore`, interned so it can be used in a [`Datum`](crate::datum::Datum)
+/// while still being as cheap to copy, hash and compare as a number. The same name always
+/// gives the same symbol.
+///
+/// Symbols are only equal or not, there is no order between them. The number is only
+/// meaningful within the running program, so it's reflected as a value that shows its name
+/// instead, and the name is what should be stored.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[reflect_value(Debug, PartialEq, Hash, Default)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct SymbolTable {
+    names: Vec<Arc<str>>,
+    ids: FxHashMap<Arc<str>, u32>,
+}
+
+fn symbols() -> &'static RwLock<SymbolTable> {
+    static SYMBOLS: OnceLock<RwLock<SymbolTable>> = OnceLock::new();
+    SYMBOLS.get_or_init(|| {
+        let mut table = SymbolTable::default();
+        // So the default symbol is the empty name
+        let empty: Arc<str> = Arc::from("");
+        table.names.push(empty.clone());
+        table.ids.insert(empty, 0);
+        RwLock::new(table)
+    })
+}
+
+impl Symbol {
+    /// The symbol for `name`, adding it to the symbol table if it's new. Names are never
+    /// removed from the table again
+    pub fn new(name: &str) -> Self {
+        if let Some(symbol) = Self::get(name) {
+            return symbol;
+        }
+        let mut table = symbols().write().unwrap_or_else(|err| err.into_inner());
+        // Someone else might have added it in the meantime
+        if let Some(id) = table.ids.get(name) {
+            return Symbol(*id);
+        }
+        let id = u32::try_from(table.names.len()).expect("Too many symbols");
+        let name: Arc<str> = Arc::from(name);
+        table.names.push(name.clone());
+        table.ids.insert(name, id);
+        Symbol(id)
+    }
+
+    /// The symbol for `name`, if it was added already
+    pub fn get(name: &str) -> Option<Self> {
+        let table = symbols().read().unwrap_or_else(|err| err.into_inner());
+        table.ids.get(name).map(|id| Symbol(*id))
+    }
+
+    /// Looks the name up in the symbol table
+    pub fn name(&self) -> Arc<str> {
+        let table = symbols().read().unwrap_or_else(|err| err.into_inner());
+        table.names[self.0 as usize].clone()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Symbol({:?})", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        let iron = Symbol::new("iron_ore");
+        assert_eq!(iron, Symbol::new("iron_ore"));
+        assert_eq!(Some(iron), Symbol::get("iron_ore"));
+        assert_ne!(iron, Symbol::new("gold_ore"));
+        assert_eq!("iron_ore", &*iron.name());
+        assert_eq!("iron_ore", iron.to_string());
+        assert_eq!("Symbol(\"iron_ore\")", format!("{:?}", iron));
+        assert_eq!("", &*Symbol::default().name());
+        assert_eq!(None, Symbol::get("never_interned"));
+    }
+
+    #[test]
+    fn test_reflect_name() {
+        let iron = Symbol::new("iron_ore");
+        assert_eq!("Symbol(\"iron_ore\")", format!("{:?}", iron.as_reflect()));
+        assert_eq!(
+            Some(true),
+            iron.reflect_partial_eq(&Symbol::new("iron_ore"))
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use dogoap::{
     prelude::*,
     simple::{
@@ -18,18 +20,23 @@ fn test_basic_bool_setting() {
         action: "eat".to_string(),
         mutators: vec![eat_mutator.clone()],
         state: LocalState::new(),
-        cost: 1,
+        cost: 1.0,
+        conditional_costs: vec![],
+        probability: None,
+        arguments: vec![],
     };
 
     let eat_action = Action {
         key: "eat".to_string(),
+        parameters: vec![],
         preconditions: vec![],
+        conditions: vec![],
         effects: vec![eat_consequence],
     };
 
     let actions: Vec<Action> = vec![eat_action];
 
-    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap().0);
+    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap());
     assert_eq!(1, plan.len());
 
     let cons = plan.get(0).unwrap();
@@ -54,23 +61,28 @@ fn test_no_actions_needed() {
         action: "eat".to_string(),
         mutators: vec![eat_mutator.clone()],
         state: LocalState::new(),
-        cost: 1,
+        cost: 1.0,
+        conditional_costs: vec![],
+        probability: None,
+        arguments: vec![],
     };
 
     let eat_action = Action {
         key: "eat".to_string(),
+        parameters: vec![],
         preconditions: vec![],
+        conditions: vec![],
         effects: vec![eat_consequence],
     };
 
     let actions: Vec<Action> = vec![eat_action];
 
-    let (plan, plan_cost) = make_plan(&start, &actions[..], &goal).unwrap();
-    assert_eq!(1, plan.len());
-    assert_eq!(0, plan_cost);
+    let plan = make_plan(&start, &actions[..], &goal).unwrap();
+    assert!(plan.steps.is_empty());
+    assert_eq!(0.0, plan.cost);
 
     let expected_state = LocalState::new().with_datum("is_hungry", Datum::Bool(false));
-    assert_eq!(expected_state, plan.first().unwrap().state().clone());
+    assert_eq!(&expected_state, plan.final_state());
 }
 
 // Shorthand for one action that sets one field
@@ -86,7 +98,7 @@ fn test_simple_action() {
 
     let actions: Vec<Action> = vec![eat_action];
 
-    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap().0);
+    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap());
     assert_eq!(1, plan.len());
 
     let cons = plan.get(0).unwrap();
@@ -118,7 +130,7 @@ fn test_two_bools() {
 
     let plan = make_plan(&start, &actions[..], &goal).unwrap();
 
-    let cons = get_effects_from_plan(plan.0);
+    let cons = get_effects_from_plan(plan);
     assert_eq!(2, cons.len());
 
     let first_cons = cons.get(0).unwrap();
@@ -182,7 +194,7 @@ fn test_four_bools() {
 
     let plan = make_plan(&start, &actions[..], &goal).unwrap();
 
-    let cons = get_effects_from_plan(plan.0);
+    let cons = get_effects_from_plan(plan);
     assert_eq!(4, cons.len());
 
     let first_cons = cons.get(0).unwrap();
@@ -218,10 +230,11 @@ enum TestLocation {
 
 #[test]
 fn test_enums() {
-    let loc_house = Datum::Enum(TestLocation::House as usize);
-    let loc_outside = Datum::Enum(TestLocation::Outside as usize);
-    let loc_market = Datum::Enum(TestLocation::Market as usize);
-    let loc_ramen = Datum::Enum(TestLocation::RamenShop as usize);
+    let location = |loc: TestLocation| Datum::Enum(EnumValue::new("TestLocation", loc as usize));
+    let loc_house = location(TestLocation::House);
+    let loc_outside = location(TestLocation::Outside);
+    let loc_market = location(TestLocation::Market);
+    let loc_ramen = location(TestLocation::RamenShop);
 
     let start = LocalState::new().with_datum("at_location", loc_house.clone());
 
@@ -241,7 +254,7 @@ fn test_enums() {
     let actions: Vec<Action> = vec![go_outside_action, go_to_market_action, go_to_ramen_action];
 
     let plan = make_plan(&start, &actions[..], &goal);
-    let effects = get_effects_from_plan(plan.unwrap().0);
+    let effects = get_effects_from_plan(plan.unwrap());
 
     assert_eq!(3, effects.len());
 
@@ -289,7 +302,7 @@ fn test_preconditions() {
 
     let actions: Vec<Action> = vec![eat_action, sleep_action];
 
-    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap().0);
+    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap());
     assert_eq!(3, plan.len());
 
     let first_cons = plan.get(0).unwrap();
@@ -324,7 +337,7 @@ fn test_int_increment() {
 
     let actions: Vec<Action> = vec![eat_action];
 
-    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap().0);
+    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap());
     assert_eq!(5, plan.len());
 
     for cons in &plan {
@@ -348,7 +361,7 @@ fn test_int_decrement() {
 
     let actions: Vec<Action> = vec![eat_action];
 
-    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap().0);
+    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap());
     assert_eq!(7, plan.len());
 
     for cons in &plan {
@@ -372,7 +385,7 @@ fn test_float_increment() {
 
     let actions: Vec<Action> = vec![eat_action];
 
-    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap().0);
+    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap());
     assert_eq!(5, plan.len());
 
     for cons in &plan {
@@ -397,7 +410,7 @@ fn test_greater_than_equals() {
     let actions: Vec<Action> = vec![eat_action];
 
     let plan = make_plan(&start, &actions[..], &goal).unwrap();
-    let effects = get_effects_from_plan(plan.0.clone());
+    let effects = get_effects_from_plan(plan.clone());
 
     assert_eq!(9, effects.len());
 
@@ -440,23 +453,24 @@ fn test_long_plan() {
                 Mutator::Increment("hunger".to_string(), Datum::I64(20)),
             ],
             state: LocalState::default(),
-            cost: 1,
+            cost: 1.0,
+            conditional_costs: vec![],
+            probability: None,
+            arguments: vec![],
         })
         .with_precondition("hunger", Compare::LessThanEquals(Datum::I64(50)))
         .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(50)));
 
     let actions: Vec<Action> = vec![sleep_action, eat_action, rob_people];
 
-    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap().0);
+    let plan = get_effects_from_plan(make_plan(&start, &actions[..], &goal).unwrap());
 
     assert_eq!(50, plan.len());
 
     assert_eq!(expected_state, plan.last().unwrap().state);
 }
 
-// TODO haven't implemented `PlanningStrategy::GoalToStart` yet
 #[test]
-#[should_panic]
 fn test_reverse_strategy() {
     let start = LocalState::new().with_datum("is_hungry", Datum::Bool(true));
     let expected_state = LocalState::new().with_datum("is_hungry", Datum::Bool(false));
@@ -470,8 +484,7 @@ fn test_reverse_strategy() {
 
     let plan = get_effects_from_plan(
         make_plan_with_strategy(PlanningStrategy::GoalToStart, &start, &actions[..], &goal)
-            .unwrap()
-            .0,
+            .unwrap(),
     );
     assert_eq!(1, plan.len());
 
@@ -482,6 +495,171 @@ fn test_reverse_strategy() {
     assert_eq!(expected_state, cons.state);
 }
 
+#[test]
+fn test_reverse_strategy_fallback() {
+    let start = LocalState::new().with_datum("gold", Datum::I64(1));
+    let goal = Goal::new().with_req("gold", Compare::GreaterThanEquals(Datum::I64(4)));
+    let work = simple_increment_action("work", "gold", Datum::I64(1));
+    let options = PlanOptions::new().with_strategy(PlanningStrategy::GoalToStart);
+
+    let (plan, stats) = make_plan_with_stats(&options, &start, &[work.clone()], &goal);
+    assert_eq!(3, plan.unwrap().steps.len());
+    assert!(!stats.searched_forward_instead);
+
+    // Doubling can't be undone, so the planner has to search from the start instead
+    let double =
+        Action::new("double").add_mutator(Mutator::Multiply("gold".to_string(), Datum::I64(2)));
+    let (plan, stats) = make_plan_with_stats(&options, &start, &[work, double], &goal);
+    assert_eq!(2, plan.unwrap().steps.len());
+    assert!(stats.searched_forward_instead);
+}
+
+// Working backwards needs to regress through preconditions of each action
+#[test]
+fn test_reverse_strategy_preconditions() {
+    let start = LocalState::new()
+        .with_datum("is_hungry", Datum::Bool(true))
+        .with_datum("is_tired", Datum::Bool(true));
+
+    let expected_state = LocalState::new()
+        .with_datum("is_hungry", Datum::Bool(false))
+        .with_datum("is_tired", Datum::Bool(false));
+
+    let goal = Goal::new()
+        .with_req("is_hungry", Compare::Equals(Datum::Bool(false)))
+        .with_req("is_tired", Compare::Equals(Datum::Bool(false)));
+
+    let eat_action = simple_multi_mutate_action(
+        "eat",
+        vec![
+            ("is_hungry", Datum::Bool(false)),
+            ("is_tired", Datum::Bool(true)),
+        ],
+    )
+    .add_precondition(("is_tired".to_string(), Compare::Equals(Datum::Bool(false))));
+
+    let sleep_action = simple_action("sleep", "is_tired", Datum::Bool(false));
+
+    let actions: Vec<Action> = vec![eat_action, sleep_action];
+
+    let plan = make_plan_with_strategy(PlanningStrategy::GoalToStart, &start, &actions[..], &goal)
+        .unwrap();
+    assert_eq!(3, plan.steps.len());
+    assert_eq!(3.0, plan.cost);
+
+    let actions_taken: Vec<&str> = plan.steps.iter().map(|e| e.action.as_str()).collect();
+    assert_eq!(vec!["sleep", "eat", "sleep"], actions_taken);
+
+    assert_eq!(&expected_state, plan.final_state());
+}
+
+// Increments get regressed by shifting the requirement instead of discharging it
+#[test]
+fn test_reverse_strategy_increment() {
+    let start = LocalState::new().with_datum("energy", Datum::I64(0));
+    let expected_state = LocalState::new().with_datum("energy", Datum::I64(54));
+
+    let goal = Goal::new().with_req("energy", Compare::GreaterThanEquals(Datum::I64(50)));
+
+    let eat_action = simple_increment_action("eat", "energy", Datum::I64(6));
+
+    let actions: Vec<Action> = vec![eat_action];
+
+    let plan = make_plan_with_strategy(PlanningStrategy::GoalToStart, &start, &actions[..], &goal)
+        .unwrap();
+    let effects = plan.steps;
+
+    assert_eq!(9, effects.len());
+    assert_eq!(9.0, plan.cost);
+    assert_eq!(expected_state, effects.last().unwrap().state);
+}
+
+// Both strategies should agree on the cost of a plan where every step matters
+#[test]
+fn test_reverse_strategy_long_plan() {
+    let start = LocalState::new()
+        .with_datum("energy", Datum::I64(30))
+        .with_datum("hunger", Datum::I64(70))
+        .with_datum("gold", Datum::I64(0));
+
+    let expected_state = LocalState::new()
+        .with_datum("energy", Datum::I64(50))
+        .with_datum("hunger", Datum::I64(50))
+        .with_datum("gold", Datum::I64(10));
+
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+    let sleep_action = simple_increment_action("sleep", "energy", Datum::I64(1));
+
+    let eat_action = simple_decrement_action("eat", "hunger", Datum::I64(1))
+        .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(50)));
+
+    let rob_people = simple_increment_action("rob", "gold", Datum::I64(1))
+        .with_precondition("hunger", Compare::LessThanEquals(Datum::I64(50)))
+        .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(50)));
+
+    let actions: Vec<Action> = vec![sleep_action, eat_action, rob_people];
+
+    let forward = make_plan(&start, &actions[..], &goal).unwrap();
+    let backward =
+        make_plan_with_strategy(PlanningStrategy::GoalToStart, &start, &actions[..], &goal)
+            .unwrap();
+
+    assert_eq!(forward.cost, backward.cost);
+    assert_eq!(forward.steps.len(), backward.steps.len());
+    assert_eq!(expected_state, backward.steps.last().unwrap().state);
+}
+
+// One action fulfills several requirements at once, so counting the actions each
+// requirement needs and summing them up would steer the search towards the plan that
+// fulfills them one by one, even though that costs more
+#[test]
+fn test_reverse_strategy_one_action_for_several_requirements() {
+    let start = LocalState::new()
+        .with_datum("has_axe", Datum::Bool(false))
+        .with_datum("has_saw", Datum::Bool(false))
+        .with_datum("has_hammer", Datum::Bool(false))
+        .with_datum("at_shop", Datum::Bool(false))
+        .with_datum("has_money", Datum::Bool(false));
+
+    let goal = Goal::new()
+        .with_req("has_axe", Compare::Equals(Datum::Bool(true)))
+        .with_req("has_saw", Compare::Equals(Datum::Bool(true)))
+        .with_req("has_hammer", Compare::Equals(Datum::Bool(true)));
+
+    let buy_toolbox = simple_multi_mutate_action(
+        "buy_toolbox",
+        vec![
+            ("has_axe", Datum::Bool(true)),
+            ("has_saw", Datum::Bool(true)),
+            ("has_hammer", Datum::Bool(true)),
+        ],
+    )
+    .with_precondition("at_shop", Compare::Equals(Datum::Bool(true)))
+    .with_precondition("has_money", Compare::Equals(Datum::Bool(true)));
+    let go_to_shop = simple_multi_mutate_action(
+        "go_to_shop",
+        vec![
+            ("at_shop", Datum::Bool(true)),
+            ("has_money", Datum::Bool(true)),
+        ],
+    );
+    let actions: Vec<Action> = vec![
+        buy_toolbox,
+        go_to_shop,
+        simple_action("make_axe", "has_axe", Datum::Bool(true)).set_cost(0.9),
+        simple_action("make_saw", "has_saw", Datum::Bool(true)).set_cost(0.9),
+        simple_action("make_hammer", "has_hammer", Datum::Bool(true)).set_cost(0.9),
+    ];
+
+    let plan = make_plan_with_strategy(PlanningStrategy::GoalToStart, &start, &actions[..], &goal)
+        .unwrap();
+
+    assert_eq!(2.0, plan.cost);
+    let actions_taken: Vec<&str> = plan.steps.iter().map(|e| e.action.as_str()).collect();
+    assert_eq!(vec!["go_to_shop", "buy_toolbox"], actions_taken);
+}
+
 #[test]
 fn test_prefer_lower_cost_plan() {
     // Planner should prefer cheaper plans based on cost
@@ -498,16 +676,16 @@ fn test_prefer_lower_cost_plan() {
 
     let cheap_action = Action::new("cheap_action")
         .add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(1)))
-        .set_cost(1); // Cost/gold is lower than expensive_action
+        .set_cost(1.0); // Cost/gold is lower than expensive_action
 
     let expensive_action = Action::new("expensive_action")
         .add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(3)))
-        .set_cost(4); // Cost/gold is higher than cheap_action
+        .set_cost(4.0); // Cost/gold is higher than cheap_action
 
     let actions = vec![cheap_action, expensive_action];
 
     let plan = make_plan(&start, &actions[..], &goal).unwrap();
-    let effects = get_effects_from_plan(plan.0.clone());
+    let effects = get_effects_from_plan(plan.clone());
 
     println!("Found plan:");
     println!("{:#?}", plan);
@@ -518,3 +696,1086 @@ fn test_prefer_lower_cost_plan() {
     }
     assert_eq!(expected_state, effects.last().unwrap().state);
 }
+
+#[test]
+fn test_admissible_heuristic_finds_cheapest_plan() {
+    // Big action adds 10 gold and costs 3, small action adds 5 gold and costs 1
+    //
+    // Going by the distance, the big action looks like the obvious choice, but using the
+    // small action twice is cheaper
+    let start = LocalState::new().with_datum("gold", Datum::I64(0));
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+    let actions = [
+        Action::new("big_action")
+            .add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(10)))
+            .set_cost(3.0),
+        Action::new("small_action")
+            .add_mutator(Mutator::Increment("gold".to_string(), Datum::I64(5)))
+            .set_cost(1.0),
+    ];
+
+    let plan = make_plan(&start, &actions[..], &goal).unwrap();
+    assert_eq!(3.0, plan.cost);
+
+    for options in [
+        PlanOptions::new().with_heuristic(ZeroHeuristic),
+        PlanOptions::new().with_heuristic(AdmissibleHeuristic::new(&actions)),
+    ] {
+        let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+        assert_eq!(2.0, plan.cost);
+        let effects = get_effects_from_plan(plan);
+        assert_eq!(2, effects.len());
+        for effect in &effects {
+            assert_eq!("small_action", effect.action);
+        }
+    }
+}
+
+#[test]
+fn test_custom_heuristic() {
+    struct HungerHeuristic;
+
+    impl Heuristic for HungerHeuristic {
+        fn estimate(&self, state: &SearchState, _goal: &Goal) -> Result<f64, PlanError> {
+            match state.get("is_hungry") {
+                Some(Datum::Bool(true)) => Ok(1.0),
+                _ => Ok(0.0),
+            }
+        }
+    }
+
+    let start = LocalState::new().with_datum("is_hungry", Datum::Bool(true));
+    let goal = Goal::new().with_req("is_hungry", Compare::Equals(Datum::Bool(false)));
+    let actions = [simple_action("eat", "is_hungry", Datum::Bool(false))];
+
+    let options = PlanOptions::new().with_heuristic(HungerHeuristic);
+    let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+    let effects = get_effects_from_plan(plan);
+    assert_eq!(1, effects.len());
+    assert_eq!("eat", effects[0].action);
+}
+
+#[test]
+fn test_strict_and_set_comparisons() {
+    let start = LocalState::new()
+        .with_datum("gold", Datum::I64(0))
+        .with_datum("location", Datum::Enum(0.into()));
+    let goal = Goal::new()
+        .with_req("gold", Compare::GreaterThan(Datum::I64(2)))
+        .with_req(
+            "location",
+            Compare::OneOf(vec![Datum::Enum(2.into()), Datum::Enum(3.into())]),
+        );
+
+    let actions = [
+        simple_increment_action("mine", "gold", Datum::I64(1)).with_precondition(
+            "location",
+            Compare::InRange(Datum::Enum(1.into()), Datum::Enum(2.into())),
+        ),
+        simple_action("go_to_mine", "location", Datum::Enum(1.into())),
+        simple_action("go_to_shop", "location", Datum::Enum(3.into()))
+            .with_precondition("gold", Compare::LessThan(Datum::I64(10))),
+    ];
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let plan = make_plan_with_strategy(strategy, &start, &actions[..], &goal).unwrap();
+        let effects = get_effects_from_plan(plan);
+        let actions: Vec<&str> = effects.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(
+            vec!["go_to_mine", "mine", "mine", "mine", "go_to_shop"],
+            actions
+        );
+    }
+}
+
+#[test]
+fn test_conditions() {
+    let start = LocalState::new()
+        .with_datum("has_axe", Datum::Bool(false))
+        .with_datum("has_sword", Datum::Bool(false))
+        .with_datum("at_home", Datum::Bool(false))
+        .with_datum("energy", Datum::I64(10))
+        .with_datum("wood", Datum::I64(0));
+
+    // Energy needs to be at least 20, unless we're at home
+    let goal = Goal::new()
+        .with_req("wood", Compare::GreaterThanEquals(Datum::I64(1)))
+        .with_condition(Condition::any([
+            Condition::compare("at_home", Compare::Equals(Datum::Bool(true))),
+            Condition::compare("energy", Compare::GreaterThanEquals(Datum::I64(20))),
+        ]));
+
+    let actions = [
+        simple_action("buy_sword", "has_sword", Datum::Bool(true)),
+        simple_increment_action("chop", "wood", Datum::I64(1)).with_condition(Condition::all([
+            Condition::any([
+                Condition::compare("has_axe", Compare::Equals(Datum::Bool(true))),
+                Condition::compare("has_sword", Compare::Equals(Datum::Bool(true))),
+            ]),
+            Condition::not(Condition::compare(
+                "energy",
+                Compare::LessThan(Datum::I64(5)),
+            )),
+        ])),
+        simple_action("go_home", "at_home", Datum::Bool(true)).set_cost(5.0),
+        simple_increment_action("rest", "energy", Datum::I64(10)),
+    ];
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let plan = make_plan_with_strategy(strategy, &start, &actions[..], &goal).unwrap();
+        let effects = plan.steps;
+        let mut actions: Vec<&str> = effects.iter().map(|e| e.action.as_str()).collect();
+        actions.sort();
+        assert_eq!(3.0, plan.cost);
+        assert_eq!(vec!["buy_sword", "chop", "rest"], actions);
+    }
+
+    // Chopping is impossible without any weapon
+    let actions = &actions[1..3];
+    assert!(matches!(
+        make_plan(&start, actions, &goal),
+        Err(PlanError::NoPlanFound { .. })
+    ));
+}
+
+#[test]
+fn test_no_plan_found() {
+    let start = LocalState::new().with_datum("is_hungry", Datum::Bool(true));
+    let goal = Goal::new().with_req("is_hungry", Compare::Equals(Datum::Bool(false)));
+
+    let actions = [simple_action("sleep", "is_hungry", Datum::Bool(true))];
+
+    assert_eq!(
+        Err(PlanError::NoPlanFound { partial: None }),
+        make_plan(&start, &actions[..], &goal)
+    );
+    assert_eq!(
+        Err(PlanError::NoPlanFound { partial: None }),
+        make_plan_with_strategy(PlanningStrategy::GoalToStart, &start, &actions[..], &goal)
+    );
+}
+
+#[test]
+fn test_missing_key_error() {
+    // Goal refers to a key we don't have in our state
+    let start = LocalState::new().with_datum("is_hungry", Datum::Bool(true));
+    let goal = Goal::new().with_req("is_tired", Compare::Equals(Datum::Bool(false)));
+
+    let actions = [simple_action("eat", "is_hungry", Datum::Bool(false))];
+
+    assert_eq!(
+        Err(PlanError::MissingKey {
+            key: "is_tired".to_string(),
+            action: None
+        }),
+        make_plan(&start, &actions[..], &goal)
+    );
+
+    // Action refers to a key we don't have in our state
+    let goal = Goal::new().with_req("is_hungry", Compare::Equals(Datum::Bool(false)));
+    let actions = [simple_action("eat", "is_hungry", Datum::Bool(false))
+        .with_precondition("has_food", Compare::Equals(Datum::Bool(true)))];
+
+    assert_eq!(
+        Err(PlanError::MissingKey {
+            key: "has_food".to_string(),
+            action: Some("eat".to_string())
+        }),
+        make_plan(&start, &actions[..], &goal)
+    );
+}
+
+#[test]
+fn test_type_mismatch_error() {
+    let start = LocalState::new().with_datum("gold", Datum::I64(0));
+    let goal = Goal::new().with_req("gold", Compare::GreaterThanEquals(Datum::I64(3)));
+
+    let actions = [
+        Action::new("mine").add_mutator(Mutator::Increment("gold".to_string(), Datum::F64(1.0)))
+    ];
+
+    let err = make_plan(&start, &actions[..], &goal).unwrap_err();
+    assert_eq!(
+        PlanError::TypeMismatch {
+            key: "gold".to_string(),
+            found: Datum::I64(0),
+            other: Datum::F64(1.0),
+            action: Some("mine".to_string())
+        },
+        err
+    );
+    assert_eq!(
+        "Type mismatch for key \"gold\", found Datum:I64(0) in LocalState but it was used with Datum:F64(1) by action \"mine\"",
+        err.to_string()
+    );
+}
+
+#[test]
+fn test_overflow_error() {
+    let start = LocalState::new().with_datum("gold", Datum::I64(i64::MAX - 1));
+    let goal = Goal::new().with_req("gold", Compare::LessThan(Datum::I64(0)));
+
+    let actions = [simple_increment_action("mine", "gold", Datum::I64(1))];
+
+    // The first increment fits, the second one overflows instead of panicking
+    let err = make_plan(&start, &actions[..], &goal).unwrap_err();
+    assert_eq!(
+        PlanError::TypeMismatch {
+            key: "gold".to_string(),
+            found: Datum::I64(i64::MAX),
+            other: Datum::I64(1),
+            action: Some("mine".to_string())
+        },
+        err
+    );
+}
+
+#[test]
+fn test_enum_type_mismatch() {
+    EnumValue::register("Location", &["Home", "Outside"]);
+    EnumValue::register("Weapon", &["Sword", "Bow"]);
+    let home = Datum::Enum(EnumValue::new("Location", 0));
+    let outside = Datum::Enum(EnumValue::new("Location", 1));
+    let bow = Datum::Enum(EnumValue::new("Weapon", 1));
+
+    let start = LocalState::new().with_datum("at_location", home);
+    let actions = [simple_action("go_outside", "at_location", outside)];
+
+    // Bow has the same index as Outside, but is from another enum
+    let goal = Goal::new().with_req("at_location", Compare::Equals(bow));
+    let err = make_plan(&start, &actions, &goal).unwrap_err();
+    assert_eq!(
+        PlanError::TypeMismatch {
+            key: "at_location".to_string(),
+            found: home,
+            other: bow,
+            action: None
+        },
+        err
+    );
+    assert!(err.to_string().contains("Datum:Enum(Weapon::Bow)"));
+
+    let goal = Goal::new().with_req("at_location", Compare::Equals(outside));
+    let plan = make_plan(&start, &actions, &goal).unwrap();
+    assert_eq!(
+        "Datum:Enum(Location::Outside)",
+        plan.final_state().data["at_location"].to_string()
+    );
+}
+
+#[test]
+fn test_budget_exhausted() {
+    let start = LocalState::new().with_datum("gold", Datum::I64(0));
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+    let actions = [simple_increment_action("mine", "gold", Datum::I64(1))];
+
+    let options = PlanOptions::new().with_max_expanded_nodes(5);
+    assert_eq!(
+        Err(PlanError::BudgetExhausted {
+            expanded_nodes: 5,
+            partial: None
+        }),
+        make_plan_with_options(&options, &start, &actions[..], &goal)
+    );
+
+    let options = options.with_strategy(PlanningStrategy::GoalToStart);
+    assert_eq!(
+        Err(PlanError::BudgetExhausted {
+            expanded_nodes: 5,
+            partial: None
+        }),
+        make_plan_with_options(&options, &start, &actions[..], &goal)
+    );
+
+    // Enough budget to reach the goal
+    let options = PlanOptions::new().with_max_expanded_nodes(11);
+    let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+    assert_eq!(10, get_effects_from_plan(plan).len());
+}
+
+#[test]
+fn test_plan_stats() {
+    let start = LocalState::new().with_datum("gold", Datum::I64(0));
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(3)));
+
+    // Both actions lead to the same state, so every other generated state is a duplicate
+    let actions = [
+        simple_increment_action("mine", "gold", Datum::I64(1)),
+        simple_increment_action("dig", "gold", Datum::I64(1)),
+    ];
+
+    let (plan, stats) = make_plan_with_stats(&PlanOptions::new(), &start, &actions[..], &goal);
+    assert_eq!(3, get_effects_from_plan(plan.unwrap()).len());
+    assert_eq!(3, stats.expanded_nodes);
+    assert_eq!(6, stats.generated_nodes);
+    assert_eq!(3, stats.duplicates_pruned);
+    assert_eq!(1, stats.peak_open_set);
+
+    // Stats are there even when no plan was found
+    let options = PlanOptions::new().with_max_expanded_nodes(2);
+    let (plan, stats) = make_plan_with_stats(&options, &start, &actions[..], &goal);
+    assert!(plan.is_err());
+    assert_eq!(2, stats.expanded_nodes);
+}
+
+#[test]
+fn test_deadline_passed() {
+    let start = LocalState::new().with_datum("gold", Datum::I64(0));
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+    let actions = [simple_increment_action("mine", "gold", Datum::I64(1))];
+
+    let options = PlanOptions::new().with_timeout(Duration::ZERO);
+    assert_eq!(
+        Err(PlanError::BudgetExhausted {
+            expanded_nodes: 0,
+            partial: None
+        }),
+        make_plan_with_options(&options, &start, &actions[..], &goal)
+    );
+}
+
+#[test]
+fn test_partial_plan_on_budget_exhausted() {
+    let start = LocalState::new().with_datum("gold", Datum::I64(0));
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+    let actions = [simple_increment_action("mine", "gold", Datum::I64(1))];
+
+    let options = PlanOptions::new()
+        .with_max_expanded_nodes(5)
+        .with_partial_plans(true);
+    let err = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap_err();
+
+    let plan = err.partial_plan().unwrap().clone();
+    let effects = plan.steps;
+    assert_eq!(5, effects.len());
+    assert_eq!(5.0, plan.cost);
+    assert_eq!(
+        LocalState::new().with_datum("gold", Datum::I64(5)),
+        effects.last().unwrap().state
+    );
+}
+
+#[test]
+fn test_partial_plan_on_unreachable_goal() {
+    // We only have enough energy to mine three times, so we can't reach the goal
+    let start = LocalState::new()
+        .with_datum("gold", Datum::I64(0))
+        .with_datum("energy", Datum::I64(3));
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+    let mine = simple_increment_action("mine", "gold", Datum::I64(1))
+        .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(1)))
+        .add_mutator(Mutator::Decrement("energy".to_string(), Datum::I64(1)));
+
+    let actions = [mine];
+
+    let options = PlanOptions::new().with_partial_plans(true);
+    let partial = make_plan_with_options(&options, &start, &actions[..], &goal)
+        .unwrap_err()
+        .into_partial_plan()
+        .unwrap();
+
+    let effects = get_effects_from_plan(partial);
+    assert_eq!(3, effects.len());
+    assert_eq!(
+        Some(&Datum::I64(3)),
+        effects.last().unwrap().state.data.get("gold")
+    );
+
+    // Not asking for partial plans gives us none
+    assert_eq!(
+        Err(PlanError::NoPlanFound { partial: None }),
+        make_plan(&start, &actions[..], &goal)
+    );
+}
+
+#[test]
+fn test_partial_plan_with_zero_heuristic() {
+    // Same as above, the heuristic shouldn't change what counts as closest
+    let start = LocalState::new()
+        .with_datum("gold", Datum::I64(0))
+        .with_datum("energy", Datum::I64(3));
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(10)));
+
+    let mine = simple_increment_action("mine", "gold", Datum::I64(1))
+        .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(1)))
+        .add_mutator(Mutator::Decrement("energy".to_string(), Datum::I64(1)));
+
+    let actions = [mine];
+
+    let options = PlanOptions::new()
+        .with_heuristic(ZeroHeuristic)
+        .with_partial_plans(true);
+    let partial = make_plan_with_options(&options, &start, &actions[..], &goal)
+        .unwrap_err()
+        .into_partial_plan()
+        .unwrap();
+
+    let effects = get_effects_from_plan(partial);
+    assert_eq!(3, effects.len());
+    assert_eq!(
+        Some(&Datum::I64(3)),
+        effects.last().unwrap().state.data.get("gold")
+    );
+}
+
+#[test]
+fn test_explain_no_plan() {
+    let start = LocalState::new()
+        .with_datum("energy", Datum::I64(2))
+        .with_datum("gold", Datum::I64(0))
+        .with_datum("has_pickaxe", Datum::Bool(false))
+        .with_datum("is_rich", Datum::Bool(false));
+    let goal = Goal::new()
+        .with_req("gold", Compare::GreaterThanEquals(Datum::I64(5)))
+        .with_req("is_rich", Compare::Equals(Datum::Bool(true)));
+
+    let actions = [
+        simple_increment_action("beg", "gold", Datum::I64(1))
+            .with_precondition("energy", Compare::GreaterThanEquals(Datum::I64(1)))
+            .add_mutator(Mutator::Decrement("energy".to_string(), Datum::I64(1))),
+        simple_increment_action("mine", "gold", Datum::I64(5))
+            .with_precondition("has_pickaxe", Compare::Equals(Datum::Bool(true))),
+    ];
+
+    let explanation = explain_no_plan(&PlanOptions::new(), &start, &actions[..], &goal)
+        .unwrap()
+        .unwrap();
+
+    assert!(explanation.complete);
+    assert_eq!(
+        vec![("is_rich".to_string(), Compare::Equals(Datum::Bool(true)))],
+        explanation.untouched_requirements
+    );
+    assert_eq!(vec!["mine".to_string()], explanation.never_applicable);
+    assert_eq!(
+        Some(&Datum::I64(2)),
+        explanation.closest_state.data.get("gold")
+    );
+    assert_eq!(2, explanation.unmet_requirements.len());
+
+    // Nothing to explain when there is a plan
+    let goal = Goal::new().with_req("gold", Compare::Equals(Datum::I64(1)));
+    assert_eq!(
+        None,
+        explain_no_plan(&PlanOptions::new(), &start, &actions[..], &goal).unwrap()
+    );
+}
+
+#[test]
+fn test_no_partial_plan_without_progress() {
+    let start = LocalState::new().with_datum("is_hungry", Datum::Bool(true));
+    let goal = Goal::new().with_req("is_hungry", Compare::Equals(Datum::Bool(false)));
+
+    let actions = [simple_action("sleep", "is_hungry", Datum::Bool(true))];
+
+    let options = PlanOptions::new().with_partial_plans(true);
+    assert_eq!(
+        Err(PlanError::NoPlanFound { partial: None }),
+        make_plan_with_options(&options, &start, &actions[..], &goal)
+    );
+}
+
+#[test]
+fn test_key_to_key_comparisons() {
+    let start = LocalState::new()
+        .with_datum("gold", Datum::I64(0))
+        .with_datum("price", Datum::I64(3))
+        .with_datum("has_item", Datum::Bool(false));
+
+    let actions = [
+        simple_increment_action("work", "gold", Datum::I64(1)),
+        simple_action("haggle", "price", Datum::I64(1)),
+        simple_action("buy", "has_item", Datum::Bool(true)).with_precondition(
+            "gold",
+            Compare::to_key(Comparison::GreaterThanEquals, "price"),
+        ),
+    ];
+
+    let buy_goal = Goal::new().with_req("has_item", Compare::Equals(Datum::Bool(true)));
+    // Enough gold for the item, with 2 to spare
+    let save_goal = Goal::new().with_req(
+        "gold",
+        Compare::ToKey(
+            Comparison::GreaterThanEquals,
+            KeyOperand::new("price").with_offset(Datum::I64(2)),
+        ),
+    );
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        for (goal, expected_cost, expected_actions) in [
+            (&buy_goal, 3.0, vec!["buy", "haggle", "work"]),
+            (&save_goal, 4.0, vec!["haggle", "work", "work", "work"]),
+        ] {
+            let plan = make_plan_with_strategy(strategy, &start, &actions[..], goal).unwrap();
+            let effects = plan.steps;
+            let mut actions: Vec<&str> = effects.iter().map(|e| e.action.as_str()).collect();
+            actions.sort();
+            assert_eq!(expected_cost, plan.cost, "{:?}", strategy);
+            assert_eq!(expected_actions, actions, "{:?}", strategy);
+        }
+    }
+
+    let mut state = start.clone();
+    state.data.remove("price");
+    assert_eq!(
+        Err(PlanError::MissingKey {
+            key: "price".to_string(),
+            action: Some("buy".to_string())
+        }),
+        make_plan(&state, &actions[..], &buy_goal)
+    );
+}
+
+#[test]
+fn test_richer_mutators() {
+    let start = LocalState::new()
+        .with_datum("gold", Datum::I64(10))
+        .with_datum("savings", Datum::I64(40))
+        .with_datum("hunger", Datum::F64(3.0))
+        .with_datum("is_lit", Datum::Bool(false));
+
+    let actions = [
+        Action::new("invest").add_mutator(Mutator::Multiply("gold".to_string(), Datum::F64(1.5))),
+        Action::new("withdraw")
+            .add_mutator(Mutator::CopyFrom("gold".to_string(), "savings".to_string()))
+            .set_cost(2.0),
+        Action::new("eat")
+            .add_mutator(Mutator::Decrement("hunger".to_string(), Datum::F64(5.0)))
+            .add_mutator(Mutator::ClampMin("hunger".to_string(), Datum::F64(0.0))),
+        Action::new("light").add_mutator(Mutator::Toggle("is_lit".to_string())),
+    ];
+
+    let goal = Goal::new()
+        .with_req("gold", Compare::GreaterThanEquals(Datum::I64(50)))
+        .with_req("hunger", Compare::Equals(Datum::F64(0.0)))
+        .with_req("is_lit", Compare::Equals(Datum::Bool(true)));
+
+    // Multiply and ClampMin can't be searched backwards, so GoalToStart searches forwards
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new()
+            .with_strategy(strategy)
+            .with_heuristic(AdmissibleHeuristic::new(&actions));
+        let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+        let effects = plan.steps;
+        let mut actions: Vec<&str> = effects.iter().map(|e| e.action.as_str()).collect();
+        actions.sort();
+        assert_eq!(5.0, plan.cost);
+        assert_eq!(vec!["eat", "invest", "light", "withdraw"], actions);
+        let end = &effects.last().unwrap().state;
+        assert_eq!(Datum::I64(60), end.data["gold"]);
+    }
+}
+
+#[test]
+fn test_missing_key_behavior() {
+    // Nothing says how much wood we start with
+    let start = LocalState::new()
+        .with_datum("has_axe", Datum::Bool(true))
+        .with_datum("has_kindling", Datum::Bool(false))
+        .with_datum("has_fire", Datum::Bool(false));
+    let actions = [
+        Action::new("chop")
+            .with_precondition("has_axe", Compare::Equals(Datum::Bool(true)))
+            .add_mutator(Mutator::Increment("wood".to_string(), Datum::I64(2)))
+            .add_mutator(Mutator::Set("has_kindling".to_string(), Datum::Bool(true))),
+        simple_action("light_fire", "has_fire", Datum::Bool(true))
+            .with_precondition("has_kindling", Compare::Equals(Datum::Bool(true))),
+    ];
+    let goal = Goal::new().with_req("has_fire", Compare::Equals(Datum::Bool(true)));
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new().with_strategy(strategy);
+        assert_eq!(
+            Err(PlanError::MissingKey {
+                key: "wood".to_string(),
+                action: Some("chop".to_string())
+            }),
+            make_plan_with_options(&options, &start, &actions[..], &goal)
+        );
+
+        let options = options.with_missing_keys(MissingKeyBehavior::Zero);
+        let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+        assert_eq!(2.0, plan.cost);
+        assert_eq!(
+            Datum::I64(2),
+            plan.final_state().data["wood"],
+            "{:?}",
+            strategy
+        );
+    }
+}
+
+#[test]
+fn test_create_missing_key_from_zero() {
+    // Paying without any gold leaves us in debt, it doesn't give us gold
+    let start = LocalState::new().with_datum("has_food", Datum::Bool(false));
+    let actions = [simple_action("buy_food", "has_food", Datum::Bool(true))
+        .add_mutator(Mutator::Decrement("gold".to_string(), Datum::I64(5)))];
+    let goal = Goal::new().with_req("has_food", Compare::Equals(Datum::Bool(true)));
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new()
+            .with_strategy(strategy)
+            .with_missing_keys(MissingKeyBehavior::Create);
+        let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+        assert_eq!(
+            Datum::I64(-5),
+            plan.final_state().data["gold"],
+            "{:?}",
+            strategy
+        );
+    }
+}
+
+#[test]
+fn test_zero_reads_missing_keys_without_creating_them() {
+    // No alarm has ever gone off, and nobody has thought about noise yet
+    let start = LocalState::new().with_datum("has_food", Datum::Bool(false));
+    let actions = [simple_action("steal_food", "has_food", Datum::Bool(true))
+        .with_precondition("alarm", Compare::Equals(Datum::Bool(false)))
+        .add_mutator(Mutator::Increment("noise".to_string(), Datum::I64(3)))];
+    let goal = Goal::new().with_req("has_food", Compare::Equals(Datum::Bool(true)));
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new()
+            .with_strategy(strategy)
+            .with_missing_keys(MissingKeyBehavior::Zero);
+        let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+        let data = &plan.final_state().data;
+        assert_eq!(Some(&Datum::I64(3)), data.get("noise"), "{:?}", strategy);
+        assert_eq!(None, data.get("alarm"), "{:?}", strategy);
+
+        // Create only adds keys that get changed, so the alarm is still missing
+        let options = options.with_missing_keys(MissingKeyBehavior::Create);
+        assert_eq!(
+            Err(PlanError::MissingKey {
+                key: "alarm".to_string(),
+                action: Some("steal_food".to_string())
+            }),
+            make_plan_with_options(&options, &start, &actions[..], &goal),
+            "{:?}",
+            strategy
+        );
+    }
+}
+
+#[test]
+fn test_state_dependent_costs() {
+    let (home, shop, market) = (
+        Datum::Enum(0.into()),
+        Datum::Enum(1.into()),
+        Datum::Enum(2.into()),
+    );
+    let start = LocalState::new()
+        .with_datum("at_location", home)
+        .with_datum("has_food", Datum::Bool(false));
+    // The shop is far away from home, but close to the market
+    let actions = [
+        simple_action("go_to_shop", "at_location", shop)
+            .set_cost(10.0)
+            .with_cost_when(
+                Condition::compare("at_location", Compare::Equals(market)),
+                0.5,
+            ),
+        simple_action("go_to_market", "at_location", market).set_cost(3.0),
+        simple_action("buy_food", "has_food", Datum::Bool(true))
+            .with_precondition("at_location", Compare::Equals(shop)),
+    ];
+    let goal = Goal::new().with_req("has_food", Compare::Equals(Datum::Bool(true)));
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new().with_strategy(strategy);
+        let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+        let effects = plan.steps;
+        assert_eq!(
+            vec![
+                ("go_to_market", 3.0),
+                ("go_to_shop", 0.5),
+                ("buy_food", 1.0)
+            ],
+            effects
+                .iter()
+                .map(|effect| (effect.action.as_str(), effect.cost))
+                .collect::<Vec<_>>(),
+            "{:?}",
+            strategy
+        );
+        assert_eq!(4.5, plan.cost);
+    }
+
+    // The market turns out to be closed today, so going there takes forever
+    struct ClosedMarket;
+    impl CostModel for ClosedMarket {
+        fn cost(
+            &self,
+            action: &Action,
+            effect: &Effect,
+            state: &SearchState,
+        ) -> Result<f64, PlanError> {
+            match action.key.as_str() {
+                "go_to_market" => Ok(100.0),
+                _ => effect.cost_at(state),
+            }
+        }
+    }
+    let options = PlanOptions::new().with_cost_model(ClosedMarket);
+    let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+    assert_eq!(11.0, plan.cost);
+    assert_eq!(2, plan.steps.len());
+}
+
+#[test]
+fn test_fractional_costs() {
+    let start = LocalState::new().with_datum("energy", Datum::I64(0));
+    // Two naps are cheaper than one sleep
+    let actions = [
+        simple_increment_action("nap", "energy", Datum::I64(5)).set_cost(0.4),
+        simple_increment_action("sleep", "energy", Datum::I64(10)).set_cost(1.0),
+    ];
+    let goal = Goal::new().with_req("energy", Compare::GreaterThanEquals(Datum::I64(10)));
+
+    let options = PlanOptions::new().with_heuristic(AdmissibleHeuristic::new(&actions));
+    let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+    assert_eq!(0.8, plan.cost);
+    assert_eq!(2, plan.steps.len());
+
+    let actions = [simple_increment_action("nap", "energy", Datum::I64(5)).set_cost(f64::NAN)];
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new().with_strategy(strategy);
+        let Err(PlanError::InvalidCost { action, cost }) =
+            make_plan_with_options(&options, &start, &actions[..], &goal)
+        else {
+            panic!("NaN costs should be refused");
+        };
+        assert_eq!("nap", action);
+        assert!(cost.is_nan());
+    }
+}
+
+#[test]
+fn test_outcome_branches() {
+    let start = LocalState::new().with_datum("has_fish", Datum::Bool(false));
+    // Only every fourth try catches something
+    let fish = Action::new("fish")
+        .with_effect(
+            Effect::new("fish")
+                .with_mutator(Mutator::Set("has_fish".to_string(), Datum::Bool(true)))
+                .with_probability(0.25),
+        )
+        .with_effect(Effect::new("fish"));
+    let buy_fish = simple_action("buy_fish", "has_fish", Datum::Bool(true)).set_cost(3.0);
+    let actions = [fish, buy_fish];
+    let goal = Goal::new().with_req("has_fish", Compare::Equals(Datum::Bool(true)));
+    assert_eq!(vec![0.25, 0.75], actions[0].outcome_probabilities());
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        for (outcomes, expected_action, expected_cost) in [
+            (OutcomeMode::BestBranch, "fish", 1.0),
+            (OutcomeMode::ExpectedCost, "buy_fish", 3.0),
+        ] {
+            let options = PlanOptions::new()
+                .with_strategy(strategy)
+                .with_outcomes(outcomes);
+            let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+            let effects = plan.steps;
+            assert_eq!(1, effects.len());
+            assert_eq!(expected_action, effects[0].action, "{:?}", outcomes);
+            assert_eq!(expected_cost, plan.cost, "{:?}", outcomes);
+        }
+    }
+}
+
+#[test]
+fn test_parameterized_actions() {
+    let (house, forest, mine) = (
+        Datum::Enum(0.into()),
+        Datum::Enum(1.into()),
+        Datum::Enum(2.into()),
+    );
+    let start = LocalState::new()
+        .with_datum("at_location", house)
+        .with_datum("has_ore", Datum::Bool(false));
+    // One action for going anywhere, instead of one per location
+    let go_to = Action::new("go_to")
+        .with_parameter("location", [house, forest, mine])
+        .with_precondition(
+            "at_location",
+            Compare::to_key(Comparison::NotEquals, "location"),
+        )
+        .add_mutator(Mutator::CopyFrom(
+            "at_location".to_string(),
+            "location".to_string(),
+        ))
+        // Nothing to do in the house, and the mine is further away
+        .with_precondition("location", Compare::NotEquals(house))
+        .with_cost_when(Condition::compare("location", Compare::Equals(mine)), 3.0);
+    let mine_ore = simple_action("mine_ore", "has_ore", Datum::Bool(true))
+        .with_precondition("at_location", Compare::Equals(mine));
+    let actions = [go_to, mine_ore];
+    let goal = Goal::new().with_req("has_ore", Compare::Equals(Datum::Bool(true)));
+
+    assert_eq!(2, actions[0].ground().unwrap().len());
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new().with_strategy(strategy);
+        let plan = make_plan_with_options(&options, &start, &actions[..], &goal).unwrap();
+        let effects = plan.steps;
+        assert_eq!(2, effects.len());
+        assert_eq!(4.0, plan.cost);
+        assert_eq!("go_to", effects[0].action);
+        assert_eq!(Some(mine), effects[0].argument("location"));
+        assert_eq!(Some(&mine), effects[0].state.data.get("at_location"));
+        assert_eq!("mine_ore", effects[1].action);
+        assert!(effects[1].arguments.is_empty());
+    }
+}
+
+#[test]
+fn test_htn_decomposition() {
+    let start = LocalState::new()
+        .with_datum("gold", Datum::I64(0))
+        .with_datum("ore", Datum::I64(0))
+        .with_datum("has_metal", Datum::Bool(false))
+        .with_datum("has_sword", Datum::Bool(false));
+    let actions = [
+        simple_increment_action("mine_ore", "ore", Datum::I64(1)),
+        simple_action("smelt", "has_metal", Datum::Bool(true))
+            .with_precondition("ore", Compare::GreaterThanEquals(Datum::I64(3))),
+        simple_action("forge", "has_sword", Datum::Bool(true))
+            .with_precondition("has_metal", Compare::Equals(Datum::Bool(true))),
+        simple_action("buy_sword", "has_sword", Datum::Bool(true))
+            .with_precondition("gold", Compare::GreaterThanEquals(Datum::I64(10))),
+    ];
+    let compound_tasks = [
+        CompoundTask::new("craft_sword")
+            .with_method(
+                Method::new("buy")
+                    .with_precondition("gold", Compare::GreaterThanEquals(Datum::I64(10)))
+                    .with_subtask(Task::primitive("buy_sword")),
+            )
+            // Passes the method precondition, but forging without metal fails
+            .with_method(Method::new("just_forge").with_subtask(Task::primitive("forge")))
+            .with_method(
+                Method::new("from_scratch")
+                    .with_subtask(Task::compound("gather_ore"))
+                    .with_subtask(Task::Goal(
+                        Goal::new().with_req("has_metal", Compare::Equals(Datum::Bool(true))),
+                    ))
+                    .with_subtask(Task::primitive("forge")),
+            ),
+        // Keeps mining until there is enough ore
+        CompoundTask::new("gather_ore")
+            .with_method(
+                Method::new("mine_more")
+                    .with_precondition("ore", Compare::LessThan(Datum::I64(3)))
+                    .with_subtask(Task::primitive("mine_ore"))
+                    .with_subtask(Task::compound("gather_ore")),
+            )
+            .with_method(Method::new("enough")),
+    ];
+    let tasks = [Task::compound("craft_sword")];
+
+    let plan = make_htn_plan(
+        &PlanOptions::new(),
+        &start,
+        &actions,
+        &compound_tasks,
+        &tasks,
+    )
+    .unwrap();
+    let effects = plan.steps;
+    let names: Vec<&str> = effects.iter().map(|e| e.action.as_str()).collect();
+    assert_eq!(
+        vec!["mine_ore", "mine_ore", "mine_ore", "smelt", "forge"],
+        names
+    );
+    assert_eq!(5.0, plan.cost);
+    assert_eq!(
+        Some(&Datum::Bool(true)),
+        effects.last().unwrap().state.data.get("has_sword")
+    );
+
+    let rich = start.clone().with_datum("gold", Datum::I64(10));
+    let plan = make_htn_plan(
+        &PlanOptions::new(),
+        &rich,
+        &actions,
+        &compound_tasks,
+        &tasks,
+    )
+    .unwrap();
+    assert_eq!("buy_sword", plan.steps[0].action);
+
+    let Err(PlanError::UnknownTask { task }) = make_htn_plan(
+        &PlanOptions::new(),
+        &start,
+        &actions,
+        &compound_tasks,
+        &[Task::compound("craft_shield")],
+    ) else {
+        panic!("craft_shield doesn't exist");
+    };
+    assert_eq!("craft_shield", task);
+
+    // A primitive task has nothing to fall back to when its preconditions aren't met
+    let Err(PlanError::NoPlanFound { .. }) = make_htn_plan(
+        &PlanOptions::new(),
+        &start,
+        &actions,
+        &compound_tasks[..1],
+        &[Task::primitive("forge")],
+    ) else {
+        panic!("forging without metal should fail");
+    };
+}
+
+#[test]
+fn test_repair_plan() {
+    let start = LocalState::new()
+        .with_datum("has_food", Datum::Bool(false))
+        .with_datum("has_fuel", Datum::Bool(true))
+        .with_datum("has_meal", Datum::Bool(false))
+        .with_datum("is_fed", Datum::Bool(false));
+    let actions = vec![
+        simple_action("get_food", "has_food", Datum::Bool(true)),
+        simple_action("buy_fuel", "has_fuel", Datum::Bool(true)).set_cost(5.0),
+        simple_action("cook", "has_meal", Datum::Bool(true))
+            .with_precondition("has_food", Compare::Equals(Datum::Bool(true)))
+            .with_precondition("has_fuel", Compare::Equals(Datum::Bool(true))),
+        simple_action("eat", "is_fed", Datum::Bool(true))
+            .with_precondition("has_meal", Compare::Equals(Datum::Bool(true))),
+    ];
+    let goal = Goal::new().with_req("is_fed", Compare::Equals(Datum::Bool(true)));
+    let options = PlanOptions::new();
+    let previous = make_plan(&start, &actions, &goal).unwrap();
+    let names =
+        |plan: &Plan| -> Vec<String> { plan.steps.iter().map(|e| e.action.clone()).collect() };
+    assert_eq!(vec!["get_food", "cook", "eat"], names(&previous));
+
+    // Nothing changed, so the plan stays the same without searching
+    let (plan, stats) = repair_plan_with_stats(&options, &previous, &start, &actions, &goal);
+    assert_eq!(previous, plan.unwrap());
+    assert_eq!(0, stats.expanded_nodes);
+
+    // The food was gotten already, so only the rest of the plan is left
+    let got_food = start.clone().with_datum("has_food", Datum::Bool(true));
+    let (plan, stats) = repair_plan_with_stats(&options, &previous, &got_food, &actions, &goal);
+    let plan = plan.unwrap();
+    assert_eq!(vec!["cook", "eat"], names(&plan));
+    assert_eq!(2.0, plan.cost);
+    assert_eq!(&got_food, &plan.start);
+    assert_eq!(0, stats.expanded_nodes);
+
+    // The fuel ran out after getting the food, so fuel has to be bought before cooking
+    let no_fuel = got_food.clone().with_datum("has_fuel", Datum::Bool(false));
+    let plan = repair_plan(&options, &previous, &no_fuel, &actions, &goal).unwrap();
+    assert_eq!(vec!["buy_fuel", "cook", "eat"], names(&plan));
+    assert_eq!(7.0, plan.cost);
+    assert_eq!(Ok(()), plan.validate(&no_fuel, &actions));
+
+    // Same when it ran out before getting the food, keeping the steps in front of it
+    let no_fuel = start.clone().with_datum("has_fuel", Datum::Bool(false));
+    let plan = repair_plan(&options, &previous, &no_fuel, &actions, &goal).unwrap();
+    assert_eq!(vec!["get_food", "buy_fuel", "cook", "eat"], names(&plan));
+    assert_eq!(8.0, plan.cost);
+
+    // Without a way to cook, the meal has to come from somewhere else entirely
+    let takeout = vec![
+        actions[0].clone(),
+        simple_action("order_meal", "has_meal", Datum::Bool(true)).set_cost(3.0),
+        actions[3].clone(),
+    ];
+    let plan = repair_plan(&options, &previous, &start, &takeout, &goal).unwrap();
+    assert_eq!(vec!["order_meal", "eat"], names(&plan));
+
+    // The goal is reached already
+    let fed = start.clone().with_datum("is_fed", Datum::Bool(true));
+    let plan = repair_plan(&options, &previous, &fed, &actions, &goal).unwrap();
+    assert!(plan.steps.is_empty());
+}
+
+#[test]
+fn test_quantized_floats() {
+    // 0.1 + 0.2 is 0.30000000000000004, so without quantization the goal is never reached
+    // exactly, and every slightly different sum is a new state to explore
+    let start = LocalState::new().with_datum("hunger", Datum::F64(0.0));
+    let actions = [
+        simple_increment_action("snack", "hunger", Datum::F64(0.1)),
+        simple_increment_action("meal", "hunger", Datum::F64(0.2)),
+    ];
+    let goal = Goal::new().with_req("hunger", Compare::Equals(Datum::F64(0.3)));
+
+    let options = PlanOptions::new().with_max_expanded_nodes(1000);
+    let Err(PlanError::BudgetExhausted { .. }) =
+        make_plan_with_options(&options, &start, &actions, &goal)
+    else {
+        panic!("floats that don't add up exactly should never reach the goal");
+    };
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = options
+            .clone()
+            .with_strategy(strategy)
+            .with_quantization_step("hunger", 0.1);
+        let (plan, stats) = make_plan_with_stats(&options, &start, &actions, &goal);
+        let plan = plan.unwrap();
+        assert_eq!(2, plan.steps.len());
+        assert_eq!(2.0, plan.cost);
+        assert_eq!(
+            Some(&Datum::F64(0.3).quantize(0.1)),
+            plan.final_state().data.get("hunger")
+        );
+        // Different orders of the same actions lead to the same state
+        assert!(stats.duplicates_pruned > 0);
+        // Backwards search can't round, so it searched forwards
+        assert_eq!(
+            strategy == PlanningStrategy::GoalToStart,
+            stats.searched_forward_instead
+        );
+    }
+
+    // Planning and repairing both give back the start as it was, not rounded
+    let options = options.with_quantization_step("hunger", 0.1);
+    let start = LocalState::new().with_datum("hunger", Datum::F64(0.01));
+    let plan = make_plan_with_options(&options, &start, &actions, &goal).unwrap();
+    assert_eq!(start, plan.start);
+    let repaired = repair_plan(&options, &plan, &start, &actions, &goal).unwrap();
+    assert_eq!(plan.steps, repaired.steps);
+    assert_eq!(start, repaired.start);
+}
+
+#[test]
+fn test_symbol_datums() {
+    // Item names straight from a data file, planned with without giving each a number
+    let start = LocalState::new()
+        .with_datum("holding", Datum::symbol("nothing"))
+        .with_datum("has_ingot", Datum::Bool(false));
+    let actions = [
+        simple_action("pick_up_iron_ore", "holding", Datum::symbol("iron_ore")),
+        simple_action("pick_up_stick", "holding", Datum::symbol("stick")),
+        simple_action("smelt", "has_ingot", Datum::Bool(true)).with_precondition(
+            "holding",
+            Compare::OneOf(vec![Datum::symbol("iron_ore"), Datum::symbol("gold_ore")]),
+        ),
+    ];
+    let goal = Goal::new().with_req("has_ingot", Compare::Equals(Datum::Bool(true)));
+
+    for strategy in [PlanningStrategy::StartToGoal, PlanningStrategy::GoalToStart] {
+        let options = PlanOptions::new().with_strategy(strategy);
+        let plan = make_plan_with_options(&options, &start, &actions, &goal).unwrap();
+        let keys: Vec<&str> = plan.steps.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(vec!["pick_up_iron_ore", "smelt"], keys);
+        assert_eq!(
+            Some(&Datum::Symbol(Symbol::new("iron_ore"))),
+            plan.final_state().data.get("holding")
+        );
+    }
+    assert_eq!(
+        "Datum:Symbol(iron_ore)",
+        Datum::symbol("iron_ore").to_string()
+    );
+}
@@ -2,7 +2,7 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{parse_macro_input, Data, DeriveInput, Fields};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
 
 /// ActionComponent allows you to create Actions directly from your action struct
 ///
@@ -29,7 +29,16 @@ pub fn action_component_derive(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
-#[proc_macro_derive(DatumComponent)]
+/// DatumComponent turns a tuple struct with one `bool`, `i64`, `f64`, `usize` or `Symbol`
+/// into a [`Datum`] that can be planned with
+///
+/// Symbols are only equal or not, so `Symbol` fields only get `set`, `copy_from`, `is`,
+/// `is_not` and `is_one_of`, the same as an [`EnumComponent`]. Trying to increase them or
+/// compare them with "greater" or "less" doesn't compile.
+///
+/// `f64` fields can be quantized with `#[datum(step = 0.01)]`, which makes the planner treat
+/// values that round to the same multiple of the step as the same value
+#[proc_macro_derive(DatumComponent, attributes(datum))]
 pub fn datum_component_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -44,8 +53,12 @@ pub fn datum_component_derive(input: TokenStream) -> TokenStream {
                     let variant = match ty.to_token_stream().to_string().as_str() {
                         "bool" => quote! { Datum::Bool },
                         "f64" => quote! { Datum::F64 },
-                        "usize" => quote! { Datum::Enum },
+                        // Plain indices aren't from any enum in particular
+                        "usize" => {
+                            quote! { (|index: usize| Datum::Enum(EnumValue::untyped(index))) }
+                        }
                         "i64" => quote! { Datum::I64 },
+                        "Symbol" => quote! { Datum::Symbol },
                         _ => panic!("Unsupported type for DatumComponent"),
                     };
                     (ty, variant)
@@ -59,6 +72,51 @@ pub fn datum_component_derive(input: TokenStream) -> TokenStream {
         _ => panic!("Expected a struct"),
     };
 
+    let quantization_step = match datum_step(&input.attrs) {
+        Some(step) => {
+            if field_type.to_token_stream().to_string() != "f64" {
+                panic!("Only f64 fields can have a quantization step");
+            }
+            quote! {
+                fn quantization_step(&self) -> Option<f64> {
+                    Some(#step)
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    let helpers = if field_type.to_token_stream().to_string() == "Symbol" {
+        quote! {
+            #[allow(dead_code)]
+            impl #name {
+                pub fn key() -> String {
+                    #snake_case_name.to_owned()
+                }
+
+                pub fn set(val: Symbol) -> Mutator {
+                    Mutator::Set(#snake_case_name.to_string(), Datum::Symbol(val))
+                }
+                /// Sets the value to whatever another key has
+                pub fn copy_from(key: &str) -> Mutator {
+                    Mutator::CopyFrom(#snake_case_name.to_string(), key.to_string())
+                }
+
+                pub fn is(val: Symbol) -> (String, Compare) {
+                    (#snake_case_name.to_string(), Compare::Equals(Datum::Symbol(val)))
+                }
+                pub fn is_not(val: Symbol) -> (String, Compare) {
+                    (#snake_case_name.to_string(), Compare::NotEquals(Datum::Symbol(val)))
+                }
+                pub fn is_one_of(vals: Vec<Symbol>) -> (String, Compare) {
+                    (#snake_case_name.to_string(), Compare::OneOf(vals.into_iter().map(Datum::Symbol).collect()))
+                }
+            }
+        }
+    } else {
+        ordered_helpers(name, &snake_case_name, field_type, &field_enum_variant)
+    };
+
     let gen = quote! {
 
         impl DatumComponent for #name {
@@ -69,8 +127,24 @@ pub fn datum_component_derive(input: TokenStream) -> TokenStream {
             fn field_value(&self) -> Datum {
                 #field_enum_variant(self.0)
             }
+
+            #quantization_step
         }
 
+        #helpers
+    };
+    gen.into()
+}
+
+/// The [`MutatorTrait`] and [`Precondition`] helpers of a DatumComponent with a value that
+/// can be ordered and counted with
+fn ordered_helpers(
+    name: &syn::Ident,
+    snake_case_name: &str,
+    field_type: &syn::Type,
+    field_enum_variant: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    quote! {
         impl #name {
             pub fn key() -> String {
                 #snake_case_name.to_owned()
@@ -87,6 +161,24 @@ pub fn datum_component_derive(input: TokenStream) -> TokenStream {
             fn decrease(val: #field_type) -> Mutator {
                 Mutator::Decrement(#snake_case_name.to_string(), #field_enum_variant(val))
             }
+            fn multiply(val: #field_type) -> Mutator {
+                Mutator::Multiply(#snake_case_name.to_string(), #field_enum_variant(val))
+            }
+            fn divide(val: #field_type) -> Mutator {
+                Mutator::Divide(#snake_case_name.to_string(), #field_enum_variant(val))
+            }
+            fn clamp_min(val: #field_type) -> Mutator {
+                Mutator::ClampMin(#snake_case_name.to_string(), #field_enum_variant(val))
+            }
+            fn clamp_max(val: #field_type) -> Mutator {
+                Mutator::ClampMax(#snake_case_name.to_string(), #field_enum_variant(val))
+            }
+            fn toggle() -> Mutator {
+                Mutator::Toggle(#snake_case_name.to_string())
+            }
+            fn copy_from(key: &str) -> Mutator {
+                Mutator::CopyFrom(#snake_case_name.to_string(), key.to_string())
+            }
         }
 
         impl Precondition<#field_type> for #name {
@@ -102,23 +194,62 @@ pub fn datum_component_derive(input: TokenStream) -> TokenStream {
             fn is_less(val: #field_type) -> (String, Compare) {
                 (#snake_case_name.to_string(), Compare::LessThanEquals(#field_enum_variant(val)))
             }
+            fn is_greater_than(val: #field_type) -> (String, Compare) {
+                (#snake_case_name.to_string(), Compare::GreaterThan(#field_enum_variant(val)))
+            }
+            fn is_less_than(val: #field_type) -> (String, Compare) {
+                (#snake_case_name.to_string(), Compare::LessThan(#field_enum_variant(val)))
+            }
+            fn is_in_range(min: #field_type, max: #field_type) -> (String, Compare) {
+                (#snake_case_name.to_string(), Compare::InRange(#field_enum_variant(min), #field_enum_variant(max)))
+            }
+            fn is_one_of(vals: Vec<#field_type>) -> (String, Compare) {
+                (#snake_case_name.to_string(), Compare::OneOf(vals.into_iter().map(#field_enum_variant).collect()))
+            }
         }
-    };
-    gen.into()
+    }
+}
+
+/// The `step` from `#[datum(step = ...)]`, if there is one
+fn datum_step(attrs: &[Attribute]) -> Option<f64> {
+    let mut step = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("datum")) {
+        let Ok(Meta::List(list)) = attr.parse_meta() else {
+            panic!("Expected #[datum(step = ...)]");
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("step") =>
+                {
+                    let parsed = match &name_value.lit {
+                        Lit::Float(lit) => lit.base10_parse(),
+                        Lit::Int(lit) => lit.base10_parse(),
+                        _ => panic!("Expected a number as the step"),
+                    };
+                    step = Some(parsed.expect("Expected a number as the step"));
+                }
+                _ => panic!("Expected #[datum(step = ...)]"),
+            }
+        }
+    }
+    step
 }
 
 /// EnumComponent is specifically for DatumComponent's that use an Enum/EnumDatum
 ///
-/// They're special in the way they'll refuse to be incremented/decremented
-/// and compared with "greater" or "less".
+/// Enums have no order, so only `set`, `copy_from`, `is`, `is_not` and `is_one_of` are
+/// generated for them. Trying to increment/decrement them or compare them with "greater"
+/// or "less" doesn't compile.
 ///
-/// It also handles the `as usize` for you, gives type safety.
+/// It also turns the enum into a [`Datum`] for you, which remembers what enum it came from,
+/// so it can't be compared with a variant of another enum by accident.
 ///
 /// Example:
 ///
 /// ```ignore
 /// #[derive(EnumDatum)]
-/// struct Location {
+/// enum Location {
 ///     Home,
 ///     Outside
 /// }
@@ -129,13 +260,13 @@ pub fn datum_component_derive(input: TokenStream) -> TokenStream {
 /// // Used as a Mutator:
 /// assert_eq!(
 ///     AtLocation::set(Location::Home),
-///     Mutator::Increment("at_location".to_string(), Datum::Enum(Location::Home as usize))
+///     Mutator::Set("at_location".to_string(), Location::Home.datum())
 /// );
 ///
 /// // Used as a Precondition:
 /// assert_eq!(
 ///     AtLocation::is(Location::Outside),
-///     ("at_location".to_string(), Compare::Equals(Datum::Enum(Location::Outside as usize)))
+///     ("at_location".to_string(), Compare::Equals(Location::Outside.datum()))
 /// )
 /// ```
 #[proc_macro_derive(EnumComponent)]
@@ -145,7 +276,6 @@ pub fn enum_component_derive(input: TokenStream) -> TokenStream {
     let name = &input.ident;
     let snake_case_name = to_snake_case(&name.to_string());
 
-    let field_enum_variant = quote! { Datum::Enum };
     let field_type = match &input.data {
         Data::Struct(data_struct) => {
             if let Fields::Unnamed(fields) = &data_struct.fields {
@@ -169,40 +299,32 @@ pub fn enum_component_derive(input: TokenStream) -> TokenStream {
             }
 
             fn field_value(&self) -> Datum {
-                #field_enum_variant(self.0 as usize)
+                EnumDatum::datum(self.0)
             }
         }
 
+        #[allow(dead_code)]
         impl #name {
             pub fn key() -> String {
                 #snake_case_name.to_owned()
             }
-        }
 
-        impl MutatorTrait<#field_type> for #name {
-            fn set(val: #field_type) -> Mutator {
-                Mutator::Set(#snake_case_name.to_string(), #field_enum_variant(val as usize))
-            }
-            fn increase(val: #field_type) -> Mutator {
-                panic!("You cannot call .increase on a Enum!")
+            pub fn set(val: #field_type) -> Mutator {
+                Mutator::Set(#snake_case_name.to_string(), EnumDatum::datum(val))
             }
-            fn decrease(val: #field_type) -> Mutator {
-                panic!("You cannot call .increase on a Enum!")
+            /// Sets the value to whatever another key has
+            pub fn copy_from(key: &str) -> Mutator {
+                Mutator::CopyFrom(#snake_case_name.to_string(), key.to_string())
             }
-        }
 
-        impl Precondition<#field_type> for #name {
-            fn is(val: #field_type) -> (String, Compare) {
-                (#snake_case_name.to_string(), Compare::Equals(#field_enum_variant(val as usize)))
+            pub fn is(val: #field_type) -> (String, Compare) {
+                (#snake_case_name.to_string(), Compare::Equals(EnumDatum::datum(val)))
             }
-            fn is_not(val: #field_type) -> (String, Compare) {
-                (#snake_case_name.to_string(), Compare::NotEquals(#field_enum_variant(val as usize)))
-            }
-            fn is_more(val: #field_type) -> (String, Compare) {
-                panic!("You cannot call .is_more on a Enum!")
+            pub fn is_not(val: #field_type) -> (String, Compare) {
+                (#snake_case_name.to_string(), Compare::NotEquals(EnumDatum::datum(val)))
             }
-            fn is_less(val: #field_type) -> (String, Compare) {
-                panic!("You cannot call .is_less on a Enum!")
+            pub fn is_one_of(vals: Vec<#field_type>) -> (String, Compare) {
+                (#snake_case_name.to_string(), Compare::OneOf(vals.into_iter().map(EnumDatum::datum).collect()))
             }
         }
     };
@@ -211,16 +333,53 @@ pub fn enum_component_derive(input: TokenStream) -> TokenStream {
 
 /// EnumDatum implements EnumDatum trait so you can use it with an EnumComponent
 ///
+/// The [`Datum`] of a variant knows the enum it's from, by its full path, and the names of
+/// the variants, so it prints as `Location::Home`. The names are registered with
+/// `EnumValue::register` the first time a variant is turned into a [`Datum`]. Variants are
+/// told apart by their position in the enum, regardless of their discriminants
+///
 /// See docs for [`EnumComponent`] for example usage
 #[proc_macro_derive(EnumDatum)]
 pub fn enum_datum_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
 
+    let Data::Enum(data_enum) = &input.data else {
+        panic!("Expected an enum")
+    };
+    let variants: Vec<String> = data_enum
+        .variants
+        .iter()
+        .map(|variant| variant.ident.to_string())
+        .collect();
+    let arms = data_enum
+        .variants
+        .iter()
+        .enumerate()
+        .map(|(index, variant)| {
+            let ident = &variant.ident;
+            let pattern = match &variant.fields {
+                Fields::Unit => quote! { Self::#ident },
+                Fields::Unnamed(_) => quote! { Self::#ident(..) },
+                Fields::Named(_) => quote! { Self::#ident { .. } },
+            };
+            quote! { #pattern => #index, }
+        });
+
     let gen = quote! {
         impl EnumDatum for #name {
             fn datum(self) -> Datum {
-                Datum::Enum(self as usize)
+                // Looking the name up in the symbol table every time would take a lock
+                static ENUM_NAME: std::sync::OnceLock<Symbol> = std::sync::OnceLock::new();
+                let enum_name = *ENUM_NAME.get_or_init(|| {
+                    let enum_name = concat!(module_path!(), "::", stringify!(#name));
+                    EnumValue::register(enum_name, &[#(#variants),*]);
+                    Symbol::new(enum_name)
+                });
+                let index: usize = match self {
+                    #(#arms)*
+                };
+                Datum::Enum(EnumValue::from_symbol(enum_name, index))
             }
         }
     };